@@ -1,25 +1,41 @@
+use std::{collections::BTreeMap, str::FromStr};
+
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use derive_new::new;
 use reqwest::Client;
+use rust_decimal::Decimal;
+use serde_json::Value;
+
+use crate::{
+    app::utils::parse_datetime,
+    models::{Asset, Quote, Ticker, ticker::ApiProvider},
+};
 
 use super::{
-    av_dto::{AvGlobalQuoteDto, AvSymbolSearchDto},
+    av_dto::{AvDailyQuoteDto, AvGlobalQuoteDto, AvOverviewDto, AvSymbolSearchDto},
+    provider::QuoteProvider,
     utils::{make_request, parse_response_array, parse_response_object},
 };
 
 const BASE_URL: &str = "https://www.alphavantage.co";
 
-pub async fn get_quote(symbol: &str, client: &Client, api_key: &str) -> Result<AvGlobalQuoteDto> {
-    let params = format!("function=GLOBAL_QUOTE&symbol={}&apikey={}", symbol, api_key);
-    let res = make_request(client, BASE_URL, "query", &params).await?;
-
+fn check_rate_limit(res: &Value) -> Result<()> {
     if let Some(Ok(note)) = res
         .get("Information")
         .map(|v| serde_json::from_value::<String>(v.clone()))
+        && note.to_lowercase().contains("rate limit")
     {
-        if note.to_lowercase().contains("rate limit") {
-            return Err(anyhow::anyhow!("Rate limit exceeded"));
-        }
+        return Err(anyhow::anyhow!("Rate limit exceeded"));
     }
+    Ok(())
+}
+
+pub async fn get_quote(symbol: &str, client: &Client, api_key: &str) -> Result<AvGlobalQuoteDto> {
+    let params = format!("function=GLOBAL_QUOTE&symbol={}&apikey={}", symbol, api_key);
+    let res = make_request(client, BASE_URL, "query", &params).await?;
+    check_rate_limit(&res)?;
 
     let global_quote = res
         .get("Global Quote")
@@ -32,6 +48,38 @@ pub async fn get_quote(symbol: &str, client: &Client, api_key: &str) -> Result<A
     .await
 }
 
+pub async fn get_quote_history(
+    symbol: &str,
+    client: &Client,
+    api_key: &str,
+) -> Result<BTreeMap<String, AvDailyQuoteDto>> {
+    let params = format!(
+        "function=TIME_SERIES_DAILY&symbol={}&outputsize=full&apikey={}",
+        symbol, api_key
+    );
+    let res = make_request(client, BASE_URL, "query", &params).await?;
+    check_rate_limit(&res)?;
+
+    let time_series = res
+        .get("Time Series (Daily)")
+        .with_context(|| "Failed to find 'Time Series (Daily)' in the response")?;
+
+    serde_json::from_value::<BTreeMap<String, AvDailyQuoteDto>>(time_series.clone())
+        .with_context(|| format!("Failed to parse Alpha Vantage quote history for {}", symbol))
+}
+
+pub async fn get_overview(symbol: &str, client: &Client, api_key: &str) -> Result<AvOverviewDto> {
+    let params = format!("function=OVERVIEW&symbol={}&apikey={}", symbol, api_key);
+    let res = make_request(client, BASE_URL, "query", &params).await?;
+    check_rate_limit(&res)?;
+
+    parse_response_object::<AvOverviewDto>(
+        res,
+        &format!("Failed to parse Alpha Vantage overview for {}", symbol),
+    )
+    .await
+}
+
 pub async fn search_symbol(
     symbol: &str,
     client: &Client,
@@ -53,3 +101,77 @@ pub async fn search_symbol(
     )
     .await
 }
+
+#[derive(Clone, Debug, new)]
+pub struct AlphaVantageProvider {
+    api_key: Option<String>,
+}
+
+impl AlphaVantageProvider {
+    fn api_key(&self) -> Result<&str> {
+        self.api_key
+            .as_deref()
+            .with_context(|| "ALPHA_VANTAGE_API_KEY is not set")
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for AlphaVantageProvider {
+    fn api(&self) -> ApiProvider {
+        ApiProvider::AlphaVantage
+    }
+
+    async fn search_symbol(&self, symbol: &str, client: &Client) -> Result<Ticker> {
+        let search_result = search_symbol(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Alpha Vantage ({})", symbol))?;
+        let first = search_result
+            .first()
+            .with_context(|| "Failed to get first value")?;
+        Ok(first.to_ticker())
+    }
+
+    async fn get_latest_quote(&self, symbol: &str, client: &Client) -> Result<Quote> {
+        let quote = get_quote(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Alpha Vantage ({})", symbol))?;
+        let price = Decimal::from_str(quote.price())
+            .with_context(|| format!("Alpha Vantage ({}): Failed to parse price", symbol))?;
+        let date = parse_datetime(quote.latest_trading_day())
+            .with_context(|| format!("Alpha Vantage ({}): Failed to parse date", symbol))?;
+        Ok(Quote::new(symbol.to_string(), date, price, price))
+    }
+
+    async fn get_quote_history(
+        &self,
+        symbol: &str,
+        start_date: &DateTime<Local>,
+        end_date: &DateTime<Local>,
+        client: &Client,
+    ) -> Result<Vec<Quote>> {
+        let history = get_quote_history(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Alpha Vantage ({})", symbol))?;
+
+        let start = start_date.format("%Y-%m-%d").to_string();
+        let end = end_date.format("%Y-%m-%d").to_string();
+
+        let mut quotes = Vec::new();
+        for (date_str, daily) in history.range(start..=end) {
+            let close = Decimal::from_str(daily.close()).with_context(|| {
+                format!("Alpha Vantage ({}): Failed to parse close price", symbol)
+            })?;
+            let date = parse_datetime(date_str)?;
+            quotes.push(Quote::new(symbol.to_string(), date, close, close));
+        }
+
+        Ok(quotes)
+    }
+
+    async fn get_metadata(&self, symbol: &str, client: &Client) -> Result<Asset> {
+        let overview = get_overview(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Alpha Vantage ({})", symbol))?;
+        Ok(overview.to_asset())
+    }
+}
@@ -2,7 +2,7 @@ use derive_getters::Getters;
 use derive_new::new;
 use serde::Deserialize;
 
-use crate::models::{Ticker, ticker::ApiProvider};
+use crate::models::{Asset, AssetType, Ticker, ticker::ApiProvider};
 
 #[derive(Debug, Deserialize, Getters, new)]
 #[serde(rename_all = "camelCase")]
@@ -67,3 +67,46 @@ impl AvSymbolSearchDto {
         )
     }
 }
+
+#[derive(Debug, Deserialize, Getters, new)]
+pub struct AvDailyQuoteDto {
+    #[serde(rename = "1. open")]
+    open: String,
+    #[serde(rename = "2. high")]
+    high: String,
+    #[serde(rename = "3. low")]
+    low: String,
+    #[serde(rename = "4. close")]
+    close: String,
+    #[serde(rename = "5. volume")]
+    volume: String,
+}
+
+#[derive(Debug, Deserialize, Getters, new)]
+#[serde(rename_all = "PascalCase")]
+pub struct AvOverviewDto {
+    symbol: String,
+    asset_type: Option<String>,
+    name: String,
+    currency: Option<String>,
+    sector: Option<String>,
+    industry: Option<String>,
+}
+
+impl AvOverviewDto {
+    pub fn to_asset(&self) -> Asset {
+        let asset_type = match self.asset_type.as_deref() {
+            Some("ETF") => AssetType::ETF,
+            Some("Mutual Fund") => AssetType::MutualFund,
+            _ => AssetType::Stock,
+        };
+        Asset::new(
+            0,
+            self.name.clone(),
+            asset_type,
+            None,
+            self.sector.clone(),
+            self.industry.clone(),
+        )
+    }
+}
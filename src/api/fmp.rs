@@ -1,8 +1,17 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use derive_new::new;
 use reqwest::Client;
 
+use crate::{
+    app::utils::parse_datetime,
+    models::{Asset, Quote, Ticker, ticker::ApiProvider},
+};
+
 use super::{
-    fmp_dto::{FmpQuoteDto, FmpQuoteHistoryDto, FmpSearchSymbolDto},
+    fmp_dto::{FmpProfileDto, FmpQuoteDto, FmpQuoteHistoryDto, FmpSearchSymbolDto},
+    provider::QuoteProvider,
     utils::{make_request, parse_response_array},
 };
 
@@ -47,3 +56,106 @@ pub async fn get_quote_history(
     )
     .await
 }
+
+pub async fn get_profile(
+    symbol: &str,
+    client: &Client,
+    api_key: &str,
+) -> Result<Vec<FmpProfileDto>> {
+    let params = format!("symbol={}&apikey={}", symbol, api_key);
+    let res = make_request(client, BASE_URL, "profile", &params).await?;
+    parse_response_array::<FmpProfileDto>(
+        res,
+        &format!("Failed to parse FMP profile for {}", symbol),
+    )
+    .await
+}
+
+#[derive(Clone, Debug, new)]
+pub struct FmpProvider {
+    api_key: Option<String>,
+}
+
+impl FmpProvider {
+    fn api_key(&self) -> Result<&str> {
+        self.api_key
+            .as_deref()
+            .with_context(|| "FMP_API_KEY is not set")
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for FmpProvider {
+    fn api(&self) -> ApiProvider {
+        ApiProvider::Fmp
+    }
+
+    async fn search_symbol(&self, symbol: &str, client: &Client) -> Result<Ticker> {
+        let search_result = search_symbol(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("FMP ({})", symbol))?;
+        let first = search_result
+            .first()
+            .with_context(|| "Failed to get first value")?;
+        Ok(first.to_ticker())
+    }
+
+    async fn get_latest_quote(&self, symbol: &str, client: &Client) -> Result<Quote> {
+        let quote_result = get_quote(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("FMP ({})", symbol))?;
+        let first = quote_result
+            .first()
+            .with_context(|| format!("FMP ({}): Failed to get first entry", symbol))?;
+        let date = DateTime::from_timestamp(*first.timestamp(), 0)
+            .with_context(|| format!("FMP ({}): Failed to parse timestamp", symbol))?
+            .with_timezone(&Local);
+        Ok(Quote::new(
+            symbol.to_string(),
+            date,
+            *first.price(),
+            *first.price(),
+        ))
+    }
+
+    async fn get_quote_history(
+        &self,
+        symbol: &str,
+        start_date: &DateTime<Local>,
+        end_date: &DateTime<Local>,
+        client: &Client,
+    ) -> Result<Vec<Quote>> {
+        let history = get_quote_history(
+            symbol,
+            &start_date.format("%Y-%m-%d").to_string(),
+            &end_date.format("%Y-%m-%d").to_string(),
+            client,
+            self.api_key()?,
+        )
+        .await
+        .with_context(|| format!("FMP ({})", symbol))?;
+
+        let mut quotes = Vec::new();
+        for entry in history.iter().rev() {
+            let date = parse_datetime(entry.date())?;
+            quotes.push(Quote::new(
+                symbol.to_string(),
+                date,
+                *entry.price(),
+                *entry.price(),
+            ));
+        }
+
+        Ok(quotes)
+    }
+
+    async fn get_metadata(&self, symbol: &str, client: &Client) -> Result<Asset> {
+        let profile = get_profile(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("FMP ({})", symbol))?;
+        let first = profile
+            .first()
+            .with_context(|| format!("FMP ({}): Failed to get first entry", symbol))?;
+        Ok(first.to_asset())
+    }
+}
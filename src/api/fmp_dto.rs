@@ -3,7 +3,7 @@ use derive_new::new;
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use crate::models::{Ticker, ticker::ApiProvider};
+use crate::models::{Asset, AssetType, Ticker, ticker::ApiProvider};
 
 #[derive(Debug, Deserialize, Getters, new)]
 #[serde(rename_all = "camelCase")]
@@ -61,3 +61,38 @@ impl FmpSearchSymbolDto {
         )
     }
 }
+
+#[derive(Debug, Deserialize, Getters, new)]
+#[serde(rename_all = "camelCase")]
+pub struct FmpProfileDto {
+    symbol: String,
+    company_name: String,
+    currency: Option<String>,
+    isin: Option<String>,
+    sector: Option<String>,
+    industry: Option<String>,
+    is_etf: Option<bool>,
+    is_fund: Option<bool>,
+}
+
+impl FmpProfileDto {
+    pub fn to_asset(&self) -> Asset {
+        let asset_type = if self.is_etf.unwrap_or(false) {
+            AssetType::ETF
+        } else if self.is_fund.unwrap_or(false) {
+            AssetType::MutualFund
+        } else {
+            AssetType::Stock
+        };
+        Asset::new(
+            0,
+            self.company_name.clone(),
+            asset_type,
+            self.isin.clone().filter(|isin| !isin.is_empty()),
+            self.sector.clone().filter(|sector| !sector.is_empty()),
+            self.industry
+                .clone()
+                .filter(|industry| !industry.is_empty()),
+        )
+    }
+}
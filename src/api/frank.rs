@@ -1,8 +1,12 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
 use reqwest::Client;
+use rust_decimal::Decimal;
 
 use super::{
     frank_dto::FrankForexDto,
+    provider::ForexProvider,
     utils::{make_request, parse_response_object},
 };
 
@@ -23,3 +27,30 @@ pub async fn get_forex_history(
     )
     .await
 }
+
+#[derive(Clone, Debug, Default)]
+pub struct FrankfurterProvider;
+
+#[async_trait]
+impl ForexProvider for FrankfurterProvider {
+    fn name(&self) -> &'static str {
+        "Frankfurter"
+    }
+
+    async fn get_rate(
+        &self,
+        from_currency: &str,
+        to_currency: &str,
+        date: &DateTime<Local>,
+        client: &Client,
+    ) -> Result<Decimal> {
+        let date_str = date.format("%Y-%m-%d").to_string();
+        let forex = get_forex_history(from_currency, to_currency, &date_str, client).await?;
+        forex.rates().get(to_currency).copied().with_context(|| {
+            format!(
+                "No exchange rate for date {} from {} to {}",
+                date_str, from_currency, to_currency
+            )
+        })
+    }
+}
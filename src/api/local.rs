@@ -0,0 +1,176 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use csv::Reader;
+use derive_new::new;
+use reqwest::Client;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    app::utils::parse_datetime,
+    models::{Asset, Quote, Ticker, ticker::ApiProvider},
+};
+
+use super::{
+    local_dto::{LocalForexDto, LocalQuoteDto, LocalSymbolDto},
+    provider::{ForexProvider, QuoteProvider},
+};
+
+const SYMBOLS_FILE: &str = "symbols.csv";
+const QUOTES_FILE: &str = "quotes.csv";
+const FOREX_FILE: &str = "forex.csv";
+
+fn read_fixture<T>(dir: &Path, file: &str) -> Result<Vec<T>>
+where
+    T: DeserializeOwned,
+{
+    let path = dir.join(file);
+    let mut reader = Reader::from_path(&path)
+        .with_context(|| format!("Failed to open fixture file at path: {}", path.display()))?;
+
+    let mut records = Vec::new();
+    for (i, record) in reader.deserialize().enumerate() {
+        let rec: T = record
+            .with_context(|| format!("Failed to parse record {} in {}", i + 1, path.display()))?;
+        records.push(rec);
+    }
+
+    Ok(records)
+}
+
+pub fn load_symbols(dir: &Path) -> Result<Vec<LocalSymbolDto>> {
+    read_fixture(dir, SYMBOLS_FILE)
+}
+
+pub fn load_quotes(dir: &Path, symbol: &str) -> Result<Vec<LocalQuoteDto>> {
+    let mut quotes: Vec<LocalQuoteDto> = read_fixture::<LocalQuoteDto>(dir, QUOTES_FILE)?
+        .into_iter()
+        .filter(|q| q.symbol() == symbol)
+        .collect();
+    quotes.sort_by(|a, b| a.date().cmp(b.date()));
+    Ok(quotes)
+}
+
+pub fn load_forex(dir: &Path) -> Result<Vec<LocalForexDto>> {
+    read_fixture(dir, FOREX_FILE)
+}
+
+/// Returns the latest rate on or before `date`, falling back to the inverse of
+/// the opposite pair when only that one is present in the fixtures.
+pub fn find_rate(
+    rates: &[LocalForexDto],
+    from_currency: &str,
+    to_currency: &str,
+    date: &str,
+) -> Option<Decimal> {
+    let latest = |from: &str, to: &str| {
+        rates
+            .iter()
+            .filter(|r| r.from() == from && r.to() == to && r.date().as_str() <= date)
+            .max_by(|a, b| a.date().cmp(b.date()))
+            .map(|r| *r.rate())
+    };
+
+    latest(from_currency, to_currency).or_else(|| {
+        latest(to_currency, from_currency)
+            .filter(|rate| *rate != Decimal::ZERO)
+            .map(|rate| dec!(1) / rate)
+    })
+}
+
+fn to_quote(dto: &LocalQuoteDto) -> Result<Quote> {
+    Ok(Quote::new(
+        dto.symbol().clone(),
+        parse_datetime(dto.date())?,
+        *dto.close(),
+        dto.adj_close().unwrap_or(*dto.close()),
+    ))
+}
+
+#[derive(Clone, Debug, new)]
+pub struct LocalProvider {
+    fixtures_dir: Option<PathBuf>,
+}
+
+impl LocalProvider {
+    fn fixtures_dir(&self) -> Result<&Path> {
+        self.fixtures_dir
+            .as_deref()
+            .with_context(|| "LOCAL_FIXTURES_DIR is not set")
+    }
+
+    fn find_symbol(&self, symbol: &str) -> Result<LocalSymbolDto> {
+        load_symbols(self.fixtures_dir()?)?
+            .into_iter()
+            .find(|s| s.symbol() == symbol)
+            .with_context(|| format!("Local ({}): Symbol not found in fixtures", symbol))
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for LocalProvider {
+    fn api(&self) -> ApiProvider {
+        ApiProvider::Local
+    }
+
+    async fn search_symbol(&self, symbol: &str, _client: &Client) -> Result<Ticker> {
+        Ok(self.find_symbol(symbol)?.to_ticker())
+    }
+
+    async fn get_latest_quote(&self, symbol: &str, _client: &Client) -> Result<Quote> {
+        let quotes = load_quotes(self.fixtures_dir()?, symbol)?;
+        let last = quotes
+            .last()
+            .with_context(|| format!("Local ({}): No quotes in fixtures", symbol))?;
+        to_quote(last)
+    }
+
+    async fn get_quote_history(
+        &self,
+        symbol: &str,
+        start_date: &DateTime<Local>,
+        end_date: &DateTime<Local>,
+        _client: &Client,
+    ) -> Result<Vec<Quote>> {
+        let start = start_date.format("%Y-%m-%d").to_string();
+        let end = end_date.format("%Y-%m-%d").to_string();
+
+        load_quotes(self.fixtures_dir()?, symbol)?
+            .iter()
+            .filter(|q| q.date() >= &start && q.date() <= &end)
+            .map(to_quote)
+            .collect()
+    }
+
+    async fn get_metadata(&self, symbol: &str, _client: &Client) -> Result<Asset> {
+        Ok(self.find_symbol(symbol)?.to_asset())
+    }
+}
+
+#[async_trait]
+impl ForexProvider for LocalProvider {
+    fn name(&self) -> &'static str {
+        "Local"
+    }
+
+    async fn get_rate(
+        &self,
+        from_currency: &str,
+        to_currency: &str,
+        date: &DateTime<Local>,
+        _client: &Client,
+    ) -> Result<Decimal> {
+        let date_str = date.format("%Y-%m-%d").to_string();
+        let rates = load_forex(self.fixtures_dir()?)?;
+        find_rate(&rates, from_currency, to_currency, &date_str).with_context(|| {
+            format!(
+                "No local exchange rate from {} to {} on or before {}",
+                from_currency, to_currency, date_str
+            )
+        })
+    }
+}
@@ -0,0 +1,66 @@
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::models::{Asset, AssetType, Ticker, ticker::ApiProvider};
+
+#[derive(Clone, Debug, Deserialize, Getters, new)]
+pub struct LocalSymbolDto {
+    symbol: String,
+    name: String,
+    currency: String,
+    exchange: Option<String>,
+    asset_type: Option<String>,
+    isin: Option<String>,
+    sector: Option<String>,
+    industry: Option<String>,
+}
+
+impl LocalSymbolDto {
+    pub fn to_ticker(&self) -> Ticker {
+        Ticker::new(
+            0,
+            0,
+            self.symbol.clone(),
+            self.name.clone(),
+            self.currency.clone(),
+            self.exchange.clone(),
+            None,
+            None,
+            ApiProvider::Local,
+        )
+    }
+
+    pub fn to_asset(&self) -> Asset {
+        let asset_type = self
+            .asset_type
+            .as_deref()
+            .and_then(|s| AssetType::parse_str(s).ok())
+            .unwrap_or(AssetType::Stock);
+        Asset::new(
+            0,
+            self.name.clone(),
+            asset_type,
+            self.isin.clone(),
+            self.sector.clone(),
+            self.industry.clone(),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Getters, new)]
+pub struct LocalQuoteDto {
+    symbol: String,
+    date: String,
+    close: Decimal,
+    adj_close: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, Deserialize, Getters, new)]
+pub struct LocalForexDto {
+    from: String,
+    to: String,
+    date: String,
+    rate: Decimal,
+}
@@ -1,9 +1,16 @@
 use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use derive_new::new;
 use reqwest::Client;
 
-use crate::api::{
-    marketstack_dto::{MarketstackQuoteDto, MarketstackSearchSymbolDto},
-    utils::{make_request, parse_response_array, parse_response_object},
+use crate::{
+    api::{
+        marketstack_dto::{MarketstackQuoteDto, MarketstackSearchSymbolDto},
+        provider::QuoteProvider,
+        utils::{make_request, parse_response_array, parse_response_object},
+    },
+    models::{Asset, Quote, Ticker, ticker::ApiProvider},
 };
 
 const BASE_URL: &str = "https://api.marketstack.com/v2";
@@ -27,6 +34,30 @@ pub async fn get_quote(
     .await
 }
 
+pub async fn get_quote_history(
+    symbol: &str,
+    start_date: &str,
+    end_date: &str,
+    client: &Client,
+    api_key: &str,
+) -> Result<Vec<MarketstackQuoteDto>> {
+    let params = format!(
+        "access_key={}&symbols={}&date_from={}&date_to={}&limit=1000",
+        api_key, symbol, start_date, end_date
+    );
+    let res = make_request(client, BASE_URL, "eod", &params).await?;
+
+    let quotes = res
+        .get("data")
+        .with_context(|| "Failed to get 'data' in response")?;
+
+    parse_response_array::<MarketstackQuoteDto>(
+        quotes.clone(),
+        &format!("Failed to parse Marketstack quote history for {}", symbol),
+    )
+    .await
+}
+
 pub async fn search_symbol(
     symbol: &str,
     client: &Client,
@@ -40,3 +71,83 @@ pub async fn search_symbol(
     )
     .await
 }
+
+#[derive(Clone, Debug, new)]
+pub struct MarketstackProvider {
+    api_key: Option<String>,
+}
+
+impl MarketstackProvider {
+    fn api_key(&self) -> Result<&str> {
+        self.api_key
+            .as_deref()
+            .with_context(|| "MARKETSTACK_API_KEY is not set")
+    }
+}
+
+#[async_trait]
+impl QuoteProvider for MarketstackProvider {
+    fn api(&self) -> ApiProvider {
+        ApiProvider::Marketstack
+    }
+
+    async fn search_symbol(&self, symbol: &str, client: &Client) -> Result<Ticker> {
+        let search_result = search_symbol(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Marketstack ({})", symbol))?;
+        search_result.to_ticker()
+    }
+
+    async fn get_latest_quote(&self, symbol: &str, client: &Client) -> Result<Quote> {
+        let quote_result = get_quote(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Marketstack ({})", symbol))?;
+        let first = quote_result
+            .first()
+            .with_context(|| "Failed to get first entry")?;
+        Ok(Quote::new(
+            symbol.to_string(),
+            *first.date(),
+            *first.close(),
+            *first.adj_close(),
+        ))
+    }
+
+    async fn get_quote_history(
+        &self,
+        symbol: &str,
+        start_date: &DateTime<Local>,
+        end_date: &DateTime<Local>,
+        client: &Client,
+    ) -> Result<Vec<Quote>> {
+        let history = get_quote_history(
+            symbol,
+            &start_date.format("%Y-%m-%d").to_string(),
+            &end_date.format("%Y-%m-%d").to_string(),
+            client,
+            self.api_key()?,
+        )
+        .await
+        .with_context(|| format!("Marketstack ({})", symbol))?;
+
+        Ok(history
+            .iter()
+            .rev()
+            .map(|entry| {
+                Quote::new(
+                    symbol.to_string(),
+                    *entry.date(),
+                    *entry.close(),
+                    *entry.adj_close(),
+                )
+            })
+            .collect())
+    }
+
+    async fn get_metadata(&self, symbol: &str, client: &Client) -> Result<Asset> {
+        let search_result = search_symbol(symbol, client, self.api_key()?)
+            .await
+            .with_context(|| format!("Marketstack ({})", symbol))?;
+        Ok(search_result.to_asset())
+    }
+}
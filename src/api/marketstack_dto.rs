@@ -5,7 +5,7 @@ use derive_new::new;
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use crate::models::{Ticker, ticker::ApiProvider};
+use crate::models::{Asset, AssetType, Ticker, ticker::ApiProvider};
 
 #[derive(Debug, Deserialize, Getters, new)]
 pub struct MarketstackQuoteDto {
@@ -61,6 +61,24 @@ impl MarketstackSearchSymbolDto {
             ApiProvider::Marketstack,
         ))
     }
+
+    pub fn to_asset(&self) -> Asset {
+        let asset_type = match self.item_type.to_lowercase().as_str() {
+            "etf" => AssetType::ETF,
+            "mutual_fund" | "fund" => AssetType::MutualFund,
+            "bond" => AssetType::Bond,
+            "crypto" | "cryptocurrency" => AssetType::Crypto,
+            _ => AssetType::Stock,
+        };
+        Asset::new(
+            0,
+            self.name.clone(),
+            asset_type,
+            Some(self.isin.clone()).filter(|isin| !isin.is_empty()),
+            Some(self.sector.clone()).filter(|sector| !sector.is_empty()),
+            Some(self.industry.clone()).filter(|industry| !industry.is_empty()),
+        )
+    }
 }
 
 #[derive(Debug, Deserialize, Getters, new)]
@@ -4,6 +4,9 @@ pub mod fmp;
 pub mod fmp_dto;
 pub mod frank;
 pub mod frank_dto;
+pub mod local;
+pub mod local_dto;
 pub mod marketstack;
 pub mod marketstack_dto;
+pub mod provider;
 pub mod utils;
@@ -0,0 +1,145 @@
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use reqwest::Client;
+use rust_decimal::Decimal;
+
+use crate::models::{Asset, Quote, Ticker, ticker::ApiProvider};
+
+use super::{
+    av::AlphaVantageProvider, fmp::FmpProvider, frank::FrankfurterProvider, local::LocalProvider,
+    marketstack::MarketstackProvider,
+};
+
+/// A market data source, looked up in a [`ProviderRegistry`] by the
+/// [`ApiProvider`] stored in the `api` column of the `tickers` table.
+#[async_trait]
+pub trait QuoteProvider: Send + Sync {
+    fn api(&self) -> ApiProvider;
+
+    async fn search_symbol(&self, symbol: &str, client: &Client) -> Result<Ticker>;
+
+    async fn get_latest_quote(&self, symbol: &str, client: &Client) -> Result<Quote>;
+
+    /// Returns the daily quotes between `start_date` and `end_date` (inclusive),
+    /// oldest first.
+    async fn get_quote_history(
+        &self,
+        symbol: &str,
+        start_date: &DateTime<Local>,
+        end_date: &DateTime<Local>,
+        client: &Client,
+    ) -> Result<Vec<Quote>>;
+
+    async fn get_metadata(&self, symbol: &str, client: &Client) -> Result<Asset>;
+}
+
+/// A source of historical exchange rates.
+#[async_trait]
+pub trait ForexProvider: Send + Sync {
+    /// Recorded as the source of cached rates.
+    fn name(&self) -> &'static str;
+
+    /// Returns the amount of `to_currency` for one unit of `from_currency`.
+    async fn get_rate(
+        &self,
+        from_currency: &str,
+        to_currency: &str,
+        date: &DateTime<Local>,
+        client: &Client,
+    ) -> Result<Decimal>;
+}
+
+#[derive(Clone)]
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn QuoteProvider>>,
+    forex: Arc<dyn ForexProvider>,
+}
+
+impl Default for ProviderRegistry {
+    fn default() -> Self {
+        Self {
+            providers: Vec::new(),
+            forex: Arc::new(FrankfurterProvider),
+        }
+    }
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_defaults(
+        api_key_alpha_vantage: Option<String>,
+        api_key_fmp: Option<String>,
+        api_key_marketstack: Option<String>,
+        local_fixtures_dir: Option<PathBuf>,
+    ) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(AlphaVantageProvider::new(api_key_alpha_vantage)));
+        registry.register(Arc::new(FmpProvider::new(api_key_fmp)));
+        registry.register(Arc::new(MarketstackProvider::new(api_key_marketstack)));
+        registry.register(Arc::new(LocalProvider::new(local_fixtures_dir)));
+        registry
+    }
+
+    /// Adds a provider, replacing any previously registered provider for the
+    /// same [`ApiProvider`].
+    pub fn register(&mut self, provider: Arc<dyn QuoteProvider>) {
+        let api = provider.api();
+        match self.providers.iter().position(|p| p.api() == api) {
+            Some(i) => self.providers[i] = provider,
+            None => self.providers.push(provider),
+        }
+    }
+
+    pub fn get(&self, api: &ApiProvider) -> Result<Arc<dyn QuoteProvider>> {
+        self.providers
+            .iter()
+            .find(|p| &p.api() == api)
+            .cloned()
+            .with_context(|| format!("No provider registered for {}", api.to_str()))
+    }
+
+    /// Parses the name of a built-in or registered provider.
+    pub fn parse_api(&self, s: &str) -> Result<ApiProvider> {
+        ApiProvider::parse_str(s).or_else(|e| {
+            self.providers
+                .iter()
+                .map(|p| p.api())
+                .find(|api| api.to_str() == s)
+                .ok_or(e)
+        })
+    }
+
+    pub fn set_forex(&mut self, forex: Arc<dyn ForexProvider>) {
+        self.forex = forex;
+    }
+
+    pub fn forex(&self) -> Arc<dyn ForexProvider> {
+        self.forex.clone()
+    }
+
+    pub fn apis(&self) -> Vec<ApiProvider> {
+        self.providers.iter().map(|p| p.api()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+impl fmt::Debug for ProviderRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.providers.iter().map(|p| p.api()))
+            .finish()
+    }
+}
@@ -1,7 +1,11 @@
-use std::io;
-use strum::IntoEnumIterator;
+use std::{
+    io,
+    path::Path,
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
+use chrono::Local;
 use crossterm::{
     event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     execute,
@@ -14,10 +18,20 @@ use ratatui::{
 };
 
 use crate::{
-    app::{Portfolio, ui},
-    models::ticker::ApiProvider,
+    app::{
+        Config, Portfolio,
+        export::ExportFormat,
+        form::TransactionForm,
+        ui::{self, Tab},
+    },
+    models::{
+        AssetType, DividendReport, ImportPreview, ImportStatus, LedgerEntry, LedgerFilter,
+        LedgerPage, LedgerSort, PerformanceReport, PositionDetail, ReturnPeriod, ValueHistory,
+    },
 };
 
+const LEDGER_PAGE_SIZE: i64 = 50;
+
 trait SelectableState {
     fn selected(&self) -> Option<usize>;
     fn select(&mut self, index: Option<usize>);
@@ -44,8 +58,13 @@ impl SelectableState for TableState {
 struct PopupManager {
     message: Option<String>,
     error: Option<String>,
+    notice: Option<String>,
     show_api_selector: bool,
     show_database_reset: bool,
+    show_dividends: bool,
+    show_performance: bool,
+    show_position_detail: bool,
+    show_import_preview: bool,
 }
 
 impl PopupManager {
@@ -53,8 +72,13 @@ impl PopupManager {
         Self {
             message: None,
             error: None,
+            notice: None,
             show_api_selector: false,
             show_database_reset: false,
+            show_dividends: false,
+            show_performance: false,
+            show_position_detail: false,
+            show_import_preview: false,
         }
     }
 
@@ -74,12 +98,26 @@ impl PopupManager {
         self.error = None;
     }
 
+    /// Shows `message` until it is dismissed, unlike the progress messages.
+    fn show_notice(&mut self, message: &str) {
+        self.notice = Some(message.to_string());
+    }
+
+    fn clear_notice(&mut self) {
+        self.notice = None;
+    }
+
     fn has_error(&self) -> bool {
         self.error.is_some()
     }
 
     fn has_any_popup(&self) -> bool {
-        self.show_api_selector || self.show_database_reset
+        self.show_api_selector
+            || self.show_database_reset
+            || self.show_dividends
+            || self.show_performance
+            || self.show_position_detail
+            || self.show_import_preview
     }
 }
 
@@ -90,6 +128,25 @@ pub struct App {
     default_api_state: ListState,
     selection_mode: bool,
     default_reset_state: ListState,
+    refresh_interval: Option<Duration>,
+    last_refresh: Instant,
+    dividend_report: DividendReport,
+    performance_report: PerformanceReport,
+    tab: Tab,
+    value_history: ValueHistory,
+    history_overlay: Option<usize>,
+    position_detail: Option<PositionDetail>,
+    ledger: LedgerPage,
+    ledger_state: TableState,
+    ledger_filter: LedgerFilter,
+    ledger_sort: LedgerSort,
+    ledger_descending: bool,
+    filter_input: Option<String>,
+    transaction_form: Option<TransactionForm>,
+    pending_delete: Option<LedgerEntry>,
+    import_preview: ImportPreview,
+    import_preview_state: TableState,
+    export_directory: String,
 }
 
 impl App {
@@ -105,17 +162,44 @@ impl App {
             default_api_state: default_api_list_state,
             selection_mode: false,
             default_reset_state: default_reset_list_state,
+            refresh_interval: None,
+            last_refresh: Instant::now(),
+            dividend_report: DividendReport::default(),
+            performance_report: PerformanceReport::default(),
+            tab: Tab::default(),
+            value_history: ValueHistory::default(),
+            history_overlay: None,
+            position_detail: None,
+            ledger: LedgerPage::default(),
+            ledger_state: TableState::default(),
+            ledger_filter: LedgerFilter::default(),
+            ledger_sort: LedgerSort::default(),
+            ledger_descending: true,
+            filter_input: None,
+            transaction_form: None,
+            pending_delete: None,
+            import_preview: ImportPreview::default(),
+            import_preview_state: TableState::default(),
+            export_directory: Config::default().export_directory_expanded(),
         }
     }
 
-    pub async fn run(&mut self, csv_path: &str) -> Result<()> {
+    pub fn set_refresh_interval(&mut self, refresh_interval: Option<Duration>) {
+        self.refresh_interval = refresh_interval;
+    }
+
+    pub fn set_export_directory(&mut self, export_directory: String) {
+        self.export_directory = export_directory;
+    }
+
+    pub async fn run(&mut self, csv_paths: &[String]) -> Result<()> {
         enable_raw_mode()?;
         let mut stdout = io::stdout();
         execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
         let backend = CrosstermBackend::new(stdout);
         let mut terminal = Terminal::new(backend)?;
 
-        let result = self.run_app(&mut terminal, csv_path).await;
+        let result = self.run_app(&mut terminal, csv_paths).await;
 
         disable_raw_mode()?;
         execute!(
@@ -129,6 +213,9 @@ impl App {
     }
 
     fn render_ui<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let history_overlay = self
+            .history_overlay
+            .and_then(|i| self.value_history.by_position().values().nth(i));
         terminal.draw(|frame| {
             ui::render(
                 frame,
@@ -136,11 +223,39 @@ impl App {
                 &mut self.table_state,
                 &self.popup_manager.message,
                 &self.popup_manager.error,
+                &self.popup_manager.notice,
                 self.popup_manager.show_api_selector,
                 &mut self.default_api_state,
                 self.selection_mode,
                 self.popup_manager.show_database_reset,
                 &mut self.default_reset_state,
+                self.popup_manager
+                    .show_dividends
+                    .then_some(&self.dividend_report),
+                self.popup_manager
+                    .show_performance
+                    .then_some(&self.performance_report),
+                self.tab,
+                &self.value_history,
+                history_overlay,
+                self.popup_manager
+                    .show_position_detail
+                    .then_some(self.position_detail.as_ref())
+                    .flatten(),
+                ui::LedgerView::new(
+                    &self.ledger,
+                    &self.ledger_filter,
+                    self.ledger_sort,
+                    self.ledger_descending,
+                    self.filter_input.as_deref(),
+                ),
+                &mut self.ledger_state,
+                self.transaction_form.as_ref(),
+                self.pending_delete.as_ref(),
+                self.popup_manager
+                    .show_import_preview
+                    .then_some(&self.import_preview),
+                &mut self.import_preview_state,
             )
         })?;
         Ok(())
@@ -181,20 +296,21 @@ impl App {
 
     fn handle_api_popup_keys(&mut self, key_code: KeyCode) -> Result<()> {
         self.deselect_table();
+        let apis = self.portfolio.providers().apis();
         match key_code {
             KeyCode::Esc => {
                 self.popup_manager.show_api_selector = false;
             }
             KeyCode::Down => {
-                Self::navigate_down(&mut self.default_api_state, ApiProvider::iter().len());
+                Self::navigate_down(&mut self.default_api_state, apis.len());
             }
             KeyCode::Up => {
-                Self::navigate_up(&mut self.default_api_state, ApiProvider::iter().len());
+                Self::navigate_up(&mut self.default_api_state, apis.len());
             }
             KeyCode::Enter => {
                 if let Some(i) = self.default_api_state.selected() {
                     self.portfolio.set_default_api(
-                        ApiProvider::iter()
+                        apis.into_iter()
                             .nth(i)
                             .with_context(|| "Cannot select API provider")?,
                     );
@@ -273,26 +389,88 @@ impl App {
         }
     }
 
+    /// Dry-runs the import of the files and shows what it would change.
+    async fn preview_import<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        csv_paths: &[String],
+    ) -> Result<()> {
+        self.deselect_table();
+        self.popup_manager.show_message("Checking transactions...");
+        self.render_ui(terminal)?;
+
+        let default_api = self.portfolio.default_api().clone();
+        let preview_result = self
+            .portfolio
+            .preview_import(&expand_paths(csv_paths), &default_api)
+            .await;
+        self.popup_manager.clear_message();
+
+        match preview_result {
+            Ok(preview) => {
+                self.import_preview = preview;
+                self.import_preview_state.select(None);
+                self.popup_manager.show_import_preview = true;
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error importing transactions: {:?}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_import_preview_keys<B: Backend>(
+        &mut self,
+        key_code: KeyCode,
+        terminal: &mut Terminal<B>,
+        csv_paths: &[String],
+    ) -> Result<()> {
+        let len = self
+            .import_preview
+            .rows()
+            .iter()
+            .filter(|row| *row.status() != ImportStatus::Unchanged)
+            .count();
+        match key_code {
+            KeyCode::Esc => {
+                self.popup_manager.show_import_preview = false;
+            }
+            KeyCode::Enter if self.import_preview.errors().is_empty() => {
+                self.popup_manager.show_import_preview = false;
+                self.import_transactions(terminal, csv_paths).await?;
+            }
+            KeyCode::Down if len > 0 => {
+                Self::navigate_down(&mut self.import_preview_state, len);
+            }
+            KeyCode::Up if len > 0 => {
+                Self::navigate_up(&mut self.import_preview_state, len);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     async fn import_transactions<B: Backend>(
         &mut self,
         terminal: &mut Terminal<B>,
-        csv_path: &str,
+        csv_paths: &[String],
     ) -> Result<()> {
         self.deselect_table();
         self.popup_manager.show_message("Importing transactions...");
         self.render_ui(terminal)?;
 
-        let csv_path_expanded = shellexpand::tilde(csv_path);
         let default_api = self.portfolio.default_api().clone();
-
         let import_result = self
             .portfolio
-            .import_transactions(&csv_path_expanded, &default_api)
+            .commit_import(&expand_paths(csv_paths), &default_api)
             .await;
         let update_result = self.portfolio.update_prices().await;
         let positions_result = self.portfolio.set_positions().await;
 
         self.popup_manager.clear_message();
+        self.refresh_tab(terminal).await?;
         self.render_ui(terminal)?;
 
         if let Err(e) = import_result {
@@ -316,8 +494,10 @@ impl App {
 
         let update_result = self.portfolio.update_prices().await;
         let positions_result = self.portfolio.set_positions().await;
+        self.last_refresh = Instant::now();
 
         self.popup_manager.clear_message();
+        self.refresh_tab(terminal).await?;
         self.render_ui(terminal)?;
 
         if let Err(e) = update_result {
@@ -331,14 +511,422 @@ impl App {
         Ok(())
     }
 
+    /// Writes the positions, transactions and realized gains as CSV, which
+    /// can be imported again.
+    async fn export(&mut self) {
+        self.deselect_table();
+        match self
+            .portfolio
+            .export(Path::new(&self.export_directory), ExportFormat::Csv)
+            .await
+        {
+            Ok(paths) => {
+                let paths: Vec<String> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                self.popup_manager
+                    .show_notice(&format!("Exported to:\n{}", paths.join("\n")));
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error exporting: {:?}", e));
+            }
+        }
+    }
+
+    async fn show_dividends(&mut self) {
+        self.deselect_table();
+        match self.portfolio.get_dividend_report().await {
+            Ok(report) => {
+                self.dividend_report = report;
+                self.popup_manager.show_dividends = true;
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error loading dividends: {:?}", e));
+            }
+        }
+    }
+
+    async fn show_performance<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        period: ReturnPeriod,
+    ) -> Result<()> {
+        self.deselect_table();
+        self.popup_manager.show_message("Calculating returns...");
+        self.render_ui(terminal)?;
+
+        let report_result = self.portfolio.get_performance_report(period).await;
+        self.popup_manager.clear_message();
+
+        match report_result {
+            Ok(report) => {
+                self.performance_report = report;
+                self.popup_manager.show_performance = true;
+            }
+            Err(e) => {
+                self.popup_manager.show_performance = false;
+                self.popup_manager
+                    .show_error(&format!("Error calculating returns: {:?}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle_performance_popup_keys<B: Backend>(
+        &mut self,
+        key_code: KeyCode,
+        terminal: &mut Terminal<B>,
+    ) -> Result<()> {
+        let period = *self.performance_report.period();
+        match key_code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::F(7) => {
+                self.popup_manager.show_performance = false;
+            }
+            KeyCode::Left => {
+                self.show_performance(terminal, period.previous()).await?;
+            }
+            KeyCode::Right => {
+                self.show_performance(terminal, period.next()).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn show_position_detail(&mut self) {
+        let Some(position) = self
+            .table_state
+            .selected()
+            .and_then(|i| self.portfolio.positions().get(i))
+        else {
+            return;
+        };
+        // Cash accounts have no asset to show
+        if matches!(position.asset().asset_type(), AssetType::Cash) {
+            return;
+        }
+
+        match self
+            .portfolio
+            .get_position_detail(*position.asset().id())
+            .await
+        {
+            Ok(detail) => {
+                self.position_detail = Some(detail);
+                self.popup_manager.show_position_detail = true;
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error loading position: {:?}", e));
+            }
+        }
+    }
+
+    async fn show_value_history<B: Backend>(
+        &mut self,
+        terminal: &mut Terminal<B>,
+        period: ReturnPeriod,
+    ) -> Result<()> {
+        self.deselect_table();
+        self.popup_manager.show_message("Loading value history...");
+        self.render_ui(terminal)?;
+
+        let history_result = self.portfolio.get_value_history(period).await;
+        self.popup_manager.clear_message();
+
+        match history_result {
+            Ok(history) => {
+                let overlay = self
+                    .history_overlay
+                    .and_then(|i| self.value_history.by_position().keys().nth(i))
+                    .and_then(|asset_id| {
+                        history.by_position().keys().position(|id| id == asset_id)
+                    });
+                self.value_history = history;
+                self.history_overlay = overlay;
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error loading value history: {:?}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn switch_tab<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        self.deselect_table();
+        self.tab = self.tab.next();
+        self.refresh_tab(terminal).await
+    }
+
+    /// Reloads the data shown by the history and transactions tabs.
+    async fn refresh_tab<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        match self.tab {
+            Tab::Positions => {}
+            Tab::History => {
+                let period = *self.value_history.period();
+                self.show_value_history(terminal, period).await?;
+            }
+            Tab::Transactions => {
+                let page = *self.ledger.page();
+                self.load_ledger(page).await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn load_ledger(&mut self, page: i64) {
+        let ledger_result = self
+            .portfolio
+            .get_ledger(
+                &self.ledger_filter,
+                self.ledger_sort,
+                self.ledger_descending,
+                page.max(0),
+                LEDGER_PAGE_SIZE,
+            )
+            .await;
+
+        match ledger_result {
+            // Past the last page, e.g. after a narrower filter
+            Ok(ledger) if ledger.entries().is_empty() && *ledger.page() > 0 => {
+                let last_page = ledger.page_count() - 1;
+                Box::pin(self.load_ledger(last_page)).await;
+            }
+            Ok(ledger) => {
+                let len = ledger.entries().len();
+                self.ledger = ledger;
+                if self.ledger_state.selected().is_some_and(|i| i >= len) {
+                    self.ledger_state.select(len.checked_sub(1));
+                }
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error loading transactions: {:?}", e));
+            }
+        }
+    }
+
+    async fn handle_ledger_keys(&mut self, key_code: KeyCode) {
+        let page = *self.ledger.page();
+        match key_code {
+            KeyCode::Down | KeyCode::Up => {
+                let len = self.ledger.entries().len();
+                if len == 0 {
+                    return;
+                }
+                if key_code == KeyCode::Down {
+                    Self::navigate_down(&mut self.ledger_state, len);
+                } else {
+                    Self::navigate_up(&mut self.ledger_state, len);
+                }
+            }
+            KeyCode::Left | KeyCode::PageUp if page > 0 => {
+                self.ledger_state.select(None);
+                self.load_ledger(page - 1).await;
+            }
+            KeyCode::Right | KeyCode::PageDown if page + 1 < self.ledger.page_count() => {
+                self.ledger_state.select(None);
+                self.load_ledger(page + 1).await;
+            }
+            KeyCode::Char('s') => {
+                self.ledger_sort = self.ledger_sort.next();
+                self.load_ledger(0).await;
+            }
+            KeyCode::Char('d') => {
+                self.ledger_descending = !self.ledger_descending;
+                self.load_ledger(0).await;
+            }
+            KeyCode::Char('/') => {
+                self.filter_input = Some(self.ledger_filter.to_str());
+            }
+            KeyCode::Char('c') => {
+                self.ledger_filter = LedgerFilter::default();
+                self.load_ledger(0).await;
+            }
+            KeyCode::Char('a') => {
+                self.transaction_form = Some(TransactionForm::new(Local::now().date_naive()));
+            }
+            KeyCode::Char('e') => {
+                self.transaction_form = self.selected_ledger_entry().map(TransactionForm::edit);
+            }
+            KeyCode::Char('x') | KeyCode::Delete => {
+                self.pending_delete = self.selected_ledger_entry().cloned();
+            }
+            _ => {}
+        }
+    }
+
+    fn selected_ledger_entry(&self) -> Option<&LedgerEntry> {
+        self.ledger_state
+            .selected()
+            .and_then(|i| self.ledger.entries().get(i))
+    }
+
+    async fn handle_transaction_form_keys<B: Backend>(
+        &mut self,
+        key_code: KeyCode,
+        terminal: &mut Terminal<B>,
+    ) -> Result<()> {
+        let Some(form) = self.transaction_form.as_mut() else {
+            return Ok(());
+        };
+        match key_code {
+            KeyCode::Tab | KeyCode::Down => form.next_field(),
+            KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+            KeyCode::Left => form.cycle_type(false),
+            KeyCode::Right => form.cycle_type(true),
+            KeyCode::Char(c) => form.push(c),
+            KeyCode::Backspace => form.pop(),
+            KeyCode::Esc => self.transaction_form = None,
+            KeyCode::Enter => self.save_transaction(terminal).await?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn save_transaction<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let Some(form) = &self.transaction_form else {
+            return Ok(());
+        };
+        let input = match form.to_input(Local::now().date_naive()) {
+            Ok(input) => input,
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Invalid transaction: {:#}", e));
+                return Ok(());
+            }
+        };
+        let id = *form.id();
+
+        self.popup_manager.show_message("Saving transaction...");
+        self.render_ui(terminal)?;
+        let save_result = self.portfolio.save_transaction(id, &input).await;
+        self.popup_manager.clear_message();
+
+        match save_result {
+            Ok(_) => {
+                self.transaction_form = None;
+                self.reload_after_edit(terminal).await?;
+            }
+            Err(e) => {
+                self.popup_manager
+                    .show_error(&format!("Error saving transaction: {:?}", e));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle_delete_transaction_keys<B: Backend>(
+        &mut self,
+        key_code: KeyCode,
+        terminal: &mut Terminal<B>,
+    ) -> Result<()> {
+        match key_code {
+            KeyCode::Char('y') => {
+                let Some(entry) = self.pending_delete.take() else {
+                    return Ok(());
+                };
+                match self
+                    .portfolio
+                    .delete_transaction(*entry.transaction().id())
+                    .await
+                {
+                    Ok(()) => self.reload_after_edit(terminal).await?,
+                    Err(e) => {
+                        self.popup_manager
+                            .show_error(&format!("Error deleting transaction: {:?}", e));
+                    }
+                }
+            }
+            KeyCode::Char('n') | KeyCode::Esc => self.pending_delete = None,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    async fn reload_after_edit<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
+        let positions_result = self.portfolio.set_positions().await;
+        self.refresh_tab(terminal).await?;
+        if let Err(e) = positions_result {
+            self.popup_manager
+                .show_error(&format!("Error updating positions: {:?}", e));
+        }
+        Ok(())
+    }
+
+    async fn handle_filter_input_keys(&mut self, key_code: KeyCode) {
+        let Some(input) = self.filter_input.as_mut() else {
+            return;
+        };
+        match key_code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.filter_input = None,
+            KeyCode::Enter => match LedgerFilter::parse_str(input) {
+                Ok(filter) => {
+                    self.filter_input = None;
+                    self.ledger_filter = filter;
+                    self.ledger_state.select(None);
+                    self.load_ledger(0).await;
+                }
+                Err(e) => {
+                    self.popup_manager
+                        .show_error(&format!("Invalid filter: {:#}", e));
+                }
+            },
+            _ => {}
+        }
+    }
+
+    /// Up and Down step through the positions drawn over the portfolio
+    /// value, with no overlay between the last and the first position.
+    fn select_history_overlay(&mut self, key_code: KeyCode) {
+        let len = self.value_history.by_position().len();
+        if len == 0 {
+            self.history_overlay = None;
+            return;
+        }
+
+        self.history_overlay = match (key_code, self.history_overlay) {
+            (KeyCode::Down, None) => Some(0),
+            (KeyCode::Down, Some(i)) if i + 1 < len => Some(i + 1),
+            (KeyCode::Up, None) => Some(len - 1),
+            (KeyCode::Up, Some(i)) if i > 0 => Some(i - 1),
+            _ => None,
+        };
+    }
+
     async fn run_app<B: Backend>(
         &mut self,
         terminal: &mut Terminal<B>,
-        csv_path: &str,
+        csv_paths: &[String],
     ) -> Result<()> {
         loop {
             self.render_ui(terminal)?;
 
+            if let Some(interval) = self.refresh_interval {
+                let elapsed = self.last_refresh.elapsed();
+                let idle = !self.popup_manager.has_any_popup()
+                    && !self.popup_manager.has_error()
+                    && self.transaction_form.is_none()
+                    && self.pending_delete.is_none();
+                if elapsed >= interval && idle {
+                    self.update_prices(terminal).await?;
+                    continue;
+                }
+                if elapsed < interval && !event::poll(interval - elapsed)? {
+                    continue;
+                }
+            }
+
             if let Event::Key(key) = event::read()? {
                 if key.kind != KeyEventKind::Press {
                     continue;
@@ -349,11 +937,54 @@ impl App {
                     continue;
                 }
 
+                if self.popup_manager.show_dividends {
+                    if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::F(6)) {
+                        self.popup_manager.show_dividends = false;
+                    }
+                    continue;
+                }
+
+                if self.popup_manager.show_position_detail {
+                    if matches!(key.code, KeyCode::Esc | KeyCode::Enter) {
+                        self.popup_manager.show_position_detail = false;
+                    }
+                    continue;
+                }
+
+                if self.popup_manager.show_performance {
+                    self.handle_performance_popup_keys(key.code, terminal)
+                        .await?;
+                    continue;
+                }
+
+                if self.popup_manager.show_import_preview {
+                    self.handle_import_preview_keys(key.code, terminal, csv_paths)
+                        .await?;
+                    continue;
+                }
+
                 if self.popup_manager.show_database_reset {
                     self.handle_reset_popup_keys(key.code, terminal).await?;
                     continue;
                 }
 
+                if self.filter_input.is_some() && !self.popup_manager.has_error() {
+                    self.handle_filter_input_keys(key.code).await;
+                    continue;
+                }
+
+                if self.transaction_form.is_some() && !self.popup_manager.has_error() {
+                    self.handle_transaction_form_keys(key.code, terminal)
+                        .await?;
+                    continue;
+                }
+
+                if self.pending_delete.is_some() && !self.popup_manager.has_error() {
+                    self.handle_delete_transaction_keys(key.code, terminal)
+                        .await?;
+                    continue;
+                }
+
                 match key.code {
                     KeyCode::Char('q') => return Ok(()),
                     KeyCode::Enter | KeyCode::Esc => {
@@ -361,30 +992,80 @@ impl App {
                             self.popup_manager.clear_error();
                             continue;
                         }
+                        if self.popup_manager.notice.is_some() {
+                            self.popup_manager.clear_notice();
+                            continue;
+                        }
                         if key.code == KeyCode::Esc {
                             self.deselect_table();
+                        } else if self.tab == Tab::Positions && self.selection_mode {
+                            self.show_position_detail().await;
                         }
                     }
                     KeyCode::F(4) => {
-                        self.import_transactions(terminal, csv_path).await?;
+                        self.preview_import(terminal, csv_paths).await?;
                     }
                     KeyCode::F(5) => {
                         self.update_prices(terminal).await?;
                     }
+                    KeyCode::F(6) => {
+                        self.show_dividends().await;
+                    }
+                    KeyCode::F(7) => {
+                        let period = *self.performance_report.period();
+                        self.show_performance(terminal, period).await?;
+                    }
                     KeyCode::F(8) => {
                         self.deselect_table();
                         self.popup_manager.show_api_selector = true;
                     }
+                    KeyCode::F(9) => {
+                        self.export().await;
+                    }
                     KeyCode::F(12) => {
                         self.deselect_table();
                         self.popup_manager.show_database_reset = true;
                     }
+                    KeyCode::Tab => {
+                        self.switch_tab(terminal).await?;
+                    }
+                    KeyCode::Down
+                    | KeyCode::Up
+                    | KeyCode::Left
+                    | KeyCode::Right
+                    | KeyCode::PageDown
+                    | KeyCode::PageUp
+                    | KeyCode::Delete
+                    | KeyCode::Char(_)
+                        if self.tab == Tab::Transactions =>
+                    {
+                        self.handle_ledger_keys(key.code).await;
+                    }
+                    KeyCode::Down | KeyCode::Up if self.tab == Tab::History => {
+                        self.select_history_overlay(key.code);
+                    }
                     KeyCode::Down | KeyCode::Up => {
                         self.handle_table_navigation(key.code);
                     }
+                    KeyCode::Left | KeyCode::Right if self.tab == Tab::History => {
+                        let period = *self.value_history.period();
+                        let period = if key.code == KeyCode::Left {
+                            period.previous()
+                        } else {
+                            period.next()
+                        };
+                        self.show_value_history(terminal, period).await?;
+                    }
                     _ => {}
                 }
             }
         }
     }
 }
+
+fn expand_paths(paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| shellexpand::tilde(path).to_string())
+        .collect()
+}
@@ -1,92 +1,555 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use anyhow::{Context, Result};
-use rust_decimal::{Decimal, prelude::ToPrimitive};
+use chrono::NaiveDate;
+use rust_decimal::{
+    Decimal,
+    prelude::{FromPrimitive, ToPrimitive},
+};
+use rust_decimal_macros::dec;
 
-use crate::models::{PositionState, Transaction, TransactionGains, TransactionType};
+use crate::models::{
+    CashBalance, CostBasisMethod, DividendBreakdown, Lot, PerformancePoint, PositionState,
+    Transaction, TransactionGains, TransactionType,
+};
 
-pub fn calculate_position_state(
-    amounts: Vec<Decimal>,
-    quantities: Vec<Decimal>,
-) -> Result<PositionState> {
-    if amounts.len() != quantities.len() {
-        return Err(anyhow::anyhow!(
-            concat!(
-                "Cannot calculate position state: ",
-                "amounts and quantities have different lengths ({} vs {})"
-            ),
-            amounts.len(),
-            quantities.len()
-        ));
+use super::config::CostBasisConfig;
+
+const XIRR_TOLERANCE: f64 = 1e-9;
+
+/// Remaining quantities below this are treated as rounding noise from
+/// dividing fractional lots and close the position. Far below the smallest
+/// units of fractional shares and crypto.
+const QUANTITY_TOLERANCE: Decimal = dec!(0.000000000001);
+
+#[derive(Clone, Debug, Default)]
+pub struct LotQueue {
+    method: CostBasisMethod,
+    lots: VecDeque<Lot>,
+}
+
+impl LotQueue {
+    pub fn new(method: CostBasisMethod) -> Self {
+        Self {
+            method,
+            lots: VecDeque::new(),
+        }
     }
 
-    if amounts.is_empty() {
-        return Err(anyhow::anyhow!(
-            "Cannot calculate position state: empty amounts and quantities vectors"
-        ));
+    pub fn method(&self) -> CostBasisMethod {
+        self.method
     }
 
-    let mut queue = VecDeque::new();
-    let mut cost_of_units_sold = Decimal::ZERO;
-    let mut cumulative_units = Decimal::ZERO;
+    pub fn lots(&self) -> &VecDeque<Lot> {
+        &self.lots
+    }
 
-    for i in 0..amounts.len() {
-        cost_of_units_sold = Decimal::ZERO;
-        let amount = amounts[i];
-        let quantity = quantities[i];
+    pub fn units(&self) -> Decimal {
+        self.lots.iter().map(|lot| *lot.quantity()).sum()
+    }
+
+    pub fn cost(&self) -> Decimal {
+        self.lots.iter().map(|lot| *lot.cost()).sum()
+    }
+
+    /// Adds a lot, keeping the lots ordered by acquisition date.
+    pub fn add(&mut self, lot: Lot) {
+        let key = (*lot.date(), *lot.transaction_no());
+        let i = self
+            .lots
+            .iter()
+            .rposition(|l| (*l.date(), *l.transaction_no()) <= key)
+            .map_or(0, |i| i + 1);
+        self.lots.insert(i, lot);
+    }
 
-        if quantity == Decimal::ZERO {
+    pub fn split(&mut self, ratio: Decimal) -> Result<()> {
+        if ratio <= Decimal::ZERO {
             return Err(anyhow::anyhow!(
-                "Cannot calculate position state: quantity is zero at index {}",
-                i
+                "Cannot split lots: ratio must be positive, got {}",
+                ratio.normalize()
             ));
         }
+        for lot in self.lots.iter_mut() {
+            lot.split(ratio);
+        }
+        Ok(())
+    }
 
-        let unit_cost = amount / quantity;
-        cumulative_units += quantity;
+    /// Removes `quantity` units from the lots picked by the cost-basis method and
+    /// returns the consumed (possibly partial) lots.
+    pub fn remove(&mut self, quantity: Decimal) -> Result<Vec<Lot>> {
+        let available = self.units();
+        if quantity - available > QUANTITY_TOLERANCE {
+            return Err(anyhow::anyhow!(
+                "Cannot sell {} units: only {} units available",
+                quantity.normalize(),
+                available.normalize()
+            ));
+        }
+
+        let mut removed = match self.method {
+            CostBasisMethod::Average => self.remove_proportionally(quantity, available),
+            _ => self.remove_in_order(quantity),
+        };
+
+        if self.units().abs() < QUANTITY_TOLERANCE {
+            removed.extend(self.lots.drain(..));
+        }
+
+        Ok(removed)
+    }
 
-        let quantity_abs = quantity
-            .abs()
-            .floor()
-            .to_i64()
-            .with_context(|| "Failed to convert quantity to i64")?;
+    fn remove_in_order(&mut self, quantity: Decimal) -> Vec<Lot> {
+        let mut removed = Vec::new();
+        let mut remaining = quantity;
 
-        if amount < Decimal::ZERO {
-            for _ in 0..quantity_abs {
-                queue.push_back(unit_cost);
+        while remaining > Decimal::ZERO
+            && let Some(i) = self.next_lot_index()
+        {
+            let lot = &mut self.lots[i];
+            let taken = lot.split_off(remaining);
+            remaining -= taken.quantity();
+            if *lot.quantity() == Decimal::ZERO {
+                self.lots.remove(i);
             }
+            removed.push(taken);
+        }
+
+        removed
+    }
+
+    /// Average cost: every lot gives up the same share of its units, so the
+    /// units sold carry the average unit cost of the whole position.
+    fn remove_proportionally(&mut self, quantity: Decimal, available: Decimal) -> Vec<Lot> {
+        if available == Decimal::ZERO {
+            return Vec::new();
+        }
+        let share = (quantity / available).min(Decimal::ONE);
+
+        let removed = self
+            .lots
+            .iter_mut()
+            .map(|lot| {
+                let lot_quantity = *lot.quantity();
+                lot.split_off(lot_quantity * share)
+            })
+            .collect();
+        self.lots.retain(|lot| *lot.quantity() != Decimal::ZERO);
+
+        removed
+    }
+
+    fn next_lot_index(&self) -> Option<usize> {
+        if self.lots.is_empty() {
+            return None;
         }
+        match self.method {
+            CostBasisMethod::Fifo | CostBasisMethod::Average => Some(0),
+            CostBasisMethod::Lifo => Some(self.lots.len() - 1),
+            CostBasisMethod::Hifo => self
+                .lots
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by(|(_, a), (_, b)| a.unit_cost().cmp(&b.unit_cost()))
+                .map(|(i, _)| i),
+        }
+    }
+}
+
+/// The lot queues of one ticker across all brokers. Transfers move lots from
+/// the sending broker's queue to the receiving one with their original cost
+/// and acquisition dates; a transfer in with nothing in transit opens a lot
+/// at its own price.
+#[derive(Clone, Debug)]
+pub struct TickerLots {
+    cost_basis: CostBasisConfig,
+    queues: BTreeMap<String, LotQueue>,
+    in_transit: VecDeque<Lot>,
+}
+
+impl TickerLots {
+    pub fn new(cost_basis: CostBasisConfig) -> Self {
+        Self {
+            cost_basis,
+            queues: BTreeMap::new(),
+            in_transit: VecDeque::new(),
+        }
+    }
+
+    pub fn queues(&self) -> &BTreeMap<String, LotQueue> {
+        &self.queues
+    }
+
+    pub fn queue(&self, broker: &str) -> Option<&LotQueue> {
+        self.queues.get(broker)
+    }
+
+    /// Lots that were transferred out but not yet transferred in.
+    pub fn in_transit(&self) -> &VecDeque<Lot> {
+        &self.in_transit
+    }
+
+    fn queue_mut(&mut self, broker: &str) -> Result<&mut LotQueue> {
+        if !self.queues.contains_key(broker) {
+            let method = self.cost_basis.method_for_broker(broker)?;
+            self.queues
+                .insert(broker.to_string(), LotQueue::new(method));
+        }
+        self.queues
+            .get_mut(broker)
+            .with_context(|| format!("Missing lot queue for broker {}", broker))
+    }
 
-        if amount > Decimal::ZERO {
-            for _ in 0..quantity_abs {
-                if queue.is_empty() {
-                    return Err(anyhow::anyhow!(concat!(
-                        "Cannot calculate position_state: ",
-                        "trying to sell more units than available in queue"
-                    )));
+    /// Applies a single transaction and returns the resulting position state of
+    /// the transaction's broker.
+    pub fn apply(&mut self, transaction: &Transaction) -> Result<PositionState> {
+        let quantity = transaction.quantity().abs();
+        let mut cost_of_units_sold = Decimal::ZERO;
+
+        match transaction.transaction_type() {
+            TransactionType::Buy
+            | TransactionType::Sell
+            | TransactionType::TransferOut
+            | TransactionType::TransferIn
+                if quantity == Decimal::ZERO =>
+            {
+                return Err(anyhow::anyhow!(
+                    "Cannot calculate position state: quantity is zero in transaction {}",
+                    transaction.transaction_no()
+                ));
+            }
+            TransactionType::Buy => {
+                self.queue_mut(transaction.broker())?.add(Lot::new(
+                    *transaction.transaction_no(),
+                    *transaction.date(),
+                    quantity,
+                    transaction.get_amount().abs(),
+                ));
+            }
+            TransactionType::Sell => {
+                cost_of_units_sold = self
+                    .queue_mut(transaction.broker())?
+                    .remove(quantity)?
+                    .iter()
+                    .map(|lot| *lot.cost())
+                    .sum();
+            }
+            TransactionType::Split => {
+                self.queue_mut(transaction.broker())?
+                    .split(*transaction.quantity())
+                    .with_context(|| {
+                        format!(
+                            "Invalid split in transaction {}",
+                            transaction.transaction_no()
+                        )
+                    })?;
+            }
+            TransactionType::TransferOut => {
+                let lots = self.queue_mut(transaction.broker())?.remove(quantity)?;
+                self.in_transit.extend(lots);
+            }
+            TransactionType::TransferIn => {
+                // Units from outside the portfolio open a lot at the cost
+                // basis given with the transfer
+                let lots = if self.in_transit.is_empty() {
+                    vec![Lot::new(
+                        *transaction.transaction_no(),
+                        *transaction.date(),
+                        quantity,
+                        transaction.price() * quantity / transaction.exchange_rate()
+                            + transaction.fees(),
+                    )]
+                } else {
+                    self.receive(quantity)?
+                };
+                let queue = self.queue_mut(transaction.broker())?;
+                for lot in lots {
+                    queue.add(lot);
                 }
-                cost_of_units_sold += queue[0];
-                queue.pop_front();
             }
+            TransactionType::Div
+            | TransactionType::Deposit
+            | TransactionType::Withdrawal
+            | TransactionType::Interest
+            | TransactionType::Fee => {}
+        }
 
-            // Correct for edge case with decimal units
-            if cumulative_units.round_dp(4) == Decimal::ZERO {
-                while !queue.is_empty() {
-                    queue.pop_front();
-                }
+        let queue = self.queue_mut(transaction.broker())?;
+        Ok(PositionState::new(
+            queue.units(),
+            queue.cost(),
+            cost_of_units_sold,
+        ))
+    }
+
+    /// Takes `quantity` units from the lots in transit, in the order they were
+    /// transferred out.
+    fn receive(&mut self, quantity: Decimal) -> Result<Vec<Lot>> {
+        let available: Decimal = self.in_transit.iter().map(|lot| *lot.quantity()).sum();
+        if quantity - available > QUANTITY_TOLERANCE {
+            return Err(anyhow::anyhow!(
+                "Cannot transfer in {} units: only {} units were transferred out",
+                quantity.normalize(),
+                available.normalize()
+            ));
+        }
+
+        let mut received = Vec::new();
+        let mut remaining = quantity;
+
+        while remaining > Decimal::ZERO
+            && let Some(lot) = self.in_transit.front_mut()
+        {
+            let taken = lot.split_off(remaining);
+            remaining -= taken.quantity();
+            if *lot.quantity() == Decimal::ZERO {
+                self.in_transit.pop_front();
             }
+            received.push(taken);
+        }
+
+        let left: Decimal = self.in_transit.iter().map(|lot| *lot.quantity()).sum();
+        if left.abs() < QUANTITY_TOLERANCE {
+            received.extend(self.in_transit.drain(..));
         }
+
+        Ok(received)
+    }
+}
+
+/// Replays all `transactions` of a ticker (in chronological order) and returns
+/// the open lots per broker.
+pub fn calculate_lots(
+    transactions: &[Transaction],
+    cost_basis: &CostBasisConfig,
+) -> Result<TickerLots> {
+    let mut lots = TickerLots::new(cost_basis.clone());
+    for transaction in transactions {
+        lots.apply(transaction)?;
     }
+    Ok(lots)
+}
+
+/// Replays all `transactions` of a ticker and returns the position state after
+/// the last one.
+pub fn calculate_position_state(
+    transactions: &[Transaction],
+    cost_basis: &CostBasisConfig,
+) -> Result<PositionState> {
+    let mut lots = TickerLots::new(cost_basis.clone());
+    let mut position_state = None;
 
-    let cumulative_cost = queue
+    for transaction in transactions {
+        position_state = Some(lots.apply(transaction)?);
+    }
+
+    position_state
+        .ok_or_else(|| anyhow::anyhow!("Cannot calculate position state: no transactions given"))
+}
+
+/// Sums the cash flows per broker and currency. Only brokers with at least one
+/// cash account transaction get a balance, so portfolios that never record
+/// deposits do not show negative cash from their purchases.
+pub fn calculate_cash_balances(transactions: &[Transaction]) -> Vec<CashBalance> {
+    let cash_brokers: BTreeSet<&String> = transactions
         .iter()
-        .fold(Decimal::ZERO, |sum, &unit_cost| sum + unit_cost);
+        .filter(|t| t.transaction_type().is_cash())
+        .map(|t| t.broker())
+        .collect();
+
+    let mut balances: BTreeMap<(String, String), (Decimal, Decimal)> = BTreeMap::new();
+    for transaction in transactions
+        .iter()
+        .filter(|t| cash_brokers.contains(t.broker()))
+    {
+        let key = (transaction.broker().clone(), transaction.currency().clone());
+        let (balance, income) = balances.entry(key).or_default();
+        let cash_flow = transaction.get_cash_flow();
+
+        *balance += cash_flow;
+        if matches!(
+            transaction.transaction_type(),
+            TransactionType::Interest | TransactionType::Fee
+        ) {
+            *income += cash_flow;
+        }
+    }
+
+    balances
+        .into_iter()
+        .map(|((broker, currency), (balance, income))| {
+            CashBalance::new(broker, currency, balance, income)
+        })
+        .collect()
+}
+
+/// Splits a dividend into gross, taxes and net in the base currency. Without a
+/// treaty rate nothing is considered reclaimable.
+pub fn calculate_dividend_breakdown(
+    transaction: &Transaction,
+    treaty_rate: Option<Decimal>,
+) -> DividendBreakdown {
+    let rate = dec!(1) / transaction.exchange_rate();
+    let gross = transaction.price() * transaction.quantity() * rate;
+    let withholding_tax = transaction.withholding_tax() * rate;
+    let domestic_tax = transaction.domestic_tax() * rate;
+    let net = gross - withholding_tax - domestic_tax - transaction.fees();
+    let reclaimable = treaty_rate
+        .map(|treaty_rate| (withholding_tax - gross * treaty_rate).max(Decimal::ZERO))
+        .unwrap_or_default();
+
+    DividendBreakdown::new(gross, withholding_tax, domestic_tax, reclaimable, net)
+}
+
+/// The source country of a security is taken from its ISIN prefix.
+pub fn country_from_isin(isin: Option<&str>) -> String {
+    isin.filter(|isin| isin.len() >= 2 && isin.is_char_boundary(2))
+        .map(|isin| isin[..2].to_uppercase())
+        .unwrap_or_else(|| String::from("Unknown"))
+}
+
+/// Units of one ticker held across all brokers at the end of each day with
+/// transactions. Like the lots, units are tracked per broker, as every broker
+/// holding the ticker records the split.
+pub fn calculate_units_by_date(transactions: &[Transaction]) -> BTreeMap<NaiveDate, Decimal> {
+    let mut units_by_date = BTreeMap::new();
+    let mut units_by_broker: BTreeMap<&str, Decimal> = BTreeMap::new();
+
+    for transaction in transactions {
+        let units = units_by_broker.entry(transaction.broker()).or_default();
+        if *transaction.transaction_type() == TransactionType::Split {
+            *units *= transaction.quantity();
+        } else {
+            *units += transaction.get_quantity();
+        }
+        units_by_date.insert(
+            transaction.date().date_naive(),
+            units_by_broker.values().sum(),
+        );
+    }
+
+    units_by_date
+}
+
+/// Cost basis in the base currency after each day, summed over the brokers.
+pub fn calculate_cost_by_date(transactions: &[Transaction]) -> BTreeMap<NaiveDate, Decimal> {
+    let mut cost_by_date = BTreeMap::new();
+    let mut cost_by_broker: BTreeMap<&str, Decimal> = BTreeMap::new();
+
+    for transaction in transactions {
+        if let Some(position_state) = transaction.position_state() {
+            cost_by_broker.insert(transaction.broker(), *position_state.cumulative_cost());
+        }
+        cost_by_date.insert(
+            transaction.date().date_naive(),
+            cost_by_broker.values().sum(),
+        );
+    }
+
+    cost_by_date
+}
+
+/// Returns the latest value on or before `date`.
+pub fn value_on_or_before<T: Copy>(series: &BTreeMap<NaiveDate, T>, date: NaiveDate) -> Option<T> {
+    series.range(..=date).next_back().map(|(_, value)| *value)
+}
+
+/// Chain-links the returns between consecutive valuation points, so the
+/// result does not depend on the timing and size of cash flows. Periods that
+/// start without any holdings are skipped.
+pub fn calculate_twr(points: &[PerformancePoint]) -> Option<Decimal> {
+    let mut growth = Decimal::ONE;
+    let mut has_return = false;
+
+    for pair in points.windows(2) {
+        let (previous, current) = (&pair[0], &pair[1]);
+        if *previous.value() <= Decimal::ZERO {
+            continue;
+        }
+        growth *= (current.value() + current.cash_flow()) / previous.value();
+        has_return = true;
+    }
+
+    has_return.then(|| growth - Decimal::ONE)
+}
+
+/// Annualized money-weighted return: the rate at which the starting value,
+/// all cash flows and the final value have a net present value of zero.
+pub fn calculate_xirr(points: &[PerformancePoint]) -> Option<Decimal> {
+    let (first, last) = (points.first()?, points.last()?);
+    if points.len() < 2 || first.date() >= last.date() {
+        return None;
+    }
+
+    let mut flows = Vec::new();
+    for (i, point) in points.iter().enumerate() {
+        let mut amount = *point.cash_flow();
+        if i == 0 {
+            amount -= point.value();
+        }
+        if i == points.len() - 1 {
+            amount += point.value();
+        }
+        if amount != Decimal::ZERO {
+            let years = (*point.date() - *first.date()).num_days() as f64 / 365.0;
+            flows.push((years, amount.to_f64()?));
+        }
+    }
+
+    if !flows.iter().any(|(_, a)| *a > 0.0) || !flows.iter().any(|(_, a)| *a < 0.0) {
+        return None;
+    }
+
+    let npv = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(years, amount)| amount / (1.0 + rate).powf(*years))
+            .sum()
+    };
+    let npv_derivative = |rate: f64| -> f64 {
+        flows
+            .iter()
+            .map(|(years, amount)| -years * amount / (1.0 + rate).powf(years + 1.0))
+            .sum()
+    };
+
+    // Newton's method first, bisection as a fallback when it does not converge
+    let mut rate = 0.1;
+    for _ in 0..100 {
+        let value = npv(rate);
+        if value.abs() < XIRR_TOLERANCE {
+            return Decimal::from_f64(rate).map(|rate| rate.round_dp(6));
+        }
+        let derivative = npv_derivative(rate);
+        if derivative == 0.0 || !derivative.is_finite() {
+            break;
+        }
+        let next = rate - value / derivative;
+        if !next.is_finite() || next <= -1.0 {
+            break;
+        }
+        rate = next;
+    }
+
+    let (mut low, mut high) = (-0.999_999, 1.0);
+    while npv(low).signum() == npv(high).signum() {
+        high *= 2.0;
+        if high > 1e6 {
+            return None;
+        }
+    }
+    for _ in 0..200 {
+        let mid = (low + high) / 2.0;
+        if npv(mid).signum() == npv(low).signum() {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
 
-    Ok(PositionState::new(
-        cumulative_units.abs().round_dp(4),
-        cumulative_cost.abs(),
-        cost_of_units_sold.abs(),
-    ))
+    Decimal::from_f64((low + high) / 2.0).map(|rate| rate.round_dp(6))
 }
 
 pub fn calculate_transaction_gains(
@@ -0,0 +1,482 @@
+use std::{collections::BTreeMap, fs, time::Duration};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use derive_getters::Getters;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::models::{CostBasisMethod, TransactionType, ticker::ApiProvider};
+
+pub const DEFAULT_CONFIG_PATH: &str = "~/.config/portfolio-tracker-tui/config.toml";
+pub const DEFAULT_FIXTURES_DIR: &str = "~/.local/share/portfolio-tracker-tui/fixtures";
+
+#[derive(Debug, Default, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path to the configuration file
+    #[arg(short, long)]
+    pub config: Option<String>,
+
+    /// Currency all positions are valued in, e.g. EUR, USD or CHF
+    #[arg(long)]
+    pub base_currency: Option<String>,
+
+    /// Path to the SQLite database
+    #[arg(long)]
+    pub database: Option<String>,
+
+    /// Transactions to import: a CSV file, an IBKR Flex Query (XML), an OFX or
+    /// QFX statement, or a Degiro or Trading 212 export (can be given multiple
+    /// times)
+    #[arg(short, long = "transactions")]
+    pub transaction_files: Vec<String>,
+
+    /// Default API provider for new tickers
+    #[arg(long)]
+    pub api: Option<String>,
+
+    /// Cost-basis method: FIFO, LIFO, Average or HIFO
+    #[arg(long)]
+    pub cost_basis: Option<String>,
+
+    /// Use the local fixtures for quotes and exchange rates
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Download the missing price history of all tickers before starting
+    #[arg(long)]
+    pub backfill: bool,
+
+    /// ECB reference rate file (CSV or XML) to load into the exchange rate cache
+    #[arg(long)]
+    pub fx_rates: Option<String>,
+
+    /// Write positions, transactions and realized gains as CSV or JSON to the
+    /// export directory and exit
+    #[arg(long, value_name = "FORMAT")]
+    pub export: Option<String>,
+
+    /// Directory exports are written to
+    #[arg(long)]
+    pub export_dir: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Getters)]
+#[serde(default)]
+pub struct Config {
+    base_currency: String,
+    database_path: String,
+    transaction_files: Vec<String>,
+    export_directory: String,
+    providers: ProviderConfig,
+    refresh: RefreshConfig,
+    cost_basis: CostBasisConfig,
+    dividends: DividendConfig,
+    import_profiles: BTreeMap<String, ImportProfile>,
+}
+
+#[derive(Clone, Debug, Deserialize, Getters)]
+#[serde(default)]
+pub struct ProviderConfig {
+    default_api: String,
+    forex: String,
+    alpha_vantage_api_key: Option<String>,
+    fmp_api_key: Option<String>,
+    marketstack_api_key: Option<String>,
+    local_fixtures_dir: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Getters)]
+#[serde(default)]
+pub struct RefreshConfig {
+    update_prices_on_startup: bool,
+    interval_minutes: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Getters, PartialEq)]
+#[serde(default)]
+pub struct CostBasisConfig {
+    method: String,
+    brokers: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            base_currency: String::from("EUR"),
+            database_path: String::from("~/.local/share/portfolio-tracker-tui/portfolio.db"),
+            transaction_files: vec![String::from(
+                "~/.config/portfolio-tracker-tui/transactions.csv",
+            )],
+            export_directory: String::from("~/.local/share/portfolio-tracker-tui/export"),
+            providers: ProviderConfig::default(),
+            refresh: RefreshConfig::default(),
+            cost_basis: CostBasisConfig::default(),
+            dividends: DividendConfig::default(),
+            import_profiles: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            default_api: String::from("Marketstack"),
+            forex: String::from("Frankfurter"),
+            alpha_vantage_api_key: None,
+            fmp_api_key: None,
+            marketstack_api_key: None,
+            local_fixtures_dir: None,
+        }
+    }
+}
+
+/// Withholding tax rates by ISIN country code that the source country keeps
+/// after a reclaim under the applicable tax treaty, e.g. `US = 0.15`.
+#[derive(Clone, Debug, Default, Deserialize, Getters)]
+#[serde(default)]
+pub struct DividendConfig {
+    treaty_rates: BTreeMap<String, Decimal>,
+}
+
+/// Maps the columns of a broker's CSV export onto the import format. A file is
+/// read with the first profile whose columns all appear in its header.
+/// `types` maps the broker's keywords, e.g. `Kauf = "Buy"`, onto transaction
+/// types; `broker` is used when no broker column is mapped.
+#[derive(Clone, Debug, Deserialize, Getters, PartialEq)]
+#[serde(default)]
+pub struct ImportProfile {
+    delimiter: char,
+    decimal_separator: char,
+    date_format: String,
+    broker: String,
+    columns: ImportColumns,
+    types: BTreeMap<String, String>,
+}
+
+/// Header names of the columns holding the fields of the import format. Fees
+/// are expected in the base currency.
+#[derive(Clone, Debug, Default, Deserialize, Getters, PartialEq)]
+pub struct ImportColumns {
+    date: String,
+    transaction_type: String,
+    price: String,
+    transaction_no: Option<String>,
+    symbol: Option<String>,
+    quantity: Option<String>,
+    fees: Option<String>,
+    broker: Option<String>,
+    alternative_symbol: Option<String>,
+    transaction_currency: Option<String>,
+    withholding_tax: Option<String>,
+    domestic_tax: Option<String>,
+}
+
+impl Default for ImportProfile {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            decimal_separator: '.',
+            date_format: String::from("%Y-%m-%d"),
+            broker: String::new(),
+            columns: ImportColumns::default(),
+            types: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for CostBasisConfig {
+    fn default() -> Self {
+        Self {
+            method: CostBasisMethod::default().to_str().to_string(),
+            brokers: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the config file (falling back to defaults if it does not exist),
+    /// fills missing API keys from the environment and applies the CLI flags.
+    pub fn load(cli: &Cli) -> Result<Config> {
+        let path = cli.config.as_deref().unwrap_or(DEFAULT_CONFIG_PATH);
+        let path_expanded = shellexpand::tilde(path);
+
+        let mut config = if fs::exists(path_expanded.as_ref())? {
+            let content = fs::read_to_string(path_expanded.as_ref())
+                .with_context(|| format!("Failed to read config file at path: {}", path))?;
+            Self::parse_str(&content)
+                .with_context(|| format!("Failed to parse config file at path: {}", path))?
+        } else if cli.config.is_some() {
+            return Err(anyhow::anyhow!("Config file not found at path: {}", path));
+        } else {
+            Config::default()
+        };
+
+        config.providers.fill_api_keys_from_env();
+        config.apply_cli(cli);
+        config.cost_basis.validate()?;
+        config.normalize_base_currency()?;
+
+        Ok(config)
+    }
+
+    /// The default settings with API keys and the fixtures directory taken
+    /// from the environment.
+    pub fn from_env() -> Config {
+        let mut config = Config::default();
+        config.providers.fill_api_keys_from_env();
+        config
+    }
+
+    pub fn parse_str(content: &str) -> Result<Config> {
+        let mut config: Config = toml::from_str(content)?;
+        config.normalize_base_currency()?;
+        if !config.providers.use_local_forex()
+            && !config.providers.forex.eq_ignore_ascii_case("frankfurter")
+        {
+            return Err(anyhow::anyhow!(
+                "Unknown forex provider '{}': expected 'Frankfurter' or 'Local'",
+                config.providers.forex
+            ));
+        }
+        config.cost_basis.validate()?;
+        for (name, profile) in config.import_profiles.iter() {
+            profile
+                .validate()
+                .with_context(|| format!("Invalid import profile '{}'", name))?;
+        }
+        Ok(config)
+    }
+
+    /// Currency codes are compared as the importers and providers write them,
+    /// in upper case.
+    fn normalize_base_currency(&mut self) -> Result<()> {
+        let base_currency = self.base_currency.trim().to_uppercase();
+        if base_currency.len() != 3 || !base_currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(anyhow::anyhow!(
+                "Invalid base_currency '{}': expected a three-letter code like EUR",
+                self.base_currency
+            ));
+        }
+        self.base_currency = base_currency;
+        Ok(())
+    }
+
+    pub fn apply_cli(&mut self, cli: &Cli) {
+        if let Some(base_currency) = &cli.base_currency {
+            self.base_currency = base_currency.to_uppercase();
+        }
+        if let Some(database) = &cli.database {
+            self.database_path = database.clone();
+        }
+        if !cli.transaction_files.is_empty() {
+            self.transaction_files = cli.transaction_files.clone();
+        }
+        if let Some(export_dir) = &cli.export_dir {
+            self.export_directory = export_dir.clone();
+        }
+        if let Some(cost_basis) = &cli.cost_basis {
+            self.cost_basis.method = cost_basis.clone();
+        }
+        if let Some(api) = &cli.api {
+            self.providers.default_api = api.clone();
+        }
+        if cli.demo {
+            self.providers.default_api = ApiProvider::Local.to_str().to_string();
+            self.providers.forex = String::from("Local");
+            if self.providers.local_fixtures_dir.is_none() {
+                self.providers.local_fixtures_dir = Some(String::from(DEFAULT_FIXTURES_DIR));
+            }
+        }
+    }
+
+    pub fn database_path_expanded(&self) -> String {
+        shellexpand::tilde(&self.database_path).to_string()
+    }
+
+    pub fn transaction_files_expanded(&self) -> Vec<String> {
+        self.transaction_files
+            .iter()
+            .map(|path| shellexpand::tilde(path).to_string())
+            .collect()
+    }
+
+    pub fn export_directory_expanded(&self) -> String {
+        shellexpand::tilde(&self.export_directory).to_string()
+    }
+
+    pub fn local_fixtures_dir_expanded(&self) -> Option<String> {
+        self.providers
+            .local_fixtures_dir
+            .as_ref()
+            .map(|dir| shellexpand::tilde(dir).to_string())
+    }
+
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        match self.refresh.interval_minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(minutes * 60)),
+        }
+    }
+}
+
+impl ProviderConfig {
+    fn fill_api_keys_from_env(&mut self) {
+        if self.alpha_vantage_api_key.is_none() {
+            self.alpha_vantage_api_key = std::env::var("ALPHA_VANTAGE_API_KEY").ok();
+        }
+        if self.fmp_api_key.is_none() {
+            self.fmp_api_key = std::env::var("FMP_API_KEY").ok();
+        }
+        if self.marketstack_api_key.is_none() {
+            self.marketstack_api_key = std::env::var("MARKETSTACK_API_KEY").ok();
+        }
+        if self.local_fixtures_dir.is_none() {
+            self.local_fixtures_dir = std::env::var("LOCAL_FIXTURES_DIR").ok();
+        }
+    }
+
+    pub fn use_local_forex(&self) -> bool {
+        self.forex.eq_ignore_ascii_case("local")
+    }
+}
+
+impl CostBasisConfig {
+    pub fn new(method: CostBasisMethod) -> Self {
+        Self {
+            method: method.to_str().to_string(),
+            brokers: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_broker(mut self, broker: &str, method: CostBasisMethod) -> Self {
+        self.brokers
+            .insert(broker.to_string(), method.to_str().to_string());
+        self
+    }
+
+    fn validate(&self) -> Result<()> {
+        CostBasisMethod::parse_str(&self.method)
+            .with_context(|| format!("Unknown cost basis method '{}'", self.method))?;
+        for (broker, method) in self.brokers.iter() {
+            CostBasisMethod::parse_str(method).with_context(|| {
+                format!(
+                    "Unknown cost basis method '{}' for broker {}",
+                    method, broker
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the broker's own method if one is configured, otherwise the
+    /// portfolio-wide one.
+    pub fn method_for_broker(&self, broker: &str) -> Result<CostBasisMethod> {
+        let method = self.brokers.get(broker).unwrap_or(&self.method);
+        CostBasisMethod::parse_str(method)
+            .with_context(|| format!("Unknown cost basis method '{}'", method))
+    }
+
+    /// A canonical description of the settings, stored alongside the computed
+    /// transactions to detect when they have to be recalculated.
+    pub fn signature(&self) -> Result<String> {
+        let mut signature = CostBasisMethod::parse_str(&self.method)?
+            .to_str()
+            .to_string();
+        for broker in self.brokers.keys() {
+            signature.push_str(&format!(
+                ";{}={}",
+                broker,
+                self.method_for_broker(broker)?.to_str()
+            ));
+        }
+        Ok(signature)
+    }
+}
+
+impl ImportProfile {
+    fn validate(&self) -> Result<()> {
+        if !matches!(self.decimal_separator, '.' | ',') {
+            return Err(anyhow::anyhow!(
+                "Unknown decimal separator '{}': expected '.' or ','",
+                self.decimal_separator
+            ));
+        }
+        if !self.delimiter.is_ascii() {
+            return Err(anyhow::anyhow!(
+                "The delimiter '{}' must be an ASCII character",
+                self.delimiter
+            ));
+        }
+        if self.decimal_separator == self.delimiter {
+            return Err(anyhow::anyhow!(
+                "The delimiter and the decimal separator must differ"
+            ));
+        }
+        if self.columns.date.is_empty()
+            || self.columns.transaction_type.is_empty()
+            || self.columns.price.is_empty()
+        {
+            return Err(anyhow::anyhow!(
+                "The date, transaction_type and price columns are required"
+            ));
+        }
+        if self.broker.is_empty() && self.columns.broker.is_none() {
+            return Err(anyhow::anyhow!(
+                "Either a broker or a broker column is required"
+            ));
+        }
+        for (keyword, transaction_type) in self.types.iter() {
+            TransactionType::parse_str(transaction_type).with_context(|| {
+                format!(
+                    "Unknown transaction type '{}' for '{}'",
+                    transaction_type, keyword
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// The mapped header names.
+    pub fn column_names(&self) -> Vec<&str> {
+        let columns = &self.columns;
+        [
+            Some(&columns.date),
+            Some(&columns.transaction_type),
+            Some(&columns.price),
+            columns.transaction_no.as_ref(),
+            columns.symbol.as_ref(),
+            columns.quantity.as_ref(),
+            columns.fees.as_ref(),
+            columns.broker.as_ref(),
+            columns.alternative_symbol.as_ref(),
+            columns.transaction_currency.as_ref(),
+            columns.withholding_tax.as_ref(),
+            columns.domestic_tax.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect()
+    }
+
+    /// Looks up a broker keyword, ignoring case, or else a transaction type
+    /// by name.
+    pub fn transaction_type(&self, value: &str) -> Result<TransactionType> {
+        let value = value.trim();
+        let type_str = self
+            .types
+            .iter()
+            .find(|(keyword, _)| keyword.eq_ignore_ascii_case(value))
+            .map_or(value, |(_, transaction_type)| transaction_type.as_str());
+        TransactionType::parse_str(type_str)
+            .with_context(|| format!("Unknown transaction type '{}'", value))
+    }
+}
+
+impl DividendConfig {
+    pub fn treaty_rate(&self, country: &str) -> Option<Decimal> {
+        self.treaty_rates.get(country).copied()
+    }
+}
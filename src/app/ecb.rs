@@ -0,0 +1,106 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use csv::ReaderBuilder;
+
+use crate::models::FxRate;
+
+use super::utils::parse_decimal;
+
+const ECB_SOURCE: &str = "ECB";
+const ECB_BASE_CURRENCY: &str = "EUR";
+
+/// Reads an ECB euro foreign exchange reference rate file, either the CSV
+/// (`eurofxref-hist.csv`, `eurofxref.csv`) or the XML
+/// (`eurofxref-hist.xml`, `eurofxref-daily.xml`) download.
+pub fn parse_ecb_file(path: &str) -> Result<Vec<FxRate>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read ECB rates from {}", path))?;
+
+    let is_xml = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+    if is_xml {
+        parse_ecb_xml(&content)
+    } else {
+        parse_ecb_csv(&content)
+    }
+    .with_context(|| format!("Failed to parse ECB rates from {}", path))
+}
+
+/// One row per day with a column per currency; missing rates are `N/A`.
+pub fn parse_ecb_csv(content: &str) -> Result<Vec<FxRate>> {
+    let mut reader = ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let currencies: Vec<String> = reader
+        .headers()
+        .with_context(|| "Failed to read CSV header")?
+        .iter()
+        .map(str::to_string)
+        .collect();
+
+    let mut rates = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.with_context(|| format!("Failed to read CSV record {}", i + 1))?;
+        let Some(date) = record.get(0) else {
+            continue;
+        };
+        let date = parse_ecb_date(date).with_context(|| format!("In record {}", i + 1))?;
+
+        for (currency, value) in currencies.iter().zip(record.iter()).skip(1) {
+            if currency.is_empty() || value.is_empty() || value == "N/A" {
+                continue;
+            }
+            rates.push(FxRate::new(
+                ECB_BASE_CURRENCY.to_string(),
+                currency.clone(),
+                date,
+                parse_decimal(value, "rate")?,
+                ECB_SOURCE.to_string(),
+            ));
+        }
+    }
+
+    Ok(rates)
+}
+
+/// `<Cube time="...">` elements holding `<Cube currency="..." rate="..."/>`.
+pub fn parse_ecb_xml(content: &str) -> Result<Vec<FxRate>> {
+    let document = roxmltree::Document::parse(content).with_context(|| "Invalid XML")?;
+
+    let mut rates = Vec::new();
+    for day in document
+        .descendants()
+        .filter(|node| node.has_tag_name("Cube"))
+    {
+        let Some(time) = day.attribute("time") else {
+            continue;
+        };
+        let date = parse_ecb_date(time)?;
+
+        for cube in day.children().filter(|node| node.has_tag_name("Cube")) {
+            let (Some(currency), Some(rate)) = (cube.attribute("currency"), cube.attribute("rate"))
+            else {
+                continue;
+            };
+            rates.push(FxRate::new(
+                ECB_BASE_CURRENCY.to_string(),
+                currency.to_string(),
+                date,
+                parse_decimal(rate, "rate")?,
+                ECB_SOURCE.to_string(),
+            ));
+        }
+    }
+
+    Ok(rates)
+}
+
+fn parse_ecb_date(field: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(field, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(field, "%d %B %Y"))
+        .with_context(|| format!("Failed to parse date '{}'", field))
+}
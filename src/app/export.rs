@@ -0,0 +1,288 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use csv::Writer;
+use derive_getters::Getters;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde_json::{Map, Value};
+
+use crate::models::{LedgerEntry, Position, TransactionType};
+
+use super::importers::IMPORT_COLUMNS;
+
+/// Columns computed by the chain replay, written after the import columns.
+const TRANSACTION_STATE_COLUMNS: [&str; 5] = [
+    "cumulative_units",
+    "cumulative_cost",
+    "cost_of_units_sold",
+    "realized_gain",
+    "dividend",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse_str(s: &str) -> Result<ExportFormat> {
+        match s.to_uppercase().as_str() {
+            "CSV" => Ok(ExportFormat::Csv),
+            "JSON" => Ok(ExportFormat::Json),
+            _ => Err(anyhow::anyhow!(
+                "Unknown export format '{}': expected CSV or JSON",
+                s
+            )),
+        }
+    }
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Json => "JSON",
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// A value of an exported table. Numbers are written exactly to CSV and as
+/// numbers to JSON; empty fields become `null` in JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Text(String),
+    Integer(i64),
+    Number(Decimal),
+    Empty,
+}
+
+impl Field {
+    fn to_csv(&self) -> String {
+        match self {
+            Field::Text(text) => text.clone(),
+            Field::Integer(integer) => integer.to_string(),
+            Field::Number(number) => number.normalize().to_string(),
+            Field::Empty => String::new(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Field::Text(text) => Value::from(text.as_str()),
+            Field::Integer(integer) => Value::from(*integer),
+            Field::Number(number) => number.to_f64().map_or(Value::Null, Value::from),
+            Field::Empty => Value::Null,
+        }
+    }
+}
+
+impl From<Option<&String>> for Field {
+    fn from(text: Option<&String>) -> Self {
+        text.map_or(Field::Empty, |text| Field::Text(text.clone()))
+    }
+}
+
+/// Rows of one export file, written as CSV with a header or as a JSON array
+/// of objects keyed by column.
+#[derive(Clone, Debug, Getters)]
+pub struct ExportTable {
+    name: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<Field>>,
+}
+
+impl ExportTable {
+    pub fn to_csv(&self) -> Result<String> {
+        let mut writer = Writer::from_writer(Vec::new());
+        writer.write_record(&self.columns)?;
+        for row in self.rows.iter() {
+            writer.write_record(row.iter().map(Field::to_csv))?;
+        }
+        let bytes = writer
+            .into_inner()
+            .with_context(|| format!("Failed to write {} as CSV", self.name))?;
+        Ok(String::from_utf8(bytes)?)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        let rows: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let object: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().map(Field::to_json))
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows)
+            .with_context(|| format!("Failed to write {} as JSON", self.name))
+    }
+
+    /// Writes the table to `<name>.<extension>` in `directory`, which is
+    /// created if missing.
+    pub fn write(&self, directory: &Path, format: ExportFormat) -> Result<PathBuf> {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Failed to create directory {}", directory.display()))?;
+        let path = directory.join(format!("{}.{}", self.name, format.extension()));
+        let content = match format {
+            ExportFormat::Csv => self.to_csv()?,
+            ExportFormat::Json => self.to_json()?,
+        };
+        fs::write(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+fn table(name: &str, columns: &[&str], rows: Vec<Vec<Field>>) -> ExportTable {
+    ExportTable {
+        name: name.to_string(),
+        columns: columns.iter().map(|column| column.to_string()).collect(),
+        rows,
+    }
+}
+
+/// All fields of the positions with their asset, amounts in the base currency.
+pub fn positions_table(positions: &[Position]) -> ExportTable {
+    let rows = positions
+        .iter()
+        .map(|position| {
+            let asset = position.asset();
+            vec![
+                Field::Integer(*asset.id()),
+                Field::Text(asset.name().clone()),
+                Field::Text(asset.asset_type().to_str().to_string()),
+                Field::from(asset.isin().as_ref()),
+                Field::from(asset.sector().as_ref()),
+                Field::from(asset.industry().as_ref()),
+                Field::Number(*position.quantity()),
+                Field::Number(*position.price()),
+                Field::Number(*position.market_value()),
+                Field::Number(*position.total_cost()),
+                Field::Number(*position.cost_per_share()),
+                Field::Number(*position.unrealized_gain()),
+                Field::Number(*position.unrealized_gain_percent()),
+                Field::Number(*position.realized_gain()),
+                Field::Number(*position.dividend()),
+                Field::Number(*position.total_gain()),
+            ]
+        })
+        .collect();
+
+    table(
+        "positions",
+        &[
+            "asset_id",
+            "name",
+            "asset_type",
+            "isin",
+            "sector",
+            "industry",
+            "quantity",
+            "price",
+            "market_value",
+            "total_cost",
+            "cost_per_share",
+            "unrealized_gain",
+            "unrealized_gain_percent",
+            "realized_gain",
+            "dividend",
+            "total_gain",
+        ],
+        rows,
+    )
+}
+
+/// The transactions in the CSV import format, so the CSV file can be imported
+/// again, followed by their position state and gains.
+pub fn transactions_table(entries: &[LedgerEntry]) -> ExportTable {
+    let rows = entries
+        .iter()
+        .map(|entry| {
+            let transaction = entry.transaction();
+            let state = transaction.position_state().as_ref();
+            let gains = transaction.transaction_gains().as_ref();
+            let computed = |value: Option<Decimal>| value.map_or(Field::Empty, Field::Number);
+            vec![
+                Field::Integer(*transaction.transaction_no()),
+                Field::Text(transaction.date().format("%Y-%m-%d").to_string()),
+                Field::Text(transaction.transaction_type().to_str().to_string()),
+                Field::Text(entry.symbol().clone()),
+                Field::Number(*transaction.quantity()),
+                Field::Number(*transaction.price()),
+                Field::Number(*transaction.fees()),
+                Field::Text(transaction.broker().clone()),
+                Field::from(entry.alternative_symbol().as_ref()),
+                Field::Text(transaction.currency().clone()),
+                Field::Number(*transaction.withholding_tax()),
+                Field::Number(*transaction.domestic_tax()),
+                Field::Number(*transaction.exchange_rate()),
+                Field::from(entry.external_id().as_ref()),
+                computed(state.map(|state| *state.cumulative_units())),
+                computed(state.map(|state| *state.cumulative_cost())),
+                computed(state.map(|state| *state.cost_of_units_sold())),
+                computed(gains.map(|gains| *gains.realized_gain())),
+                computed(gains.map(|gains| *gains.dividend())),
+            ]
+        })
+        .collect();
+
+    let columns: Vec<&str> = IMPORT_COLUMNS
+        .iter()
+        .chain(TRANSACTION_STATE_COLUMNS.iter())
+        .copied()
+        .collect();
+    table("transactions", &columns, rows)
+}
+
+/// One record per sale: the net proceeds, the cost of the units sold under the
+/// cost-basis method and the difference, all in the base currency.
+pub fn realized_gains_table(entries: &[LedgerEntry]) -> ExportTable {
+    let rows = entries
+        .iter()
+        .filter(|entry| entry.transaction().transaction_type() == &TransactionType::Sell)
+        .filter_map(|entry| {
+            let transaction = entry.transaction();
+            let state = transaction.position_state().as_ref()?;
+            let gains = transaction.transaction_gains().as_ref()?;
+            Some(vec![
+                Field::Integer(*transaction.transaction_no()),
+                Field::Text(transaction.date().format("%Y-%m-%d").to_string()),
+                Field::Text(entry.symbol().clone()),
+                Field::Text(transaction.broker().clone()),
+                Field::Number(*transaction.quantity()),
+                Field::Number(transaction.get_amount().abs()),
+                Field::Number(*state.cost_of_units_sold()),
+                Field::Number(*gains.realized_gain()),
+            ])
+        })
+        .collect();
+
+    table(
+        "realized_gains",
+        &[
+            "transaction_no",
+            "date",
+            "symbol",
+            "broker",
+            "quantity",
+            "proceeds",
+            "cost_of_units_sold",
+            "realized_gain",
+        ],
+        rows,
+    )
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use derive_getters::Getters;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::models::{LedgerEntry, TransactionInput, TransactionType};
+
+use super::utils::{parse_datetime, parse_decimal, parse_optional_decimal, parse_split_ratio};
+
+pub const FORM_FIELDS: [&str; 8] = [
+    "Date", "Type", "Symbol", "Quantity", "Price", "Fees", "Broker", "Currency",
+];
+
+const TYPE_FIELD: usize = 1;
+
+/// The add and edit transaction form. Values are kept as typed and only
+/// validated on submit; the type is picked from [`TransactionType::ALL`].
+#[derive(Clone, Debug, Getters)]
+pub struct TransactionForm {
+    id: Option<i64>,
+    transaction_type: TransactionType,
+    values: [String; 8],
+    focus: usize,
+}
+
+impl TransactionForm {
+    pub fn new(today: NaiveDate) -> Self {
+        let mut values: [String; 8] = Default::default();
+        values[0] = today.format("%Y-%m-%d").to_string();
+        Self {
+            id: None,
+            transaction_type: TransactionType::Buy,
+            values,
+            focus: 0,
+        }
+    }
+
+    /// A form prefilled with a stored transaction. The price is shown in the
+    /// ticker currency it is stored in.
+    pub fn edit(entry: &LedgerEntry) -> Self {
+        let transaction = entry.transaction();
+        Self {
+            id: Some(*transaction.id()),
+            transaction_type: transaction.transaction_type().clone(),
+            values: [
+                transaction.date().format("%Y-%m-%d").to_string(),
+                String::new(),
+                entry.symbol().clone(),
+                transaction.quantity().normalize().to_string(),
+                transaction.price().normalize().to_string(),
+                transaction.fees().normalize().to_string(),
+                transaction.broker().clone(),
+                transaction.currency().clone(),
+            ],
+            focus: 0,
+        }
+    }
+
+    pub fn value(&self, field: usize) -> &str {
+        if field == TYPE_FIELD {
+            self.transaction_type.to_str()
+        } else {
+            &self.values[field]
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        self.focus = (self.focus + 1) % FORM_FIELDS.len();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.focus = (self.focus + FORM_FIELDS.len() - 1) % FORM_FIELDS.len();
+    }
+
+    pub fn push(&mut self, c: char) {
+        if self.focus != TYPE_FIELD {
+            self.values[self.focus].push(c);
+        }
+    }
+
+    pub fn pop(&mut self) {
+        self.values[self.focus].pop();
+    }
+
+    /// Left and Right pick the type while it has the focus.
+    pub fn cycle_type(&mut self, forward: bool) {
+        if self.focus == TYPE_FIELD {
+            self.transaction_type = if forward {
+                self.transaction_type.next()
+            } else {
+                self.transaction_type.previous()
+            };
+        }
+    }
+
+    /// Validates the values, rejecting dates after `today`.
+    pub fn to_input(&self, today: NaiveDate) -> Result<TransactionInput> {
+        let transaction_type = &self.transaction_type;
+        let [date, _, symbol, quantity, price, fees, broker, currency] =
+            self.values.each_ref().map(|value| value.trim());
+
+        let naive_date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .with_context(|| format!("Invalid date '{}', expected YYYY-MM-DD", date))?;
+        if naive_date > today {
+            return Err(anyhow::anyhow!("Date {} is in the future", naive_date));
+        }
+
+        let symbol = symbol.to_uppercase();
+        if symbol.is_empty() && !transaction_type.is_cash() {
+            return Err(anyhow::anyhow!(
+                "Symbol is required for {} transactions",
+                transaction_type.to_str()
+            ));
+        }
+
+        let quantity = if *transaction_type == TransactionType::Split {
+            parse_split_ratio(quantity)?
+        } else if transaction_type.is_cash() && quantity.is_empty() {
+            // Cash transactions give the amount as price
+            dec!(1)
+        } else {
+            parse_decimal(quantity, "quantity")?
+        };
+        if quantity <= Decimal::ZERO {
+            return Err(anyhow::anyhow!("Quantity must be greater than zero"));
+        }
+
+        let price = if transaction_type.has_cash_flow() {
+            let price = parse_decimal(price, "price")?;
+            if price <= Decimal::ZERO {
+                return Err(anyhow::anyhow!("Price must be greater than zero"));
+            }
+            price
+        } else {
+            parse_optional_decimal(price, "price")?
+        };
+        if price < Decimal::ZERO {
+            return Err(anyhow::anyhow!("Price must not be negative"));
+        }
+
+        let fees = parse_optional_decimal(fees, "fees")?;
+        if fees < Decimal::ZERO {
+            return Err(anyhow::anyhow!("Fees must not be negative"));
+        }
+
+        if broker.is_empty() {
+            return Err(anyhow::anyhow!("Broker is required"));
+        }
+
+        let currency = currency.to_uppercase();
+        if !currency.is_empty()
+            && (currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()))
+        {
+            return Err(anyhow::anyhow!(
+                "Invalid currency '{}', expected a code like EUR",
+                currency
+            ));
+        }
+
+        Ok(TransactionInput::new(
+            parse_datetime(date)?,
+            transaction_type.clone(),
+            symbol,
+            broker.to_string(),
+            currency,
+            quantity,
+            price,
+            fees,
+        ))
+    }
+}
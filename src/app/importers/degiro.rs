@@ -0,0 +1,352 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use csv::StringRecord;
+use regex::Regex;
+use rust_decimal::Decimal;
+
+use crate::{app::utils::parse_decimal, models::TransactionType};
+
+use super::{RowIds, StatementRecord, csv_reader, external_id, header_index};
+
+const BROKER: &str = "Degiro";
+
+const COLUMNS: [&str; 8] = [
+    "date",
+    "product",
+    "isin",
+    "description",
+    "fx",
+    "change",
+    "balance",
+    "order id",
+];
+
+static TRADE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(Buy|Sell) ([\d.,]+) .*@([\d.,]+) ([A-Z]{3})").expect("valid regex")
+});
+
+/// A row of the account statement. The amount follows the currency in the
+/// unnamed column after `Change`.
+struct AccountRow {
+    record: usize,
+    id: String,
+    date: NaiveDate,
+    isin: String,
+    description: String,
+    fx: Option<Decimal>,
+    currency: String,
+    amount: Decimal,
+    order_id: String,
+}
+
+impl AccountRow {
+    fn is_fx(&self) -> bool {
+        self.description.starts_with("FX ")
+    }
+
+    fn is_fee(&self) -> bool {
+        let description = self.description.to_lowercase();
+        ["fee", "stamp duty", "transaction tax"]
+            .iter()
+            .any(|keyword| description.contains(keyword))
+    }
+}
+
+/// Whether `content` is a Degiro account statement (`Account.csv`).
+pub fn is_account_statement(content: &str) -> bool {
+    csv_reader(content, b',').headers().is_ok_and(|header| {
+        let index = header_index(header);
+        COLUMNS.iter().all(|column| index.contains_key(*column))
+    })
+}
+
+/// Reads the English account statement of Degiro, which books every trade
+/// with its fees and currency conversions as separate rows of the same order.
+/// These are folded into one trade per order, converted at the rate of the
+/// conversion, and dividend tax into the dividend of the same security and day.
+/// Transfers between the cash and money market accounts are skipped.
+pub fn parse_account_statement(
+    content: &str,
+    base_currency: &str,
+) -> Result<Vec<Result<StatementRecord>>> {
+    let mut reader = csv_reader(content, b',');
+    let index = header_index(
+        reader
+            .headers()
+            .with_context(|| "Failed to read CSV headers")?,
+    );
+    let mut row_ids = RowIds::default();
+
+    let mut results = Vec::new();
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let row = record
+            .with_context(|| format!("Failed to read CSV record {}", i + 1))
+            .and_then(|record| {
+                let field = |name: &str| {
+                    index
+                        .get(name)
+                        .and_then(|i| record.get(*i))
+                        .unwrap_or_default()
+                        .trim()
+                };
+                let id = row_ids.next(&[
+                    field("date"),
+                    field("time"),
+                    field("isin"),
+                    field("description"),
+                ]);
+                parse_row(&record, &index, i + 1, id)
+            });
+        match row {
+            Ok(row) => rows.push(row),
+            Err(e) => results.push(Err(e)),
+        }
+    }
+
+    let mut orders: Vec<(&str, Vec<&AccountRow>)> = Vec::new();
+    for row in rows.iter().filter(|row| !row.order_id.is_empty()) {
+        match orders.iter_mut().find(|(id, _)| *id == row.order_id) {
+            Some((_, order_rows)) => order_rows.push(row),
+            None => orders.push((&row.order_id, vec![row])),
+        }
+    }
+    for (order_id, order_rows) in orders {
+        results.push(
+            parse_order(&order_rows, base_currency)
+                .with_context(|| format!("Failed to parse order {}", order_id)),
+        );
+    }
+
+    results.extend(parse_cash_rows(
+        rows.iter().filter(|row| row.order_id.is_empty()),
+    ));
+
+    Ok(results)
+}
+
+fn parse_row(
+    record: &StringRecord,
+    index: &HashMap<String, usize>,
+    record_no: usize,
+    id: String,
+) -> Result<AccountRow> {
+    let column = |name: &str| index.get(name).copied().unwrap_or_default();
+    let field = |i: usize| record.get(i).unwrap_or_default().trim();
+
+    let date = NaiveDate::parse_from_str(field(column("date")), "%d-%m-%Y").with_context(|| {
+        format!(
+            "Failed to parse date '{}' in record {}",
+            field(column("date")),
+            record_no
+        )
+    })?;
+    let fx = match field(column("fx")) {
+        "" => None,
+        fx => Some(
+            parse_amount(fx)
+                .with_context(|| format!("Failed to parse FX in record {}", record_no))?,
+        ),
+    };
+    let amount = parse_amount(field(column("change") + 1))
+        .with_context(|| format!("Failed to parse the change in record {}", record_no))?;
+
+    Ok(AccountRow {
+        record: record_no,
+        id,
+        date,
+        isin: field(column("isin")).to_string(),
+        description: field(column("description")).to_string(),
+        fx,
+        currency: field(column("change")).to_string(),
+        amount,
+        order_id: field(column("order id")).to_string(),
+    })
+}
+
+/// Amounts use a dot or, in some exports, a comma as decimal separator.
+fn parse_amount(value: &str) -> Result<Decimal> {
+    let normalized = if value.contains(',') && !value.contains('.') {
+        value.replace(',', ".")
+    } else {
+        value.replace(',', "")
+    };
+    parse_decimal(&normalized, "amount")
+}
+
+/// Descriptions write quantities and prices in English, with commas
+/// separating thousands.
+fn parse_trade_number(value: &str, field_name: &str) -> Result<Decimal> {
+    parse_decimal(&value.replace(',', ""), field_name)
+}
+
+fn parse_order(rows: &[&AccountRow], base_currency: &str) -> Result<StatementRecord> {
+    let mut transaction_type = None;
+    let mut quantity = Decimal::ZERO;
+    let mut value = Decimal::ZERO;
+    let mut currency = String::new();
+    let mut trade = None;
+
+    for row in rows.iter().filter(|row| !row.is_fx() && !row.is_fee()) {
+        let captures = TRADE.captures(&row.description).with_context(|| {
+            format!(
+                "Unsupported description '{}' in record {}",
+                row.description, row.record
+            )
+        })?;
+        let row_type = match &captures[1] {
+            "Buy" => TransactionType::Buy,
+            _ => TransactionType::Sell,
+        };
+        if transaction_type
+            .as_ref()
+            .is_some_and(|transaction_type| *transaction_type != row_type)
+        {
+            return Err(anyhow::anyhow!(
+                "Buys and sells in the same order in record {}",
+                row.record
+            ));
+        }
+        let row_quantity = parse_trade_number(&captures[2], "quantity")?;
+        quantity += row_quantity;
+        value += row_quantity * parse_trade_number(&captures[3], "price")?;
+        currency = captures[4].to_string();
+        transaction_type = Some(row_type);
+        trade = trade.or(Some(*row));
+    }
+    let (Some(transaction_type), Some(trade)) = (transaction_type, trade) else {
+        return Err(anyhow::anyhow!("No trade in the rows of the order"));
+    };
+    if quantity == Decimal::ZERO {
+        return Err(anyhow::anyhow!("Zero quantity in record {}", trade.record));
+    }
+
+    // The conversion books the trade's value in the account currency, with
+    // the rate in units of the trade currency
+    let rate = rows.iter().filter(|row| row.is_fx()).find_map(|row| row.fx);
+    let account_currency = rows
+        .iter()
+        .find(|row| row.is_fx() && row.currency != currency)
+        .map(|row| row.currency.as_str());
+    let exchange_rate = rate.filter(|_| account_currency == Some(base_currency));
+
+    let mut fees = Decimal::ZERO;
+    for row in rows.iter().filter(|row| row.is_fee()) {
+        if row.currency == base_currency {
+            fees -= row.amount;
+        } else if row.currency == currency
+            && let Some(rate) = exchange_rate
+        {
+            fees -= row.amount / rate;
+        } else {
+            return Err(anyhow::anyhow!(
+                "Cannot convert the fee in {} to {} in record {}",
+                row.currency,
+                base_currency,
+                row.record
+            ));
+        }
+    }
+
+    Ok(StatementRecord::new(
+        external_id(BROKER, &trade.order_id),
+        trade.date,
+        transaction_type,
+        trade.isin.clone(),
+        quantity,
+        value / quantity,
+        fees,
+        BROKER.to_string(),
+        String::new(),
+        currency,
+        Decimal::ZERO,
+        Decimal::ZERO,
+        exchange_rate,
+    ))
+}
+
+fn parse_cash_rows<'a>(rows: impl Iterator<Item = &'a AccountRow>) -> Vec<Result<StatementRecord>> {
+    let rows: Vec<&AccountRow> = rows.collect();
+    let is_dividend = |row: &AccountRow| row.description.eq_ignore_ascii_case("Dividend");
+    let is_dividend_tax = |row: &AccountRow| row.description.eq_ignore_ascii_case("Dividend Tax");
+
+    let dividend_keys: HashSet<(&str, NaiveDate)> = rows
+        .iter()
+        .filter(|row| is_dividend(row))
+        .map(|row| (row.isin.as_str(), row.date))
+        .collect();
+    let mut dividend_taxes: HashMap<(&str, NaiveDate), Decimal> = HashMap::new();
+    for row in rows.iter().filter(|row| is_dividend_tax(row)) {
+        let key = (row.isin.as_str(), row.date);
+        if dividend_keys.contains(&key) {
+            *dividend_taxes.entry(key).or_default() -= row.amount;
+        }
+    }
+
+    let mut records = Vec::new();
+    for row in rows {
+        let description = row.description.to_lowercase();
+        let (transaction_type, price, withholding_tax) = if is_dividend(row) {
+            let withholding_tax = dividend_taxes
+                .remove(&(row.isin.as_str(), row.date))
+                .unwrap_or_default();
+            (TransactionType::Div, row.amount, withholding_tax)
+        } else if is_dividend_tax(row) {
+            if dividend_keys.contains(&(row.isin.as_str(), row.date)) {
+                continue;
+            }
+            (TransactionType::Div, Decimal::ZERO, -row.amount)
+        } else if row.is_fx()
+            || ["cash sweep", "money market", "reservation"]
+                .iter()
+                .any(|keyword| description.contains(keyword))
+            || description.starts_with("transfer ")
+        {
+            // Conversions of dividends and moves between own accounts
+            continue;
+        } else if description.contains("deposit") {
+            (TransactionType::Deposit, row.amount, Decimal::ZERO)
+        } else if description.contains("withdrawal") {
+            (TransactionType::Withdrawal, -row.amount, Decimal::ZERO)
+        } else if description.contains("interest") && row.amount >= Decimal::ZERO {
+            (TransactionType::Interest, row.amount, Decimal::ZERO)
+        } else if description.contains("interest") || row.is_fee() {
+            (TransactionType::Fee, -row.amount, Decimal::ZERO)
+        } else {
+            records.push(Err(anyhow::anyhow!(
+                "Unsupported description '{}' in record {}",
+                row.description,
+                row.record
+            )));
+            continue;
+        };
+
+        let is_cash = transaction_type.is_cash();
+        records.push(Ok(StatementRecord::new(
+            external_id(BROKER, &row.id),
+            row.date,
+            transaction_type,
+            if is_cash {
+                String::new()
+            } else {
+                row.isin.clone()
+            },
+            Decimal::ONE,
+            price,
+            Decimal::ZERO,
+            BROKER.to_string(),
+            String::new(),
+            row.currency.clone(),
+            withholding_tax,
+            Decimal::ZERO,
+            None,
+        )));
+    }
+
+    records
+}
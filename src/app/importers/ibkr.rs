@@ -0,0 +1,370 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use regex::Regex;
+use roxmltree::{Document, Node};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+
+use crate::{app::utils::parse_decimal, models::TransactionType};
+
+use super::{StatementRecord, external_id};
+
+const BROKER: &str = "IBKR";
+
+static SPLIT_RATIO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)SPLIT\s+(\d+(?:\.\d+)?)\s+FOR\s+(\d+(?:\.\d+)?)").expect("valid regex")
+});
+
+/// Reads an Interactive Brokers Activity Flex Query in XML. Trades, cash
+/// transactions and splits become records, with the ISIN as alternative
+/// symbol; withholding tax is folded into the dividend of the same security
+/// and day. Forex trades are skipped, as currency conversions are not tracked.
+pub fn parse_flex_query(
+    content: &str,
+    base_currency: &str,
+) -> Result<Vec<Result<StatementRecord>>> {
+    let document = Document::parse(content).with_context(|| "Failed to parse XML")?;
+    let root = document.root_element();
+    if !root.has_tag_name("FlexQueryResponse") {
+        return Err(anyhow::anyhow!(
+            "Expected a FlexQueryResponse, found {}",
+            root.tag_name().name()
+        ));
+    }
+
+    let mut records = Vec::new();
+    for statement in root
+        .descendants()
+        .filter(|node| node.has_tag_name("FlexStatement"))
+    {
+        let account_currency = statement
+            .descendants()
+            .find(|node| node.has_tag_name("AccountInformation"))
+            .and_then(|node| node.attribute("currency"));
+
+        for trade in statement
+            .descendants()
+            .filter(|node| node.has_tag_name("Trade"))
+            .filter(|node| matches!(node.attribute("levelOfDetail"), None | Some("EXECUTION")))
+            .filter(|node| node.attribute("assetCategory") != Some("CASH"))
+        {
+            records.push(parse_trade(trade, account_currency, base_currency));
+        }
+        records.extend(parse_cash_transactions(statement));
+        records.extend(parse_corporate_actions(statement));
+    }
+
+    Ok(records)
+}
+
+fn describe(node: Node) -> String {
+    format!(
+        "{} {}",
+        node.tag_name().name(),
+        node.attribute("transactionID")
+            .unwrap_or("without transactionID")
+    )
+}
+
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str> {
+    node.attribute(name)
+        .filter(|value| !value.is_empty())
+        .with_context(|| format!("Missing '{}' in {}", name, describe(node)))
+}
+
+fn decimal_attribute(node: Node, name: &str) -> Result<Decimal> {
+    parse_decimal(attribute(node, name)?, name)
+        .with_context(|| format!("Invalid '{}' in {}", name, describe(node)))
+}
+
+/// Flex dates are `yyyyMMdd` by default, optionally followed by a time.
+fn parse_flex_date(value: &str) -> Result<NaiveDate> {
+    let date = value.split([';', ',', ' ', 'T']).next().unwrap_or_default();
+    ["%Y%m%d", "%Y-%m-%d", "%m/%d/%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+        .with_context(|| format!("Failed to parse date '{}'", value))
+}
+
+fn date_attribute(node: Node, names: &[&str]) -> Result<NaiveDate> {
+    let value = names
+        .iter()
+        .find_map(|name| node.attribute(*name).filter(|value| !value.is_empty()))
+        .with_context(|| format!("Missing '{}' in {}", names.join("' or '"), describe(node)))?;
+    parse_flex_date(value).with_context(|| format!("Invalid date in {}", describe(node)))
+}
+
+fn entry_id(node: Node) -> Result<String> {
+    Ok(external_id(BROKER, attribute(node, "transactionID")?))
+}
+
+fn parse_trade(
+    trade: Node,
+    account_currency: Option<&str>,
+    base_currency: &str,
+) -> Result<StatementRecord> {
+    let category = attribute(trade, "assetCategory")?;
+    if !matches!(category, "STK" | "FUND") {
+        return Err(anyhow::anyhow!(
+            "Unsupported asset category {} in {}",
+            category,
+            describe(trade)
+        ));
+    }
+
+    let date = date_attribute(trade, &["tradeDate", "dateTime"])?;
+    let transaction_type = match attribute(trade, "buySell")? {
+        "BUY" => TransactionType::Buy,
+        "SELL" => TransactionType::Sell,
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unsupported buySell '{}' in {}",
+                other,
+                describe(trade)
+            ));
+        }
+    };
+    let currency = attribute(trade, "currency")?;
+
+    // Commissions are negative and in the commission currency
+    let commission = match trade.attribute("ibCommission") {
+        Some(value) if !value.is_empty() => -decimal_attribute(trade, "ibCommission")?,
+        _ => Decimal::ZERO,
+    };
+    let commission_currency = trade
+        .attribute("ibCommissionCurrency")
+        .filter(|value| !value.is_empty())
+        .unwrap_or(currency);
+    let fees = if commission == Decimal::ZERO || commission_currency == base_currency {
+        commission
+    } else if account_currency == Some(base_currency) && commission_currency == currency {
+        commission * decimal_attribute(trade, "fxRateToBase")?
+    } else {
+        return Err(anyhow::anyhow!(
+            "Cannot convert the commission of {} from {} to {}; include the account information with base currency {} in the Flex Query",
+            describe(trade),
+            commission_currency,
+            base_currency,
+            base_currency
+        ));
+    };
+
+    Ok(StatementRecord::new(
+        entry_id(trade)?,
+        date,
+        transaction_type,
+        attribute(trade, "symbol")?.to_string(),
+        decimal_attribute(trade, "quantity")?.abs(),
+        decimal_attribute(trade, "tradePrice")?,
+        fees,
+        BROKER.to_string(),
+        trade.attribute("isin").unwrap_or_default().to_string(),
+        currency.to_string(),
+        Decimal::ZERO,
+        Decimal::ZERO,
+        None,
+    ))
+}
+
+/// Identifies the dividend a withholding tax belongs to.
+fn dividend_key(node: Node) -> Result<(String, NaiveDate)> {
+    let security = node
+        .attribute("conid")
+        .or(node.attribute("symbol"))
+        .unwrap_or_default();
+    let date = date_attribute(node, &["dateTime", "settleDate", "reportDate"])?;
+    Ok((security.to_string(), date))
+}
+
+fn parse_cash_transactions(statement: Node) -> Vec<Result<StatementRecord>> {
+    let nodes: Vec<Node> = statement
+        .descendants()
+        .filter(|node| node.has_tag_name("CashTransaction"))
+        .filter(|node| matches!(node.attribute("levelOfDetail"), None | Some("DETAIL")))
+        .collect();
+
+    let is_dividend = |node: &Node| {
+        matches!(
+            node.attribute("type"),
+            Some("Dividends" | "Payment In Lieu Of Dividends")
+        )
+    };
+    let is_withholding_tax = |node: &Node| node.attribute("type") == Some("Withholding Tax");
+
+    let dividend_keys: HashSet<(String, NaiveDate)> = nodes
+        .iter()
+        .filter(|node| is_dividend(node))
+        .filter_map(|node| dividend_key(*node).ok())
+        .collect();
+    let mut withholding_taxes: HashMap<(String, NaiveDate), Decimal> = HashMap::new();
+    for node in nodes.iter().filter(|node| is_withholding_tax(node)) {
+        if let Ok(key) = dividend_key(*node)
+            && dividend_keys.contains(&key)
+            && let Ok(amount) = decimal_attribute(*node, "amount")
+        {
+            *withholding_taxes.entry(key).or_default() -= amount;
+        }
+    }
+
+    let mut records = Vec::new();
+    for node in nodes {
+        let record = if is_dividend(&node) {
+            let withholding_tax = dividend_key(node)
+                .ok()
+                .and_then(|key| withholding_taxes.remove(&key))
+                .unwrap_or_default();
+            parse_dividend(node, decimal_attribute(node, "amount"), withholding_tax)
+        } else if is_withholding_tax(&node) {
+            match dividend_key(node) {
+                // Folded into the dividend
+                Ok(key) if dividend_keys.contains(&key) => continue,
+                // Refunds and corrections of earlier dividends
+                _ => decimal_attribute(node, "amount")
+                    .and_then(|amount| parse_dividend(node, Ok(Decimal::ZERO), -amount)),
+            }
+        } else {
+            parse_cash_transaction(node)
+        };
+        records.push(record);
+    }
+
+    records
+}
+
+/// The gross amount is booked as a single unit, as Flex reports no share count.
+fn parse_dividend(
+    node: Node,
+    amount: Result<Decimal>,
+    withholding_tax: Decimal,
+) -> Result<StatementRecord> {
+    let date = date_attribute(node, &["dateTime", "settleDate", "reportDate"])?;
+    Ok(StatementRecord::new(
+        entry_id(node)?,
+        date,
+        TransactionType::Div,
+        attribute(node, "symbol")?.to_string(),
+        dec!(1),
+        amount?,
+        Decimal::ZERO,
+        BROKER.to_string(),
+        node.attribute("isin").unwrap_or_default().to_string(),
+        attribute(node, "currency")?.to_string(),
+        withholding_tax,
+        Decimal::ZERO,
+        None,
+    ))
+}
+
+fn parse_cash_transaction(node: Node) -> Result<StatementRecord> {
+    let date = date_attribute(node, &["dateTime", "settleDate", "reportDate"])?;
+    let amount = decimal_attribute(node, "amount")?;
+    let cash_type = attribute(node, "type")?;
+
+    // Fees are booked as positive amounts, so refunds become negative fees
+    let (transaction_type, price) = match cash_type {
+        "Deposits/Withdrawals" | "Deposits & Withdrawals" if amount >= Decimal::ZERO => {
+            (TransactionType::Deposit, amount)
+        }
+        "Deposits/Withdrawals" | "Deposits & Withdrawals" => (TransactionType::Withdrawal, -amount),
+        "Broker Interest Received" | "Bond Interest Received" if amount >= Decimal::ZERO => {
+            (TransactionType::Interest, amount)
+        }
+        "Broker Interest Received"
+        | "Bond Interest Received"
+        | "Broker Interest Paid"
+        | "Bond Interest Paid"
+        | "Other Fees"
+        | "Commission Adjustments"
+        | "Advisor Fees" => (TransactionType::Fee, -amount),
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unsupported cash transaction type '{}' in {}",
+                other,
+                describe(node)
+            ));
+        }
+    };
+
+    Ok(StatementRecord::new(
+        entry_id(node)?,
+        date,
+        transaction_type,
+        String::new(),
+        dec!(1),
+        price,
+        Decimal::ZERO,
+        BROKER.to_string(),
+        String::new(),
+        attribute(node, "currency")?.to_string(),
+        Decimal::ZERO,
+        Decimal::ZERO,
+        None,
+    ))
+}
+
+/// Forward and reverse splits, which IBKR may report in several rows of the
+/// same action.
+fn parse_corporate_actions(statement: Node) -> Vec<Result<StatementRecord>> {
+    let mut actions = HashSet::new();
+    let mut records = Vec::new();
+
+    for node in statement
+        .descendants()
+        .filter(|node| node.has_tag_name("CorporateAction"))
+        .filter(|node| matches!(node.attribute("levelOfDetail"), None | Some("DETAIL")))
+    {
+        let action = node
+            .attribute("actionID")
+            .or(node.attribute("transactionID"))
+            .unwrap_or_default();
+        if !action.is_empty() && !actions.insert(action.to_string()) {
+            continue;
+        }
+        records.push(parse_split(node));
+    }
+
+    records
+}
+
+fn parse_split(node: Node) -> Result<StatementRecord> {
+    let action_type = attribute(node, "type")?;
+    if !matches!(action_type, "FS" | "RS") {
+        return Err(anyhow::anyhow!(
+            "Unsupported corporate action type '{}' in {}",
+            action_type,
+            describe(node)
+        ));
+    }
+
+    let description = attribute(node, "description")?;
+    let captures = SPLIT_RATIO
+        .captures(description)
+        .with_context(|| format!("Missing split ratio in {}", describe(node)))?;
+    let new_units = parse_decimal(&captures[1], "split ratio")?;
+    let old_units = parse_decimal(&captures[2], "split ratio")?;
+    if old_units == Decimal::ZERO {
+        return Err(anyhow::anyhow!("Invalid split ratio in {}", describe(node)));
+    }
+
+    let date = date_attribute(node, &["dateTime", "reportDate"])?;
+    Ok(StatementRecord::new(
+        entry_id(node)?,
+        date,
+        TransactionType::Split,
+        attribute(node, "symbol")?.to_string(),
+        new_units / old_units,
+        Decimal::ZERO,
+        Decimal::ZERO,
+        BROKER.to_string(),
+        node.attribute("isin").unwrap_or_default().to_string(),
+        attribute(node, "currency")?.to_string(),
+        Decimal::ZERO,
+        Decimal::ZERO,
+        None,
+    ))
+}
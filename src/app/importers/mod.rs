@@ -0,0 +1,198 @@
+pub mod degiro;
+pub mod ibkr;
+pub mod ofx;
+pub mod profile;
+pub mod trading212;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, StringRecord};
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+use crate::{app::config::ImportProfile, models::TransactionType};
+
+/// Columns of the CSV import format. Files may carry further columns after
+/// these, which are left out of the record's content hash. Records with an
+/// `external_id`, the id of a statement entry at the broker, are matched with
+/// stored transactions by it and may leave `transaction_no` empty.
+pub const IMPORT_COLUMNS: [&str; 14] = [
+    "transaction_no",
+    "date",
+    "transaction_type",
+    "symbol",
+    "quantity",
+    "price",
+    "fees",
+    "broker",
+    "alternative_symbol",
+    "transaction_currency",
+    "withholding_tax",
+    "domestic_tax",
+    "exchange_rate",
+    "external_id",
+];
+
+/// A transaction read from a broker statement, with the columns of the CSV
+/// import format. Amounts are in `currency`, except `fees`, which are in the
+/// base currency. `exchange_rate` is the rate the broker converted at, in units
+/// of `currency` per unit of the base currency.
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct StatementRecord {
+    external_id: String,
+    date: NaiveDate,
+    transaction_type: TransactionType,
+    symbol: String,
+    quantity: Decimal,
+    price: Decimal,
+    fees: Decimal,
+    broker: String,
+    alternative_symbol: String,
+    currency: String,
+    withholding_tax: Decimal,
+    domestic_tax: Decimal,
+    exchange_rate: Option<Decimal>,
+}
+
+impl StatementRecord {
+    pub fn to_record(&self) -> StringRecord {
+        StringRecord::from(vec![
+            String::new(),
+            self.date.format("%Y-%m-%d").to_string(),
+            self.transaction_type.to_str().to_string(),
+            self.symbol.clone(),
+            self.quantity.normalize().to_string(),
+            self.price.normalize().to_string(),
+            self.fees.normalize().to_string(),
+            self.broker.clone(),
+            self.alternative_symbol.clone(),
+            self.currency.clone(),
+            self.withholding_tax.normalize().to_string(),
+            self.domestic_tax.normalize().to_string(),
+            self.exchange_rate
+                .map(|rate| rate.normalize().to_string())
+                .unwrap_or_default(),
+            self.external_id.clone(),
+        ])
+    }
+}
+
+/// Identifies a statement entry by the broker and its id there, so it is
+/// matched with the stored transaction when a statement is imported again.
+pub fn external_id(broker: &str, id: &str) -> String {
+    format!("{}:{}", broker, id)
+}
+
+/// Ids for rows without one, from the fields identifying the entry, like its
+/// date, type and security, and how often these occurred before. Identical
+/// rows stay apart, and rows keep their id when an amount is corrected.
+#[derive(Default)]
+struct RowIds {
+    occurrences: HashMap<String, usize>,
+}
+
+impl RowIds {
+    fn next(&mut self, key: &[&str]) -> String {
+        let key = key.join("|");
+        let occurrence = self.occurrences.entry(key.clone()).or_default();
+        *occurrence += 1;
+        format!("{}#{}", key, occurrence)
+    }
+}
+
+fn csv_reader(content: &str, delimiter: u8) -> csv::Reader<&[u8]> {
+    ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(content.trim_start_matches('\u{feff}').as_bytes())
+}
+
+/// Column indexes by lowercase header name; unnamed columns are left out.
+fn header_index(header: &StringRecord) -> HashMap<String, usize> {
+    let mut index = HashMap::new();
+    for (i, name) in header.iter().enumerate() {
+        let name = name.trim().to_lowercase();
+        if !name.is_empty() {
+            index.entry(name).or_insert(i);
+        }
+    }
+    index
+}
+
+/// Reads the records of a file to import, converting broker statements and
+/// exports matching one of `profiles` to the CSV import format. Records that
+/// cannot be converted are returned as errors.
+pub fn read_import_file(
+    path: &str,
+    base_currency: &str,
+    profiles: &BTreeMap<String, ImportProfile>,
+) -> Result<Vec<Result<StringRecord>>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let extension = Path::new(path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if matches!(extension.as_str(), "ofx" | "qfx") || ofx::is_ofx(&content) {
+        let statements = ofx::parse_ofx(&content, base_currency)
+            .with_context(|| format!("Failed to parse OFX statement from {}", path))?;
+        return Ok(to_records(statements));
+    }
+    if extension == "xml" {
+        let statements = ibkr::parse_flex_query(&content, base_currency)
+            .with_context(|| format!("Failed to parse IBKR Flex Query from {}", path))?;
+        return Ok(to_records(statements));
+    }
+
+    let statements = if degiro::is_account_statement(&content) {
+        Some(degiro::parse_account_statement(&content, base_currency))
+    } else if trading212::is_history(&content) {
+        Some(trading212::parse_history(&content, base_currency))
+    } else {
+        profile::detect_profile(&content, profiles)
+            .map(|profile| profile::parse_export(&content, profile))
+    };
+    if let Some(statements) = statements {
+        let statements =
+            statements.with_context(|| format!("Failed to parse CSV file at path: {}", path))?;
+        return Ok(to_records(statements));
+    }
+
+    read_csv_file(&content, path)
+}
+
+fn to_records(statements: Vec<Result<StatementRecord>>) -> Vec<Result<StringRecord>> {
+    statements
+        .into_iter()
+        .map(|statement| statement.map(|statement| statement.to_record()))
+        .collect()
+}
+
+/// Reads a CSV file in the import format, with at least the ten columns from
+/// `transaction_no` to `transaction_currency`.
+fn read_csv_file(content: &str, path: &str) -> Result<Vec<Result<StringRecord>>> {
+    let mut reader = csv_reader(content, b',');
+
+    let headers = reader
+        .headers()
+        .with_context(|| format!("Failed to read CSV headers from file: {}", path))?;
+
+    if headers.len() < 10 {
+        return Err(anyhow::anyhow!(
+            "Invalid CSV format: expected at least 10 columns, found {}",
+            headers.len()
+        ));
+    }
+
+    Ok(reader
+        .records()
+        .enumerate()
+        .map(|(i, record)| record.with_context(|| format!("Failed to read CSV record {}", i + 1)))
+        .collect())
+}
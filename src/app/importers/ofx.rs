@@ -0,0 +1,505 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+use crate::{app::utils::parse_decimal, models::TransactionType};
+
+use super::{StatementRecord, external_id};
+
+/// An element of an OFX document. Leaf elements hold a value, aggregates
+/// their children.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    value: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_uppercase(),
+            ..Self::default()
+        }
+    }
+
+    /// The first element at the `/`-separated `path` below this one.
+    fn find(&self, path: &str) -> Option<&Element> {
+        path.split('/').try_fold(self, |element, name| {
+            element.children.iter().find(|child| child.name == name)
+        })
+    }
+
+    fn text(&self, path: &str) -> Option<&str> {
+        self.find(path)
+            .map(|element| element.value.as_str())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn required(&self, path: &str) -> Result<&str> {
+        self.text(path)
+            .with_context(|| format!("Missing {} in {}", path, self.name))
+    }
+
+    fn decimal(&self, path: &str) -> Result<Decimal> {
+        parse_decimal(self.required(path)?, path)
+            .with_context(|| format!("Invalid {} in {}", path, self.name))
+    }
+
+    fn optional_decimal(&self, path: &str) -> Result<Decimal> {
+        match self.text(path) {
+            Some(_) => self.decimal(path),
+            None => Ok(Decimal::ZERO),
+        }
+    }
+
+    /// All elements named `name` below this one, in document order.
+    fn descendants<'a>(&'a self, name: &str, found: &mut Vec<&'a Element>) {
+        for child in self.children.iter() {
+            if child.name == name {
+                found.push(child);
+            }
+            child.descendants(name, found);
+        }
+    }
+}
+
+/// Whether `content` is an OFX or QFX document, in SGML (OFX 1) or XML
+/// (OFX 2).
+pub fn is_ofx(content: &str) -> bool {
+    let start = content.trim_start_matches('\u{feff}').trim_start();
+    start.starts_with("OFXHEADER") || content.contains("<OFX>")
+}
+
+/// Parses OFX 1 SGML, where leaf elements are not closed, as well as OFX 2 XML.
+fn parse_document(content: &str) -> Result<Element> {
+    let start = content
+        .find("<OFX>")
+        .with_context(|| "Missing <OFX> element")?;
+    let mut stack = vec![Element::new("")];
+    let mut rest = &content[start..];
+
+    while let Some(open) = rest.find('<') {
+        let text = rest[..open].trim();
+        if !text.is_empty()
+            && let Some(leaf) = stack.pop()
+        {
+            let parent = stack.last_mut().with_context(|| "Unexpected text")?;
+            parent.children.push(Element {
+                value: decode_entities(text),
+                ..leaf
+            });
+        }
+
+        let close = rest[open..].find('>').with_context(|| "Unterminated tag")? + open;
+        let tag = rest[open + 1..close].trim();
+        rest = &rest[close + 1..];
+
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        } else if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_uppercase();
+            // Leaf elements were closed by their value already
+            if stack.iter().skip(1).any(|element| element.name == name) {
+                while let Some(element) = stack.pop() {
+                    let done = element.name == name;
+                    stack
+                        .last_mut()
+                        .with_context(|| format!("Unexpected </{}>", name))?
+                        .children
+                        .push(element);
+                    if done {
+                        break;
+                    }
+                }
+            }
+        } else if let Some(name) = tag.strip_suffix('/') {
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(Element::new(name.trim()));
+            }
+        } else {
+            stack.push(Element::new(tag));
+        }
+    }
+
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap_or_default();
+        if let Some(parent) = stack.last_mut() {
+            parent.children.push(element);
+        }
+    }
+    stack
+        .pop()
+        .and_then(|root| root.children.into_iter().find(|child| child.name == "OFX"))
+        .with_context(|| "Missing <OFX> element")
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// OFX dates start with `YYYYMMDD`, optionally followed by a time and zone.
+fn parse_ofx_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..8).unwrap_or(value), "%Y%m%d")
+        .with_context(|| format!("Failed to parse date '{}'", value))
+}
+
+/// The ISIN of a US security from its CUSIP, with the check digit computed
+/// as for any ISIN.
+pub fn isin_from_cusip(cusip: &str) -> Option<String> {
+    if cusip.len() != 9 || !cusip.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let body = format!("US{}", cusip.to_uppercase());
+    let digits: String = body
+        .chars()
+        .map(|c| c.to_digit(36).unwrap_or_default().to_string())
+        .collect();
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, digit)| {
+            if i % 2 == 0 {
+                let doubled = digit * 2;
+                doubled / 10 + doubled % 10
+            } else {
+                digit
+            }
+        })
+        .sum();
+    Some(format!("{}{}", body, (10 - sum % 10) % 10))
+}
+
+/// The ISIN of a `SECID` aggregate, derived from the CUSIP for US securities.
+fn isin(secid: &Element) -> String {
+    let id = secid.text("UNIQUEID").unwrap_or_default();
+    match secid.text("UNIQUEIDTYPE") {
+        Some("ISIN") => Some(id.to_uppercase()),
+        Some("CUSIP") => isin_from_cusip(id),
+        _ => None,
+    }
+    .unwrap_or_default()
+}
+
+/// A security of the statement's security list, by its unique id.
+struct Security {
+    symbol: String,
+    isin: String,
+}
+
+impl Security {
+    /// Without a ticker, the security is named by its ISIN rather than its
+    /// CUSIP, which providers would resolve to another ticker or not at all.
+    fn new(ticker: Option<&str>, secid: Option<&Element>) -> Self {
+        let isin = secid.map(isin).unwrap_or_default();
+        let symbol = match ticker {
+            Some(ticker) => ticker.to_uppercase(),
+            None if !isin.is_empty() => isin.clone(),
+            None => secid
+                .and_then(|secid| secid.text("UNIQUEID"))
+                .unwrap_or_default()
+                .to_uppercase(),
+        };
+        Security { symbol, isin }
+    }
+}
+
+/// Reads the investment statements (`INVSTMTRS`) of an OFX or QFX download.
+/// Securities are named by the ticker of the security list, or else by their
+/// ISIN or id, with the ISIN as alternative symbol. Amounts
+/// are in the statement currency unless a transaction gives its own.
+/// Transfers in carry their cost basis, which opens their lot when no units
+/// are in transit.
+pub fn parse_ofx(content: &str, base_currency: &str) -> Result<Vec<Result<StatementRecord>>> {
+    let document = parse_document(content)?;
+
+    let mut securities = HashMap::new();
+    let mut security_infos = Vec::new();
+    document.descendants("SECINFO", &mut security_infos);
+    for info in security_infos {
+        let Some(id) = info.text("SECID/UNIQUEID") else {
+            continue;
+        };
+        securities.insert(
+            id.to_string(),
+            Security::new(info.text("TICKER"), info.find("SECID")),
+        );
+    }
+
+    let institution = document.text("SIGNONMSGSRSV1/SONRS/FI/ORG");
+
+    let mut statements = Vec::new();
+    document.descendants("INVSTMTRS", &mut statements);
+    if statements.is_empty() {
+        return Err(anyhow::anyhow!("Missing investment statement (INVSTMTRS)"));
+    }
+
+    let mut records = Vec::new();
+    for statement in statements {
+        let context = StatementContext {
+            currency: statement.required("CURDEF")?.to_uppercase(),
+            base_currency,
+            broker: institution
+                .or(statement.text("INVACCTFROM/BROKERID"))
+                .unwrap_or("OFX")
+                .to_string(),
+            securities: &securities,
+        };
+        let Some(transactions) = statement.find("INVTRANLIST") else {
+            continue;
+        };
+        for transaction in transactions.children.iter() {
+            if matches!(transaction.name.as_str(), "DTSTART" | "DTEND") {
+                continue;
+            }
+            let describe = || {
+                format!(
+                    "Failed to parse {} {}",
+                    transaction.name,
+                    transaction
+                        .text("INVTRAN/FITID")
+                        .or(transaction.text("STMTTRN/FITID"))
+                        .unwrap_or_default()
+                )
+            };
+            match context.parse(transaction) {
+                Ok(parsed) => records.extend(parsed.into_iter().map(Ok)),
+                Err(e) => records.push(Err(e).with_context(describe)),
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+struct StatementContext<'a> {
+    currency: String,
+    base_currency: &'a str,
+    broker: String,
+    securities: &'a HashMap<String, Security>,
+}
+
+impl StatementContext<'_> {
+    fn parse(&self, transaction: &Element) -> Result<Vec<StatementRecord>> {
+        match transaction.name.as_str() {
+            "BUYSTOCK" | "BUYMF" => Ok(vec![
+                self.trade(
+                    transaction
+                        .find("INVBUY")
+                        .with_context(|| "Missing INVBUY")?,
+                    TransactionType::Buy,
+                    "",
+                )?,
+            ]),
+            "SELLSTOCK" | "SELLMF" => Ok(vec![
+                self.trade(
+                    transaction
+                        .find("INVSELL")
+                        .with_context(|| "Missing INVSELL")?,
+                    TransactionType::Sell,
+                    "",
+                )?,
+            ]),
+            "INCOME" => Ok(vec![self.income(transaction)?]),
+            "REINVEST" => {
+                let mut income = self.income(transaction)?;
+                income.price = income.price.abs();
+                Ok(vec![
+                    income,
+                    self.trade(transaction, TransactionType::Buy, "#reinvest")?,
+                ])
+            }
+            "SPLIT" => {
+                let denominator = transaction.decimal("DENOMINATOR")?;
+                if denominator == Decimal::ZERO {
+                    return Err(anyhow::anyhow!("Invalid split ratio"));
+                }
+                let ratio = transaction.decimal("NUMERATOR")? / denominator;
+                Ok(vec![self.record(
+                    transaction,
+                    "",
+                    TransactionType::Split,
+                    ratio,
+                    Decimal::ZERO,
+                )?])
+            }
+            "TRANSFER" => {
+                let units = transaction.decimal("UNITS")?.abs();
+                let (transaction_type, price) = match transaction.required("TFERACTION")? {
+                    "IN" => {
+                        let price = match ["AVGCOSTBASIS", "UNITPRICE"]
+                            .into_iter()
+                            .find(|path| transaction.text(path).is_some())
+                        {
+                            Some(path) => transaction.decimal(path)?,
+                            None => Decimal::ZERO,
+                        };
+                        (TransactionType::TransferIn, price)
+                    }
+                    "OUT" => (TransactionType::TransferOut, Decimal::ZERO),
+                    other => return Err(anyhow::anyhow!("Unknown TFERACTION {}", other)),
+                };
+                Ok(vec![self.record(
+                    transaction,
+                    "",
+                    transaction_type,
+                    units,
+                    price,
+                )?])
+            }
+            "INVBANKTRAN" => Ok(vec![
+                self.bank_transaction(
+                    transaction
+                        .find("STMTTRN")
+                        .with_context(|| "Missing STMTTRN")?,
+                )?,
+            ]),
+            other => Err(anyhow::anyhow!("Unsupported transaction {}", other)),
+        }
+    }
+
+    /// The currency of a transaction's amounts and how many units of the
+    /// statement currency one unit of it is worth.
+    fn currency(&self, element: &Element) -> Result<(String, Decimal)> {
+        match element.find("CURRENCY") {
+            Some(currency) => Ok((
+                currency.required("CURSYM")?.to_uppercase(),
+                currency.decimal("CURRATE")?,
+            )),
+            // Converted to the statement currency already
+            None => Ok((self.currency.clone(), Decimal::ONE)),
+        }
+    }
+
+    /// A record of the security and date of an `INVTRAN` aggregate, identified
+    /// by its `FITID` followed by `suffix`.
+    fn record(
+        &self,
+        element: &Element,
+        suffix: &str,
+        transaction_type: TransactionType,
+        quantity: Decimal,
+        price: Decimal,
+    ) -> Result<StatementRecord> {
+        let date = parse_ofx_date(element.required("INVTRAN/DTTRADE")?)?;
+        let fitid = element.required("INVTRAN/FITID")?;
+        let id = element.required("SECID/UNIQUEID")?;
+        let Security { symbol, isin } = match self.securities.get(id) {
+            Some(security) => Security {
+                symbol: security.symbol.clone(),
+                isin: security.isin.clone(),
+            },
+            None => Security::new(None, element.find("SECID")),
+        };
+        let (currency, _) = self.currency(element)?;
+
+        Ok(StatementRecord::new(
+            external_id(&self.broker, &format!("{}{}", fitid, suffix)),
+            date,
+            transaction_type,
+            symbol,
+            quantity,
+            price,
+            Decimal::ZERO,
+            self.broker.clone(),
+            isin,
+            currency,
+            Decimal::ZERO,
+            Decimal::ZERO,
+            None,
+        ))
+    }
+
+    fn trade(
+        &self,
+        element: &Element,
+        transaction_type: TransactionType,
+        suffix: &str,
+    ) -> Result<StatementRecord> {
+        let mut record = self.record(
+            element,
+            suffix,
+            transaction_type,
+            element.decimal("UNITS")?.abs(),
+            element.decimal("UNITPRICE")?,
+        )?;
+
+        let (currency, rate) = self.currency(element)?;
+        let fees = ["COMMISSION", "FEES", "TAXES", "LOAD"]
+            .iter()
+            .map(|name| element.optional_decimal(name))
+            .sum::<Result<Decimal>>()?
+            * rate;
+        if fees != Decimal::ZERO && self.currency != self.base_currency {
+            return Err(anyhow::anyhow!(
+                "Cannot convert the fees from {} to {}",
+                self.currency,
+                self.base_currency
+            ));
+        }
+        record.fees = fees;
+        if currency != self.currency && self.currency == self.base_currency {
+            record.exchange_rate = Some(Decimal::ONE / rate);
+        }
+
+        Ok(record)
+    }
+
+    fn income(&self, element: &Element) -> Result<StatementRecord> {
+        let transaction_type = match element.required("INCOMETYPE")? {
+            "DIV" | "CGLONG" | "CGSHORT" => TransactionType::Div,
+            "INTEREST" => TransactionType::Interest,
+            other => return Err(anyhow::anyhow!("Unsupported INCOMETYPE {}", other)),
+        };
+        let mut record = self.record(
+            element,
+            "",
+            transaction_type.clone(),
+            Decimal::ONE,
+            element.decimal("TOTAL")?,
+        )?;
+        record.withholding_tax = element.optional_decimal("WITHHOLDING")?;
+        if transaction_type.is_cash() {
+            record.symbol = String::new();
+            record.alternative_symbol = String::new();
+        }
+
+        Ok(record)
+    }
+
+    fn bank_transaction(&self, element: &Element) -> Result<StatementRecord> {
+        let date = parse_ofx_date(element.required("DTPOSTED")?)?;
+        let amount = element.decimal("TRNAMT")?;
+        let transaction_type = match element.required("TRNTYPE")? {
+            "INT" | "DIV" => TransactionType::Interest,
+            "FEE" | "SRVCHG" => TransactionType::Fee,
+            _ if amount >= Decimal::ZERO => TransactionType::Deposit,
+            _ => TransactionType::Withdrawal,
+        };
+        let (currency, _) = self.currency(element)?;
+
+        Ok(StatementRecord::new(
+            external_id(&self.broker, element.required("FITID")?),
+            date,
+            transaction_type,
+            String::new(),
+            Decimal::ONE,
+            amount.abs(),
+            Decimal::ZERO,
+            self.broker.clone(),
+            String::new(),
+            currency,
+            Decimal::ZERO,
+            Decimal::ZERO,
+            None,
+        ))
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+use crate::{
+    app::{
+        config::ImportProfile,
+        utils::{parse_decimal, parse_split_ratio},
+    },
+    models::TransactionType,
+};
+
+use super::{RowIds, StatementRecord, csv_reader, external_id, header_index};
+
+/// Returns the first profile, in the alphabetical order of their names, whose
+/// columns all appear in the header of the CSV `content`.
+pub fn detect_profile<'a>(
+    content: &str,
+    profiles: &'a BTreeMap<String, ImportProfile>,
+) -> Option<&'a ImportProfile> {
+    profiles.values().find(|profile| {
+        read_header(content, profile).is_some_and(|header| {
+            profile
+                .column_names()
+                .iter()
+                .all(|name| header.contains_key(&name.trim().to_lowercase()))
+        })
+    })
+}
+
+fn reader<'a>(content: &'a str, profile: &ImportProfile) -> csv::Reader<&'a [u8]> {
+    csv_reader(content, *profile.delimiter() as u8)
+}
+
+fn read_header(content: &str, profile: &ImportProfile) -> Option<HashMap<String, usize>> {
+    reader(content, profile).headers().ok().map(header_index)
+}
+
+/// Reads a broker's CSV export with `profile`. Signs are dropped, as the type
+/// gives the direction. The transaction number of the export identifies its
+/// rows, or their date, type, symbol and occurrence for exports without one,
+/// so the rows are matched with the stored transactions.
+pub fn parse_export(
+    content: &str,
+    profile: &ImportProfile,
+) -> Result<Vec<Result<StatementRecord>>> {
+    let header = read_header(content, profile).with_context(|| "Failed to read CSV headers")?;
+    let mut row_ids = RowIds::default();
+
+    Ok(reader(content, profile)
+        .records()
+        .enumerate()
+        .map(|(i, record)| {
+            let record = record.with_context(|| format!("Failed to read CSV record {}", i + 1))?;
+            let columns = profile.columns();
+            let row_id = row_ids.next(&[
+                column_value(&record, &header, Some(columns.date())),
+                column_value(&record, &header, Some(columns.transaction_type())),
+                column_value(&record, &header, columns.symbol().as_ref()),
+            ]);
+            parse_row(&record, &header, profile, row_id)
+                .with_context(|| format!("Failed to parse CSV record {}", i + 1))
+        })
+        .collect())
+}
+
+/// The value of the mapped `column`, empty when unmapped or missing.
+fn column_value<'a>(
+    record: &'a StringRecord,
+    header: &HashMap<String, usize>,
+    column: Option<&String>,
+) -> &'a str {
+    column
+        .and_then(|name| header.get(&name.trim().to_lowercase()))
+        .and_then(|i| record.get(*i))
+        .unwrap_or_default()
+        .trim()
+}
+
+fn parse_row(
+    record: &StringRecord,
+    header: &HashMap<String, usize>,
+    profile: &ImportProfile,
+    row_id: String,
+) -> Result<StatementRecord> {
+    let field = |column: Option<&String>| column_value(record, header, column);
+    let number = |column: Option<&String>, field_name: &str| -> Result<Decimal> {
+        parse_number(field(column), *profile.decimal_separator(), field_name)
+    };
+    let columns = profile.columns();
+
+    let date = parse_date(field(Some(columns.date())), profile.date_format())?;
+    let transaction_type = profile.transaction_type(field(Some(columns.transaction_type())))?;
+
+    let quantity_field = field(columns.quantity().as_ref());
+    // Cash transactions and dividends give the amount as price
+    let quantity = if quantity_field.is_empty()
+        && (transaction_type.is_cash() || transaction_type == TransactionType::Div)
+    {
+        Decimal::ONE
+    } else if transaction_type == TransactionType::Split {
+        parse_split_ratio(quantity_field)?
+    } else {
+        number(columns.quantity().as_ref(), "quantity")?
+    };
+    let price = if field(Some(columns.price())).is_empty() && !transaction_type.has_cash_flow() {
+        Decimal::ZERO
+    } else {
+        number(Some(columns.price()), "price")?
+    };
+
+    let id = match field(columns.transaction_no().as_ref()) {
+        "" => row_id,
+        id => id.to_string(),
+    };
+
+    let broker = match field(columns.broker().as_ref()) {
+        "" => profile.broker().clone(),
+        broker => broker.to_string(),
+    };
+
+    Ok(StatementRecord::new(
+        external_id(&broker, &id),
+        date,
+        transaction_type,
+        field(columns.symbol().as_ref()).to_uppercase(),
+        quantity,
+        price,
+        optional_number(field(columns.fees().as_ref()), profile, "fees")?,
+        broker,
+        field(columns.alternative_symbol().as_ref()).to_string(),
+        field(columns.transaction_currency().as_ref()).to_uppercase(),
+        optional_number(
+            field(columns.withholding_tax().as_ref()),
+            profile,
+            "withholding_tax",
+        )?,
+        optional_number(
+            field(columns.domestic_tax().as_ref()),
+            profile,
+            "domestic_tax",
+        )?,
+        None,
+    ))
+}
+
+fn optional_number(value: &str, profile: &ImportProfile, field_name: &str) -> Result<Decimal> {
+    if value.is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+    parse_number(value, *profile.decimal_separator(), field_name)
+}
+
+/// Parses an absolute amount such as `-1.234,56` with a comma as decimal
+/// separator, dropping the grouping separators.
+fn parse_number(value: &str, decimal_separator: char, field_name: &str) -> Result<Decimal> {
+    let grouping_separator = if decimal_separator == ',' { '.' } else { ',' };
+    let normalized: String = value
+        .chars()
+        .filter(|c| *c != grouping_separator && *c != '\'' && !c.is_whitespace())
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    Ok(parse_decimal(normalized.trim_start_matches('+'), field_name)?.abs())
+}
+
+/// Dates may carry a time, e.g. `%d.%m.%Y %H:%M`.
+fn parse_date(value: &str, date_format: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, date_format)
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(value, date_format).map(|datetime| datetime.date())
+        })
+        .with_context(|| format!("Failed to parse date '{}' as '{}'", value, date_format))
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use csv::StringRecord;
+use rust_decimal::Decimal;
+
+use crate::{app::utils::parse_decimal, models::TransactionType};
+
+use super::{RowIds, StatementRecord, csv_reader, external_id, header_index};
+
+const BROKER: &str = "Trading 212";
+
+const COLUMNS: [&str; 4] = ["action", "time", "no. of shares", "price / share"];
+
+/// Charges that Trading 212 books with a trade, each with its own currency.
+const FEE_COLUMNS: [&str; 7] = [
+    "currency conversion fee",
+    "transaction fee",
+    "finra fee",
+    "stamp duty reserve tax",
+    "stamp duty",
+    "french transaction tax",
+    "ptm levy",
+];
+
+/// Whether `content` is a Trading 212 history export.
+pub fn is_history(content: &str) -> bool {
+    csv_reader(content, b',').headers().is_ok_and(|header| {
+        let index = header_index(header);
+        COLUMNS.iter().all(|column| index.contains_key(*column))
+    })
+}
+
+/// Reads the history export of Trading 212. Trades carry their fees and the
+/// rate they were converted at in the same row; conversions between the
+/// account's currencies are separate rows, which are skipped.
+pub fn parse_history(content: &str, base_currency: &str) -> Result<Vec<Result<StatementRecord>>> {
+    let mut reader = csv_reader(content, b',');
+    let index = header_index(
+        reader
+            .headers()
+            .with_context(|| "Failed to read CSV headers")?,
+    );
+    let mut row_ids = RowIds::default();
+
+    let mut records = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record
+            .with_context(|| format!("Failed to read CSV record {}", i + 1))
+            .and_then(|record| {
+                let row = HistoryRow {
+                    record: &record,
+                    index: &index,
+                };
+                let row_id = row_ids.next(&[
+                    row.field("time"),
+                    row.field("action"),
+                    row.field("ticker"),
+                    row.field("isin"),
+                ]);
+                row.parse(row_id, base_currency)
+                    .with_context(|| format!("Failed to parse CSV record {}", i + 1))
+            });
+        match record {
+            Ok(Some(record)) => records.push(Ok(record)),
+            Ok(None) => {}
+            Err(e) => records.push(Err(e)),
+        }
+    }
+
+    Ok(records)
+}
+
+struct HistoryRow<'a> {
+    record: &'a StringRecord,
+    index: &'a HashMap<String, usize>,
+}
+
+impl HistoryRow<'_> {
+    fn field(&self, name: &str) -> &str {
+        self.index
+            .get(name)
+            .and_then(|i| self.record.get(*i))
+            .unwrap_or_default()
+            .trim()
+    }
+
+    fn decimal(&self, name: &str) -> Result<Decimal> {
+        parse_decimal(self.field(name), name)
+    }
+
+    /// An amount with its currency, given either in a `Currency (name)` column
+    /// or, in older exports, in the header as `name (EUR)`.
+    fn amount(&self, name: &str) -> Result<Option<(Decimal, String)>> {
+        let (value, currency) = if self.index.contains_key(name) {
+            (
+                self.field(name),
+                self.field(&format!("currency ({})", name)).to_uppercase(),
+            )
+        } else {
+            let prefix = format!("{} (", name);
+            let Some((header, i)) = self
+                .index
+                .iter()
+                .find(|(header, _)| header.starts_with(&prefix))
+            else {
+                return Ok(None);
+            };
+            (
+                self.record.get(*i).unwrap_or_default().trim(),
+                header[prefix.len()..].trim_end_matches(')').to_uppercase(),
+            )
+        };
+        if value.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((parse_decimal(value, name)?, currency)))
+    }
+
+    fn parse(&self, row_id: String, base_currency: &str) -> Result<Option<StatementRecord>> {
+        let time = self.field("time");
+        let date = NaiveDate::parse_from_str(time.get(..10).unwrap_or(time), "%Y-%m-%d")
+            .with_context(|| format!("Failed to parse time '{}'", time))?;
+        let entry_id = match self.field("id") {
+            "" => external_id(BROKER, &row_id),
+            id => external_id(BROKER, id),
+        };
+        let action = self.field("action").to_lowercase();
+
+        let (transaction_type, quantity, price, currency) = if action.contains("buy")
+            || action.contains("sell")
+            || action.starts_with("dividend")
+        {
+            let transaction_type = if action.contains("buy") {
+                TransactionType::Buy
+            } else if action.contains("sell") {
+                TransactionType::Sell
+            } else {
+                TransactionType::Div
+            };
+            (
+                transaction_type,
+                self.decimal("no. of shares")?.abs(),
+                self.decimal("price / share")?,
+                self.field("currency (price / share)").to_uppercase(),
+            )
+        } else {
+            let transaction_type = match action.as_str() {
+                "deposit" => TransactionType::Deposit,
+                "withdrawal" => TransactionType::Withdrawal,
+                "interest on cash" | "lending interest" => TransactionType::Interest,
+                "currency conversion" => return Ok(None),
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "Unsupported action '{}'",
+                        self.field("action")
+                    ));
+                }
+            };
+            let (total, currency) = self.amount("total")?.context("Missing total")?;
+            return Ok(Some(StatementRecord::new(
+                entry_id,
+                date,
+                transaction_type,
+                String::new(),
+                Decimal::ONE,
+                total.abs(),
+                Decimal::ZERO,
+                BROKER.to_string(),
+                String::new(),
+                currency,
+                Decimal::ZERO,
+                Decimal::ZERO,
+                None,
+            )));
+        };
+
+        // In units of the price currency per unit of the account currency
+        let total_currency = self.amount("total")?.map(|(_, currency)| currency);
+        let exchange_rate = match self.field("exchange rate").parse::<Decimal>() {
+            Ok(rate) if rate > Decimal::ZERO && currency != base_currency => Some(rate),
+            _ => None,
+        }
+        .filter(|_| total_currency.as_deref() == Some(base_currency));
+
+        let mut fees = Decimal::ZERO;
+        for column in FEE_COLUMNS {
+            let Some((fee, fee_currency)) = self.amount(column)? else {
+                continue;
+            };
+            if fee_currency == base_currency {
+                fees += fee.abs();
+            } else if fee_currency == currency
+                && let Some(rate) = exchange_rate
+            {
+                fees += fee.abs() / rate;
+            } else {
+                return Err(anyhow::anyhow!(
+                    "Cannot convert the {} in {} to {}",
+                    column,
+                    fee_currency,
+                    base_currency
+                ));
+            }
+        }
+
+        let withholding_tax = match self.amount("withholding tax")? {
+            Some((tax, tax_currency)) if tax_currency == currency => tax.abs(),
+            Some((tax, tax_currency)) if total_currency.as_ref() == Some(&tax_currency) => {
+                let rate = self
+                    .decimal("exchange rate")
+                    .context("Missing exchange rate for the withholding tax")?;
+                tax.abs() * rate
+            }
+            Some((_, tax_currency)) => {
+                return Err(anyhow::anyhow!(
+                    "Cannot convert the withholding tax in {} to {}",
+                    tax_currency,
+                    currency
+                ));
+            }
+            None => Decimal::ZERO,
+        };
+
+        Ok(Some(StatementRecord::new(
+            entry_id,
+            date,
+            transaction_type,
+            self.field("ticker").to_uppercase(),
+            quantity,
+            price,
+            fees,
+            BROKER.to_string(),
+            self.field("isin").to_string(),
+            currency,
+            withholding_tax,
+            Decimal::ZERO,
+            exchange_rate,
+        )))
+    }
+}
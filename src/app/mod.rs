@@ -1,8 +1,16 @@
+#[allow(clippy::module_inception)]
 pub mod app;
 pub mod calc;
+pub mod config;
+pub mod ecb;
+pub mod export;
+pub mod form;
+pub mod importers;
 pub mod portfolio;
+pub mod sample;
 pub mod ui;
 pub mod utils;
 
 pub use app::App;
+pub use config::{Cli, Config, CostBasisConfig, DividendConfig, ImportColumns, ImportProfile};
 pub use portfolio::Portfolio;
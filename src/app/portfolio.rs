@@ -1,28 +1,53 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use anyhow::{Context, Result};
-use chrono::Local;
-use csv::Reader;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone};
+use csv::StringRecord;
 use derive_getters::Getters;
 use reqwest::Client;
-use rust_decimal::{Decimal, prelude::ToPrimitive};
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use sqlx::{Pool, QueryBuilder, Sqlite};
+use sqlx::{Pool, QueryBuilder, Row, Sqlite, SqliteConnection};
 
 use crate::{
-    app::utils::get_latest_price,
+    api::{
+        local::LocalProvider,
+        provider::{ForexProvider, ProviderRegistry, QuoteProvider},
+    },
     db::utils::{
-        insert_ticker, insert_transaction, parse_datetime_from_row, parse_decimal_from_row,
-        parse_i64_from_row, parse_string_from_row, parse_transaction, truncate_tables,
+        decimal_to_db, get_fx_rate_series, get_price_range, get_prices, get_setting,
+        insert_fx_rate, insert_price, insert_ticker, insert_transaction, parse_datetime_from_row,
+        parse_decimal_from_row, parse_i64_from_row, parse_string_from_row, parse_ticker,
+        parse_transaction, set_setting, truncate_tables, update_transaction,
+        update_transaction_state,
     },
     models::{
-        Asset, AssetType, Position, Ticker, Transaction, TransactionType, ticker::ApiProvider,
+        Asset, AssetType, DividendReport, ImportPreview, ImportRow, ImportStatus, LedgerEntry,
+        LedgerFilter, LedgerPage, LedgerSort, LotDetail, Performance, PerformancePoint,
+        PerformanceReport, Position, PositionChange, PositionDetail, PositionHistory,
+        PositionState, ReturnPeriod, Ticker, Transaction, TransactionInput, TransactionSource,
+        TransactionType, ValueHistory, ValuePoint, ticker::ApiProvider,
     },
 };
 
 use super::{
-    calc::{calculate_position_state, calculate_transaction_gains},
-    utils::{find_ticker, get_exchange_rate, parse_datetime, parse_decimal},
+    calc::{
+        TickerLots, calculate_cash_balances, calculate_cost_by_date, calculate_dividend_breakdown,
+        calculate_lots, calculate_transaction_gains, calculate_twr, calculate_units_by_date,
+        calculate_xirr, country_from_isin, value_on_or_before,
+    },
+    config::{Config, CostBasisConfig, DividendConfig, ImportProfile},
+    ecb::parse_ecb_file,
+    export::{ExportFormat, positions_table, realized_gains_table, transactions_table},
+    importers::{IMPORT_COLUMNS, read_import_file},
+    utils::{
+        get_exchange_rate, hash_record, parse_datetime, parse_decimal, parse_optional_decimal,
+        parse_split_ratio, uncovered_ranges,
+    },
 };
 
 #[derive(Clone, Debug, Getters)]
@@ -32,25 +57,373 @@ pub struct Portfolio {
     positions: Vec<Position>,
     client: Client,
     default_api: ApiProvider,
+    providers: ProviderRegistry,
     api_key_alpha_vantage: Option<String>,
     api_key_fmp: Option<String>,
     api_key_marketstack: Option<String>,
     forex_map: HashMap<String, Decimal>,
+    forex_map_dates: HashMap<String, NaiveDate>,
+    cost_basis: CostBasisConfig,
+    dividends: DividendConfig,
+    import_profiles: BTreeMap<String, ImportProfile>,
+}
+
+const COST_BASIS_SETTING: &str = "cost_basis";
+
+/// Transactions by ticker id, with the asset id, symbol, API and asset name.
+type Holdings = BTreeMap<i64, (i64, String, String, String, Vec<Transaction>)>;
+
+/// A stored transaction with the hash of the record it was imported from, its
+/// source and, for statement entries, their id at the broker.
+struct StoredTransaction {
+    transaction: Transaction,
+    content_hash: Option<String>,
+    source: TransactionSource,
+    external_id: Option<String>,
 }
 
 impl Portfolio {
-    pub fn new(base_currency: String, connection: Pool<Sqlite>) -> Self {
-        Self {
-            base_currency,
+    /// A portfolio with the default settings and API keys from the environment.
+    pub fn new(base_currency: String, connection: Pool<Sqlite>) -> Result<Self> {
+        let mut portfolio = Self::from_config(&Config::from_env(), connection)?;
+        portfolio.base_currency = base_currency.to_uppercase();
+        Ok(portfolio)
+    }
+
+    pub fn from_config(config: &Config, connection: Pool<Sqlite>) -> Result<Self> {
+        Self::from_config_with_providers(config, connection, Vec::new())
+    }
+
+    /// A portfolio that also queries `custom_providers`, which the configured
+    /// `default_api` may name.
+    pub fn from_config_with_providers(
+        config: &Config,
+        connection: Pool<Sqlite>,
+        custom_providers: Vec<Arc<dyn QuoteProvider>>,
+    ) -> Result<Self> {
+        let provider_config = config.providers();
+        let local_fixtures_dir = config.local_fixtures_dir_expanded().map(PathBuf::from);
+
+        let mut providers = ProviderRegistry::with_defaults(
+            provider_config.alpha_vantage_api_key().clone(),
+            provider_config.fmp_api_key().clone(),
+            provider_config.marketstack_api_key().clone(),
+            local_fixtures_dir.clone(),
+        );
+        if provider_config.use_local_forex() {
+            providers.set_forex(Arc::new(LocalProvider::new(local_fixtures_dir)));
+        }
+        for provider in custom_providers {
+            providers.register(provider);
+        }
+        let default_api = providers
+            .parse_api(provider_config.default_api())
+            .with_context(|| format!("Unknown default_api '{}'", provider_config.default_api()))?;
+
+        Ok(Self {
+            base_currency: config.base_currency().clone(),
             connection,
             positions: Vec::new(),
             client: Client::new(),
-            default_api: ApiProvider::Marketstack,
-            api_key_alpha_vantage: std::env::var("ALPHA_VANTAGE_API_KEY").ok(),
-            api_key_fmp: std::env::var("FMP_API_KEY").ok(),
-            api_key_marketstack: std::env::var("MARKETSTACK_API_KEY").ok(),
+            default_api,
+            providers,
+            api_key_alpha_vantage: provider_config.alpha_vantage_api_key().clone(),
+            api_key_fmp: provider_config.fmp_api_key().clone(),
+            api_key_marketstack: provider_config.marketstack_api_key().clone(),
             forex_map: HashMap::new(),
+            forex_map_dates: HashMap::new(),
+            cost_basis: config.cost_basis().clone(),
+            dividends: config.dividends().clone(),
+            import_profiles: config.import_profiles().clone(),
+        })
+    }
+
+    pub fn register_provider(&mut self, provider: Arc<dyn QuoteProvider>) {
+        self.providers.register(provider);
+    }
+
+    pub fn set_forex_provider(&mut self, forex: Arc<dyn ForexProvider>) {
+        self.providers.set_forex(forex);
+    }
+
+    /// Switches the cost-basis settings and recalculates the stored
+    /// transactions if they change.
+    pub async fn set_cost_basis(&mut self, cost_basis: CostBasisConfig) -> Result<bool> {
+        self.cost_basis = cost_basis;
+        self.sync_cost_basis().await
+    }
+
+    /// Recalculates the stored transactions if they were computed with other
+    /// cost-basis settings than the current ones. Returns whether it did.
+    pub async fn sync_cost_basis(&mut self) -> Result<bool> {
+        let signature = self.cost_basis.signature()?;
+        let stored_signature = match get_setting(&self.connection, COST_BASIS_SETTING).await? {
+            Some(stored_signature) => stored_signature,
+            None => CostBasisConfig::default().signature()?,
+        };
+
+        if signature == stored_signature {
+            return Ok(false);
+        }
+
+        self.recalculate_transactions().await?;
+        Ok(true)
+    }
+
+    /// Replays every transaction chain with the current cost-basis settings and
+    /// overwrites the computed columns.
+    pub async fn recalculate_transactions(&self) -> Result<()> {
+        let mut tx = self.connection.begin().await?;
+
+        self.replay_chains(None, &mut tx).await?;
+        set_setting(COST_BASIS_SETTING, &self.cost_basis.signature()?, &mut tx).await?;
+
+        tx.commit()
+            .await
+            .with_context(|| "Failed to commit database transaction")?;
+
+        Ok(())
+    }
+
+    /// Replays the chains of `ticker_ids`, or of every ticker when `None`, as
+    /// stored in `tx` and overwrites the computed columns. Transactions are
+    /// replayed by date, and by number within a day, so backdated ones take
+    /// their place in the chain.
+    async fn replay_chains(
+        &self,
+        ticker_ids: Option<&[i64]>,
+        tx: &mut sqlx::Transaction<'_, Sqlite>,
+    ) -> Result<()> {
+        let rows = sqlx::query(
+            "SELECT * FROM transactions ORDER BY transaction_date ASC, transaction_no ASC",
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        let mut lots: HashMap<i64, TickerLots> = HashMap::new();
+
+        for row in rows {
+            let mut transaction = parse_transaction(row)?;
+            if transaction.transaction_type().is_cash() {
+                continue;
+            }
+            if let Some(ticker_ids) = ticker_ids
+                && !ticker_ids.contains(transaction.ticker_id())
+            {
+                continue;
+            }
+
+            let ticker_lots = lots
+                .entry(*transaction.ticker_id())
+                .or_insert_with(|| TickerLots::new(self.cost_basis.clone()));
+
+            let position_state = ticker_lots.apply(&transaction).with_context(|| {
+                format!(
+                    "Failed to recalculate transaction {}",
+                    transaction.transaction_no()
+                )
+            })?;
+            let transaction_gains = calculate_transaction_gains(&transaction, &position_state);
+
+            transaction.set_position_state(Some(position_state));
+            transaction.set_transaction_gains(Some(transaction_gains));
+            update_transaction_state(&transaction, tx).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores a transaction entered in the TUI, replacing transaction `id` when
+    /// given, and replays the chains of the affected tickers. New transactions
+    /// are numbered after the last one, which imports then refuse to replace;
+    /// unknown symbols are looked up with the default API. Returns the id of
+    /// the stored transaction.
+    pub async fn save_transaction(
+        &mut self,
+        id: Option<i64>,
+        input: &TransactionInput,
+    ) -> Result<i64> {
+        let transaction_type = input.transaction_type();
+
+        let ticker = if transaction_type.is_cash() {
+            None
+        } else {
+            let symbols = vec![input.symbol().clone()];
+            let mut ticker_map = self.get_existing_tickers().await?;
+            let lookup_errors = self
+                .update_tickers(&symbols, &mut ticker_map, &self.default_api)
+                .await?;
+            if let Some(e) = lookup_errors.get(input.symbol()) {
+                return Err(anyhow::anyhow!(
+                    "Could not find symbol {}: {}",
+                    input.symbol(),
+                    e
+                ));
+            }
+            Some(
+                ticker_map
+                    .get(input.symbol())
+                    .cloned()
+                    .with_context(|| format!("Could not find symbol {}", input.symbol()))?,
+            )
+        };
+
+        let existing = match id {
+            Some(id) => Some(self.get_transaction(id).await?),
+            None => None,
+        };
+        let transaction_no = match &existing {
+            Some(existing) => *existing.transaction_no(),
+            None => self.get_last_transaction_no().await? + 1,
+        };
+        // Taxes are not part of the form, so edits keep the stored ones
+        let (withholding_tax, domestic_tax) = existing
+            .as_ref()
+            .map(|existing| (*existing.withholding_tax(), *existing.domestic_tax()))
+            .unwrap_or_default();
+
+        // Rates are looked up before the write transaction, which would hold
+        // the database lock while waiting for the provider
+        let forex = self.providers.forex();
+        let mut connection = self.connection.acquire().await?;
+
+        let (ticker_id, currency, price) = match &ticker {
+            None if input.currency().is_empty() => (0, self.base_currency.clone(), *input.price()),
+            None => (0, input.currency().clone(), *input.price()),
+            Some((ticker, ticker_id)) => {
+                let currency = ticker.currency();
+                let mut price = *input.price();
+                if !input.currency().is_empty() && input.currency() != currency {
+                    price *= get_exchange_rate(
+                        currency,
+                        input.currency(),
+                        input.date(),
+                        forex.as_ref(),
+                        &self.client,
+                        &mut connection,
+                    )
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to get exchange rate for {} to {}",
+                            currency,
+                            input.currency()
+                        )
+                    })?;
+                }
+                (*ticker_id, currency.clone(), price)
+            }
+        };
+
+        let exchange_rate = get_exchange_rate(
+            &currency,
+            &self.base_currency,
+            input.date(),
+            forex.as_ref(),
+            &self.client,
+            &mut connection,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to get exchange rate for {} to {}",
+                currency, self.base_currency
+            )
+        })?;
+        drop(connection);
+
+        let mut transaction = Transaction::new(
+            id.unwrap_or(0),
+            ticker_id,
+            transaction_no,
+            *input.date(),
+            transaction_type.clone(),
+            input.broker().clone(),
+            currency,
+            exchange_rate,
+            *input.quantity(),
+            price,
+            *input.fees(),
+            withholding_tax,
+            domestic_tax,
+            None,
+            None,
+        );
+        // Replaced by the chain replay below for all but cash transactions
+        let position_state = PositionState::new(Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+        let transaction_gains = calculate_transaction_gains(&transaction, &position_state);
+        transaction.set_position_state(Some(position_state));
+        transaction.set_transaction_gains(Some(transaction_gains));
+
+        let mut tx = self.connection.begin().await?;
+        let id = match id {
+            Some(id) => {
+                update_transaction(&transaction, &ticker_id, None, &mut tx)
+                    .await
+                    .with_context(|| format!("Failed to update transaction {}", transaction_no))?;
+                id
+            }
+            None => insert_transaction(
+                &transaction,
+                &ticker_id,
+                &TransactionSource::App,
+                None,
+                None,
+                &mut tx,
+            )
+            .await
+            .with_context(|| format!("Failed to insert transaction {}", transaction_no))?,
+        };
+
+        let mut ticker_ids = Vec::new();
+        if !transaction_type.is_cash() {
+            ticker_ids.push(ticker_id);
+        }
+        if let Some(existing) = &existing
+            && !existing.transaction_type().is_cash()
+            && existing.ticker_id() != &ticker_id
+        {
+            ticker_ids.push(*existing.ticker_id());
+        }
+        self.replay_chains(Some(&ticker_ids), &mut tx).await?;
+
+        tx.commit()
+            .await
+            .with_context(|| "Failed to commit database transaction")?;
+
+        Ok(id)
+    }
+
+    /// Deletes transaction `id` and replays the chain of its ticker.
+    pub async fn delete_transaction(&self, id: i64) -> Result<()> {
+        let transaction = self.get_transaction(id).await?;
+
+        let mut tx = self.connection.begin().await?;
+        sqlx::query("DELETE FROM transactions WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        if !transaction.transaction_type().is_cash() {
+            self.replay_chains(Some(&[*transaction.ticker_id()]), &mut tx)
+                .await?;
         }
+
+        tx.commit()
+            .await
+            .with_context(|| "Failed to commit database transaction")?;
+
+        Ok(())
+    }
+
+    async fn get_transaction(&self, id: i64) -> Result<Transaction> {
+        let row = sqlx::query("SELECT * FROM transactions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.connection)
+            .await?
+            .with_context(|| format!("Transaction {} not found", id))?;
+
+        parse_transaction(row)
     }
 
     pub async fn reset(&mut self, clear_assets: bool) -> Result<()> {
@@ -68,27 +441,18 @@ impl Portfolio {
         let tickers = sqlx::query(
             r#"
             WITH
-            cte_realized_gain_dividends AS (
-                SELECT
-                    ticker_id,
-                    broker,
-                    SUM(realized_gain) as realized_gain,
-                    SUM(dividend) as dividend
-                FROM
-                    transactions
-                GROUP BY
-                    broker,
-                    ticker_id
-            ),
             cte_transactions_rn AS (
                 SELECT
                     transactions.*,
-                    ROW_NUMBER() OVER (PARTITION BY ticker_id, broker ORDER BY transaction_no DESC)
+                    ROW_NUMBER() OVER (
+                        PARTITION BY ticker_id, broker
+                        ORDER BY transaction_date DESC, transaction_no DESC
+                    )
                         AS rn
                 FROM
                     transactions
                 WHERE
-                    transaction_type IN ('Buy', 'Sell')
+                    transaction_type IN ('Buy', 'Sell', 'Split', 'TransferOut', 'TransferIn')
             ),
             cte_transactions AS (
                 SELECT
@@ -99,6 +463,7 @@ impl Portfolio {
                     rn = 1
             )
             SELECT
+                ast.id AS asset_id,
                 ast.name,
                 ast.asset_type,
                 ast.isin,
@@ -106,17 +471,13 @@ impl Portfolio {
                 ast.industry,
                 tcr.last_price,
                 tcr.currency,
+                tnx.ticker_id,
+                tnx.broker,
                 tnx.exchange_rate,
                 tnx.cumulative_units,
-                tnx.cumulative_cost,
-                rld.realized_gain,
-                rld.dividend
+                tnx.cumulative_cost
             FROM
                 cte_transactions tnx
-            INNER JOIN
-                cte_realized_gain_dividends rld
-                ON tnx.ticker_id = rld.ticker_id
-                AND tnx.broker = rld.broker
             INNER JOIN
                 tickers tcr
                 ON tnx.ticker_id = tcr.id
@@ -124,15 +485,17 @@ impl Portfolio {
                 assets ast
                 ON tcr.asset_id = ast.id
             WHERE
-                tnx.cumulative_units > 0
+                CAST(tnx.cumulative_units AS REAL) > 0
             "#,
         )
         .fetch_all(&self.connection)
         .await?;
 
+        let gains = self.get_realized_gains_and_dividends().await?;
         let mut positions: Vec<Position> = Vec::new();
 
         for row in tickers.iter() {
+            let asset_id = parse_i64_from_row(row, "asset_id")?;
             let name = parse_string_from_row(row, "name")?;
             let asset_type_str = parse_string_from_row(row, "asset_type")?;
             let isin = parse_string_from_row(row, "isin").ok();
@@ -140,7 +503,7 @@ impl Portfolio {
             let industry = parse_string_from_row(row, "industry").ok();
 
             let asset = Asset::new(
-                0,
+                asset_id,
                 name,
                 AssetType::parse_str(&asset_type_str).unwrap_or(AssetType::Stock),
                 isin,
@@ -176,8 +539,12 @@ impl Portfolio {
                 Decimal::ZERO
             };
 
-            let realized_gain = parse_decimal_from_row(row, "realized_gain")?;
-            let dividend = parse_decimal_from_row(row, "dividend")?;
+            let ticker_id = parse_i64_from_row(row, "ticker_id")?;
+            let broker = parse_string_from_row(row, "broker")?;
+            let (realized_gain, dividend) = gains
+                .get(&(ticker_id, broker))
+                .copied()
+                .unwrap_or((Decimal::ZERO, Decimal::ZERO));
 
             let total_gain = unrealized_gain + realized_gain + dividend;
 
@@ -198,243 +565,896 @@ impl Portfolio {
             positions.push(position);
         }
 
+        positions.extend(self.get_cash_positions().await?);
+
         self.positions.clear();
         self.positions = positions;
 
         Ok(())
     }
 
-    async fn get_existing_tickers(&mut self) -> Result<HashMap<String, (Ticker, i64)>> {
-        let tickers = sqlx::query(
+    pub fn set_dividend_config(&mut self, dividends: DividendConfig) {
+        self.dividends = dividends;
+    }
+
+    pub fn set_import_profiles(&mut self, import_profiles: BTreeMap<String, ImportProfile>) {
+        self.import_profiles = import_profiles;
+    }
+
+    /// Metadata, tickers, transactions and open lots of one asset. Lots are
+    /// valued with the last price of their ticker.
+    pub async fn get_position_detail(&self, asset_id: i64) -> Result<PositionDetail> {
+        let row = sqlx::query("SELECT * FROM assets WHERE id = ?")
+            .bind(asset_id)
+            .fetch_one(&self.connection)
+            .await
+            .with_context(|| format!("Failed to load asset {}", asset_id))?;
+        let asset_type_str = parse_string_from_row(&row, "asset_type")?;
+        let asset = Asset::new(
+            asset_id,
+            parse_string_from_row(&row, "name")?,
+            AssetType::parse_str(&asset_type_str).unwrap_or(AssetType::Stock),
+            parse_string_from_row(&row, "isin").ok(),
+            parse_string_from_row(&row, "sector").ok(),
+            parse_string_from_row(&row, "industry").ok(),
+        );
+
+        let ticker_rows = sqlx::query(
             r#"
-            SELECT * FROM tickers
-            INNER JOIN assets on tickers.asset_id = assets.id
+            SELECT
+                tcr.*,
+                ast.name
+            FROM
+                tickers tcr
+            INNER JOIN
+                assets ast
+                ON tcr.asset_id = ast.id
+            WHERE
+                tcr.asset_id = ?
+            ORDER BY
+                tcr.symbol ASC
             "#,
         )
+        .bind(asset_id)
         .fetch_all(&self.connection)
         .await?;
+        let tickers = ticker_rows
+            .iter()
+            .map(|row| parse_ticker(row, &self.providers))
+            .collect::<Result<Vec<Ticker>>>()?;
 
-        let mut ticker_map: HashMap<String, (Ticker, i64)> = HashMap::new();
-        for row in tickers {
-            let symbol = parse_string_from_row(&row, "symbol")?;
-            let ticker_id = parse_i64_from_row(&row, "id")?;
-            let asset_id = parse_i64_from_row(&row, "asset_id")?;
-            let name = parse_string_from_row(&row, "name")?;
-            let currency = parse_string_from_row(&row, "currency")?;
-            let exchange = parse_string_from_row(&row, "exchange").ok();
-            let last_price = parse_decimal_from_row(&row, "last_price").ok();
-            let last_price_updated_at = parse_datetime_from_row(&row, "last_price_updated_at").ok();
-            let api_str = parse_string_from_row(&row, "api")?;
+        let transaction_rows = sqlx::query(
+            r#"
+            SELECT
+                tnx.*
+            FROM
+                transactions tnx
+            INNER JOIN
+                tickers tcr
+                ON tnx.ticker_id = tcr.id
+            WHERE
+                tcr.asset_id = ?
+            ORDER BY
+                tnx.transaction_date ASC,
+                tnx.transaction_no ASC
+            "#,
+        )
+        .bind(asset_id)
+        .fetch_all(&self.connection)
+        .await?;
+        let transactions = transaction_rows
+            .into_iter()
+            .map(parse_transaction)
+            .collect::<Result<Vec<Transaction>>>()?;
+
+        let mut lots = Vec::new();
+        for ticker in tickers.iter() {
+            let chain: Vec<Transaction> = transactions
+                .iter()
+                .filter(|t| t.ticker_id() == ticker.id())
+                .cloned()
+                .collect();
+            let Some(last_transaction) = chain.last() else {
+                continue;
+            };
 
-            let ticker = Ticker::new(
-                ticker_id,
-                asset_id,
-                symbol.clone(),
-                name,
-                currency,
-                exchange,
-                last_price,
-                last_price_updated_at,
-                ApiProvider::parse_str(&api_str)?,
+            let exchange_rate = self
+                .forex_map
+                .get(ticker.currency())
+                .copied()
+                .unwrap_or(*last_transaction.exchange_rate());
+            let price = if exchange_rate == Decimal::ZERO {
+                Decimal::ZERO
+            } else {
+                ticker.last_price().unwrap_or_default() / exchange_rate
+            };
+
+            let ticker_lots = calculate_lots(&chain, &self.cost_basis)
+                .with_context(|| format!("Failed to calculate lots for {}", ticker.symbol()))?;
+            for (broker, queue) in ticker_lots.queues() {
+                for lot in queue.lots() {
+                    let market_value = lot.quantity() * price;
+                    lots.push(LotDetail::new(
+                        ticker.symbol().clone(),
+                        broker.clone(),
+                        lot.clone(),
+                        market_value,
+                        market_value - lot.cost(),
+                    ));
+                }
+            }
+        }
+        lots.sort_by_key(|lot| (*lot.lot().date(), *lot.lot().transaction_no()));
+
+        Ok(PositionDetail::new(
+            asset,
+            tickers,
+            transactions,
+            self.cost_basis.signature()?,
+            lots,
+        ))
+    }
+
+    /// One page of the transactions matching `filter`, cash transactions
+    /// included. `page` starts at zero.
+    pub async fn get_ledger(
+        &self,
+        filter: &LedgerFilter,
+        sort: LedgerSort,
+        descending: bool,
+        page: i64,
+        page_size: i64,
+    ) -> Result<LedgerPage> {
+        fn push_filter(query_builder: &mut QueryBuilder<'_, Sqlite>, filter: &LedgerFilter) {
+            query_builder.push(
+                r#"
+                FROM
+                    transactions tnx
+                LEFT JOIN
+                    tickers tcr
+                    ON tnx.ticker_id = tcr.id
+                LEFT JOIN
+                    assets ast
+                    ON tcr.asset_id = ast.id
+                WHERE
+                    1 = 1
+                "#,
             );
-            ticker_map.insert(symbol, (ticker, ticker_id));
+            if let Some(symbol) = filter.symbol() {
+                query_builder
+                    .push(" AND tcr.symbol = ")
+                    .push_bind(symbol.clone());
+            }
+            if let Some(broker) = filter.broker() {
+                query_builder
+                    .push(" AND tnx.broker = ")
+                    .push_bind(broker.clone());
+            }
+            if let Some(transaction_type) = filter.transaction_type() {
+                query_builder
+                    .push(" AND tnx.transaction_type = ")
+                    .push_bind(transaction_type.to_str().to_string());
+            }
+            if let Some(from) = filter.from().and_then(local_midnight) {
+                query_builder
+                    .push(" AND tnx.transaction_date >= ")
+                    .push_bind(from);
+            }
+            if let Some(to) = filter
+                .to()
+                .and_then(|to| to.succ_opt())
+                .and_then(local_midnight)
+            {
+                query_builder
+                    .push(" AND tnx.transaction_date < ")
+                    .push_bind(to);
+            }
         }
 
-        Ok(ticker_map)
-    }
+        let mut count_builder = QueryBuilder::new("SELECT COUNT(*) ");
+        push_filter(&mut count_builder, filter);
+        let total: i64 = count_builder
+            .build_query_scalar()
+            .fetch_one(&self.connection)
+            .await
+            .with_context(|| "Failed to count transactions")?;
 
-    async fn get_existing_forex(&mut self) -> Result<HashMap<i64, Decimal>> {
-        let transaction_forex =
-            sqlx::query("SELECT transaction_no, exchange_rate FROM transactions")
-                .fetch_all(&self.connection)
-                .await?;
+        let order = match sort {
+            LedgerSort::Date => "tnx.transaction_date",
+            LedgerSort::Symbol => "tcr.symbol",
+            LedgerSort::Type => "tnx.transaction_type",
+            LedgerSort::Broker => "tnx.broker",
+            LedgerSort::Quantity => "CAST(tnx.quantity AS REAL)",
+        };
+        let direction = if descending { "DESC" } else { "ASC" };
 
-        let mut forex_map: HashMap<i64, Decimal> = HashMap::new();
-        for row in transaction_forex {
-            let txn_no = parse_i64_from_row(&row, "transaction_no")?;
-            let x_rate = parse_decimal_from_row(&row, "exchange_rate")?;
+        let mut query_builder = QueryBuilder::new(
+            "SELECT tnx.*, COALESCE(tcr.symbol, '') AS symbol, ast.isin AS alternative_symbol ",
+        );
+        push_filter(&mut query_builder, filter);
+        query_builder
+            .push(format!(
+                " ORDER BY {} {}, tnx.transaction_no {} LIMIT ",
+                order, direction, direction
+            ))
+            .push_bind(page_size)
+            .push(" OFFSET ")
+            .push_bind(page * page_size);
 
-            forex_map.insert(txn_no, x_rate);
+        let rows = query_builder.build().fetch_all(&self.connection).await?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let symbol = parse_string_from_row(&row, "symbol")?;
+            let alternative_symbol: Option<String> = row.try_get("alternative_symbol")?;
+            let external_id: Option<String> = row.try_get("external_id")?;
+            entries.push(LedgerEntry::new(
+                symbol,
+                alternative_symbol.filter(|isin| !isin.is_empty()),
+                external_id,
+                parse_transaction(row)?,
+            ));
         }
 
-        Ok(forex_map)
+        Ok(LedgerPage::new(entries, page, page_size, total))
     }
 
-    async fn get_last_transaction_no(&mut self) -> Result<i64> {
-        let result =
-            sqlx::query_scalar::<_, Option<i64>>("SELECT MAX(transaction_no) FROM transactions")
-                .fetch_one(&self.connection)
-                .await?;
-
-        Ok(result.unwrap_or(0))
+    /// Writes the positions, all transactions with their position state and
+    /// gains, and the realized gains of sales to `directory`. Returns the paths
+    /// of the files written.
+    pub async fn export(&self, directory: &Path, format: ExportFormat) -> Result<Vec<PathBuf>> {
+        let entries = self
+            .get_ledger(
+                &LedgerFilter::default(),
+                LedgerSort::Date,
+                false,
+                0,
+                i64::MAX,
+            )
+            .await
+            .with_context(|| "Failed to read transactions for export")?
+            .entries()
+            .clone();
+
+        [
+            positions_table(&self.positions),
+            transactions_table(&entries),
+            realized_gains_table(&entries),
+        ]
+        .iter()
+        .map(|table| table.write(directory, format))
+        .collect()
     }
 
-    async fn get_historical_transactions(&self, ticker_ids: Vec<i64>) -> Result<Vec<Transaction>> {
-        let mut query_builder = QueryBuilder::new(
+    /// Gross, withheld and net dividends per position, year and source country.
+    pub async fn get_dividend_report(&self) -> Result<DividendReport> {
+        let rows = sqlx::query(
             r#"
             SELECT
-                id,
-                ticker_id,
-                transaction_no,
-                transaction_date,
-                transaction_type,
-                broker,
-                currency,
-                exchange_rate,
-                quantity,
-                price,
-                fees
+                tnx.*,
+                ast.name,
+                ast.isin
             FROM
-                transactions
+                transactions tnx
+            INNER JOIN
+                tickers tcr
+                ON tnx.ticker_id = tcr.id
+            INNER JOIN
+                assets ast
+                ON tcr.asset_id = ast.id
             WHERE
-                ticker_id IN (
-            "#,
-        );
-        let mut separated = query_builder.separated(", ");
-        for id in ticker_ids {
-            separated.push_bind(id);
-        }
-        separated.push_unseparated(
-            r#"
-                )
-                AND transaction_type IN ('Buy', 'Sell')
+                tnx.transaction_type = 'Div'
             ORDER BY
-                transaction_no ASC
+                tnx.transaction_date ASC,
+                tnx.transaction_no ASC
             "#,
-        );
-
-        let rows = query_builder.build().fetch_all(&self.connection).await?;
-
-        let mut transactions = Vec::new();
+        )
+        .fetch_all(&self.connection)
+        .await?;
 
+        let mut report = DividendReport::default();
         for row in rows {
+            let name = parse_string_from_row(&row, "name")?;
+            let isin = parse_string_from_row(&row, "isin").ok();
             let transaction = parse_transaction(row)?;
-            transactions.push(transaction);
+
+            let country = country_from_isin(isin.as_deref());
+            let dividend =
+                calculate_dividend_breakdown(&transaction, self.dividends.treaty_rate(&country));
+            report.add(&name, transaction.date().year(), &country, &dividend);
         }
 
-        Ok(transactions)
+        Ok(report)
     }
 
-    pub async fn import_transactions(&mut self, path: &str, api: &ApiProvider) -> Result<()> {
-        let mut reader = Reader::from_path(path)
-            .with_context(|| format!("Failed to open CSV file at path: {}", path))?;
+    async fn get_cash_positions(&self) -> Result<Vec<Position>> {
+        let rows = sqlx::query(
+            "SELECT * FROM transactions ORDER BY transaction_date ASC, transaction_no ASC",
+        )
+        .fetch_all(&self.connection)
+        .await?;
+        let transactions = rows
+            .into_iter()
+            .map(parse_transaction)
+            .collect::<Result<Vec<Transaction>>>()?;
+
+        let mut positions = Vec::new();
+        for cash_balance in calculate_cash_balances(&transactions) {
+            let exchange_rate = self
+                .forex_map
+                .get(cash_balance.currency())
+                .with_context(|| {
+                    format!(
+                        "Failed to get exchange rate from hashmap for currency {}",
+                        cash_balance.currency()
+                    )
+                })?;
+
+            let asset = Asset::new(
+                0,
+                format!(
+                    "Cash {} ({})",
+                    cash_balance.currency(),
+                    cash_balance.broker()
+                ),
+                AssetType::Cash,
+                None,
+                None,
+                None,
+            );
 
-        let headers = reader
-            .headers()
-            .with_context(|| format!("Failed to read CSV headers from file: {}", path))?;
+            let adjusted_price = dec!(1) / exchange_rate;
+            let market_value = (adjusted_price * cash_balance.balance()).round();
+            let income = cash_balance.income() * adjusted_price;
 
-        if headers.len() < 10 {
-            return Err(anyhow::anyhow!(
-                "Invalid CSV format: expected at least 10 columns, found {}",
-                headers.len()
+            positions.push(Position::new(
+                asset,
+                *cash_balance.balance(),
+                adjusted_price,
+                market_value,
+                market_value,
+                dec!(1),
+                Decimal::ZERO,
+                Decimal::ZERO,
+                Decimal::ZERO,
+                income,
+                income,
             ));
         }
 
-        let mut symbols = std::collections::HashSet::new();
-        for record in reader.records() {
-            let rec = record?;
-            if let Some(symbol) = rec.get(3) {
-                symbols.insert(symbol.to_string());
-            }
-            if let Some(alternative_symbol) = rec.get(8) {
-                if alternative_symbol.len() > 0 {
-                    symbols.insert(alternative_symbol.to_string());
-                }
-            }
-        }
-        let unique_symbols: Vec<String> = symbols.into_iter().collect();
+        Ok(positions)
+    }
 
-        let mut ticker_map = self.get_existing_tickers().await?;
-        ticker_map = self
-            .update_tickers(&unique_symbols, &mut ticker_map, api)
-            .await?;
-        let ticker_ids = ticker_map.values().map(|val| val.1).collect();
+    async fn get_realized_gains_and_dividends(
+        &self,
+    ) -> Result<HashMap<(i64, String), (Decimal, Decimal)>> {
+        // Summed here rather than with SUM(), which would go through REAL
+        let rows = sqlx::query(
+            r#"
+            SELECT ticker_id, broker, realized_gain, dividend
+            FROM transactions
+            WHERE ticker_id IS NOT NULL
+            "#,
+        )
+        .fetch_all(&self.connection)
+        .await?;
 
-        let hist_transactions = self.get_historical_transactions(ticker_ids).await?;
+        let mut gains: HashMap<(i64, String), (Decimal, Decimal)> = HashMap::new();
+        for row in rows {
+            let ticker_id = parse_i64_from_row(&row, "ticker_id")?;
+            let broker = parse_string_from_row(&row, "broker")?;
+            let realized_gain = parse_decimal_from_row(&row, "realized_gain")?;
+            let dividend = parse_decimal_from_row(&row, "dividend")?;
+
+            let entry = gains
+                .entry((ticker_id, broker))
+                .or_insert((Decimal::ZERO, Decimal::ZERO));
+            entry.0 += realized_gain;
+            entry.1 += dividend;
+        }
 
-        let mut reader = Reader::from_path(path)
-            .with_context(|| format!("Failed to reopen CSV file at path: {}", path))?;
-        reader.headers()?;
+        Ok(gains)
+    }
 
-        let mut transactions: Vec<Transaction> = Vec::new();
-        let forex_map = self.get_existing_forex().await?;
-        let last_transaction_no = self.get_last_transaction_no().await?;
+    async fn get_existing_tickers(&mut self) -> Result<HashMap<String, (Ticker, i64)>> {
+        let tickers = sqlx::query(
+            r#"
+            SELECT * FROM tickers
+            INNER JOIN assets on tickers.asset_id = assets.id
+            "#,
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        let mut ticker_map: HashMap<String, (Ticker, i64)> = HashMap::new();
+        for row in tickers {
+            let ticker = parse_ticker(&row, &self.providers)?;
+            let symbol = ticker.symbol().clone();
+            let ticker_id = *ticker.id();
+            // Statements name securities by ISIN when their symbols differ
+            let isin: Option<String> = row.try_get("isin")?;
+            if let Some(isin) = isin.filter(|isin| !isin.is_empty()) {
+                ticker_map
+                    .entry(isin)
+                    .or_insert_with(|| (ticker.clone(), ticker_id));
+            }
+            ticker_map.insert(symbol, (ticker, ticker_id));
+        }
+
+        Ok(ticker_map)
+    }
+
+    /// Stored transactions by transaction number.
+    async fn get_stored_transactions(&self) -> Result<HashMap<i64, StoredTransaction>> {
+        let rows = sqlx::query("SELECT * FROM transactions")
+            .fetch_all(&self.connection)
+            .await?;
+
+        let mut stored_transactions = HashMap::new();
+        for row in rows {
+            let content_hash: Option<String> = row.try_get("content_hash")?;
+            let source = TransactionSource::parse_str(&parse_string_from_row(&row, "source")?)?;
+            let external_id: Option<String> = row.try_get("external_id")?;
+            let transaction = parse_transaction(row)?;
+            stored_transactions.insert(
+                *transaction.transaction_no(),
+                StoredTransaction {
+                    transaction,
+                    content_hash,
+                    source,
+                    external_id,
+                },
+            );
+        }
+
+        Ok(stored_transactions)
+    }
+
+    async fn get_last_transaction_no(&mut self) -> Result<i64> {
+        let result =
+            sqlx::query_scalar::<_, Option<i64>>("SELECT MAX(transaction_no) FROM transactions")
+                .fetch_one(&self.connection)
+                .await?;
+
+        Ok(result.unwrap_or(0))
+    }
+
+    /// Imports a CSV file in one database transaction, or nothing if one of
+    /// its records is invalid.
+    pub async fn import_transactions(&mut self, path: &str, api: &ApiProvider) -> Result<()> {
+        self.commit_import(&[path.to_string()], api).await?;
+        Ok(())
+    }
+
+    /// Runs the import of the CSV files without writing any transaction or
+    /// ticker. New symbols are still looked up with `api`, and exchange rates
+    /// fetched for the records are cached as for a committed import.
+    pub async fn preview_import(
+        &mut self,
+        paths: &[String],
+        api: &ApiProvider,
+    ) -> Result<ImportPreview> {
+        let files = read_import_files(paths, &self.base_currency, &self.import_profiles)?;
+        self.run_import(&files, api, false).await
+    }
+
+    /// Imports the CSV files in one database transaction, or nothing if one of
+    /// their records is invalid.
+    pub async fn commit_import(
+        &mut self,
+        paths: &[String],
+        api: &ApiProvider,
+    ) -> Result<ImportPreview> {
+        let files = read_import_files(paths, &self.base_currency, &self.import_profiles)?;
+        self.run_import(&files, api, true).await
+    }
+
+    /// Records are matched with stored transactions by `transaction_no`, or
+    /// by `external_id` for statement entries; unchanged ones are skipped,
+    /// while corrected or backdated ones are written and the chains of their
+    /// tickers replayed. Records numbered like a transaction entered in the
+    /// app or imported from another record are invalid. Invalid records are
+    /// collected in the preview, which is only committed when there are none.
+    async fn run_import(
+        &mut self,
+        files: &[(String, Vec<Result<StringRecord>>)],
+        api: &ApiProvider,
+        commit: bool,
+    ) -> Result<ImportPreview> {
+        self.sync_cost_basis().await?;
+
+        let mut ticker_map = self.get_existing_tickers().await?;
+        let mut symbols = HashSet::new();
+        for rec in files
+            .iter()
+            .flat_map(|(_, records)| records.iter().flatten())
+        {
+            let alternative_symbol = rec.get(8).unwrap_or_default();
+            // Brokers list a security under the symbol of the exchange it was
+            // traded at, which a provider would store as another ticker
+            if let Some(symbol) = rec.get(3)
+                && !symbol.is_empty()
+                && (ticker_map.contains_key(symbol) || !ticker_map.contains_key(alternative_symbol))
+            {
+                symbols.insert(symbol.to_string());
+            }
+            if !alternative_symbol.is_empty() {
+                symbols.insert(alternative_symbol.to_string());
+            }
+        }
+        let unique_symbols: Vec<String> = symbols.into_iter().collect();
+
+        // New tickers are stored with the transactions, so that a preview
+        // leaves none behind; until then they go by provisional negative ids
+        let (found_tickers, lookup_errors) = self
+            .lookup_tickers(&unique_symbols, &ticker_map, api)
+            .await?;
+        for (i, (symbol, ticker, asset)) in found_tickers.iter().enumerate() {
+            let provisional_id = -(i as i64) - 1;
+            add_ticker(
+                &mut ticker_map,
+                symbol.clone(),
+                ticker.clone(),
+                asset,
+                provisional_id,
+            );
+        }
+        let mut symbols_by_id: HashMap<i64, String> = ticker_map
+            .values()
+            .map(|(ticker, ticker_id)| (*ticker_id, ticker.symbol().clone()))
+            .collect();
+
+        let stored_transactions = self.get_stored_transactions().await?;
+        let external_ids: HashMap<&str, i64> = stored_transactions
+            .iter()
+            .filter_map(|(transaction_no, stored)| {
+                Some((stored.external_id.as_deref()?, *transaction_no))
+            })
+            .collect();
+        // Statement entries seen for the first time are numbered after the
+        // stored transactions and the numbers given in the files
+        let mut next_transaction_no = files
+            .iter()
+            .flat_map(|(_, records)| records.iter().flatten())
+            .filter_map(|rec| rec.get(0)?.parse::<i64>().ok())
+            .chain(stored_transactions.keys().copied())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        // Replayed as a whole, as transfers move lots between brokers
+        let mut replayed_tickers = BTreeSet::new();
+        let mut transaction_nos = HashSet::new();
+        let mut rows = Vec::new();
+        let mut errors = Vec::new();
+
+        // Records are parsed, and their exchange rates looked up, before the
+        // write transaction, which would hold the database lock while waiting
+        // for the provider
+        let forex = self.providers.forex();
+        let mut connection = self.connection.acquire().await?;
+        let mut parsed = Vec::new();
+        for (file_name, records) in files {
+            for (i, rec) in records.iter().enumerate() {
+                let result = match rec {
+                    Ok(rec) => {
+                        self.import_record(
+                            rec,
+                            i + 1,
+                            &ticker_map,
+                            &lookup_errors,
+                            &stored_transactions,
+                            &external_ids,
+                            &mut next_transaction_no,
+                            &mut transaction_nos,
+                            forex.as_ref(),
+                            &mut connection,
+                        )
+                        .await
+                    }
+                    Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+                };
+                match result {
+                    Ok(imported) => parsed.push((i + 1, imported)),
+                    Err(e) => errors.push(format!("{}: {:#}", file_name, e)),
+                }
+            }
+        }
+        drop(connection);
 
         let mut tx = self.connection.begin().await?;
+        let mut ticker_ids = HashMap::new();
+        for (i, (_, ticker, asset)) in found_tickers.iter().enumerate() {
+            let ticker_id = insert_ticker(ticker, asset, &mut tx).await?;
+            ticker_ids.insert(-(i as i64) - 1, ticker_id);
+            symbols_by_id.insert(ticker_id, ticker.symbol().clone());
+        }
+        for (record, (status, mut transaction, content_hash, external_id)) in parsed {
+            if let Some(ticker_id) = ticker_ids.get(transaction.ticker_id()) {
+                transaction.set_ticker_id(*ticker_id);
+            }
+            match status {
+                ImportStatus::New => {
+                    let id = insert_transaction(
+                        &transaction,
+                        transaction.ticker_id(),
+                        &TransactionSource::Import,
+                        Some(&content_hash),
+                        external_id.as_deref(),
+                        &mut tx,
+                    )
+                    .await
+                    .with_context(|| {
+                        format!("Failed to insert transaction in record {}", record)
+                    })?;
+                    transaction.set_id(id);
+                }
+                ImportStatus::Changed => {
+                    update_transaction(
+                        &transaction,
+                        transaction.ticker_id(),
+                        Some(&content_hash),
+                        &mut tx,
+                    )
+                    .await
+                    .with_context(|| {
+                        format!("Failed to update transaction in record {}", record)
+                    })?;
+                }
+                ImportStatus::Unchanged => {}
+            }
 
-        for (i, record) in reader.records().enumerate() {
-            let rec = record.with_context(|| format!("Failed to read CSV record {}", i + 1))?;
+            if status != ImportStatus::Unchanged {
+                if !transaction.transaction_type().is_cash() {
+                    replayed_tickers.insert(*transaction.ticker_id());
+                }
+                if let Some(stored) = stored_transactions.get(transaction.transaction_no())
+                    && !stored.transaction.transaction_type().is_cash()
+                {
+                    replayed_tickers.insert(*stored.transaction.ticker_id());
+                }
+            }
+            let symbol = symbols_by_id
+                .get(transaction.ticker_id())
+                .filter(|_| !transaction.transaction_type().is_cash())
+                .cloned()
+                .unwrap_or_default();
+            rows.push(ImportRow::new(record, symbol, status, transaction));
+        }
 
-            let missing_msg =
-                |col: &str, row: usize| format!("Missing '{}' column in record {}", col, row);
+        for ticker_id in &replayed_tickers {
+            if let Err(e) = self.replay_chains(Some(&[*ticker_id]), &mut tx).await {
+                let symbol = symbols_by_id.get(ticker_id).cloned().unwrap_or_default();
+                errors.push(format!("{}: {:#}", symbol, e));
+            }
+        }
 
-            let failed_to_parse_msg =
-                |col: &str, row: usize| format!("Failed to parse '{}' in record {}", col, row);
+        let positions_before = broker_positions(
+            stored_transactions
+                .values()
+                .map(|stored| &stored.transaction),
+        );
+        let mut imported = Vec::new();
+        for row in sqlx::query("SELECT * FROM transactions")
+            .fetch_all(&mut *tx)
+            .await?
+        {
+            imported.push(parse_transaction(row)?);
+        }
+        let positions_after = broker_positions(imported.iter());
+
+        let keys: BTreeSet<&(i64, String)> = positions_before
+            .keys()
+            .chain(positions_after.keys())
+            .filter(|(ticker_id, _)| replayed_tickers.contains(ticker_id))
+            .collect();
+        let mut position_changes = Vec::new();
+        for key in keys {
+            let (units_before, cost_before) =
+                positions_before.get(key).copied().unwrap_or_default();
+            let (units_after, cost_after) = positions_after.get(key).copied().unwrap_or_default();
+            if (units_before, cost_before) != (units_after, cost_after) {
+                position_changes.push(PositionChange::new(
+                    symbols_by_id.get(&key.0).cloned().unwrap_or_default(),
+                    key.1.clone(),
+                    units_before,
+                    units_after,
+                    cost_before,
+                    cost_after,
+                ));
+            }
+        }
+
+        let preview = ImportPreview::new(rows, position_changes, errors);
+        if !commit {
+            tx.rollback().await?;
+            return Ok(preview);
+        }
+        if !preview.errors().is_empty() {
+            return Err(anyhow::anyhow!(
+                "Found {} invalid records:\n{}",
+                preview.errors().len(),
+                preview.errors().join("\n")
+            ));
+        }
+
+        tx.commit()
+            .await
+            .with_context(|| "Failed to commit database transaction")?;
+
+        Ok(preview)
+    }
 
-            let transaction_no = rec
-                .get(0)
-                .with_context(|| missing_msg("transaction_no", i + 1))?
+    /// Parses a CSV record in the import format into a new transaction, or a
+    /// changed one when a transaction of the same number, or for statement
+    /// entries of the same external id, is stored, with the record's content
+    /// hash and external id. Stored transactions imported from the same record are returned
+    /// unchanged. The position state is left to the chain replay.
+    async fn import_record(
+        &self,
+        rec: &StringRecord,
+        record: usize,
+        ticker_map: &HashMap<String, (Ticker, i64)>,
+        lookup_errors: &HashMap<String, String>,
+        stored_transactions: &HashMap<i64, StoredTransaction>,
+        external_ids: &HashMap<&str, i64>,
+        next_transaction_no: &mut i64,
+        transaction_nos: &mut HashSet<i64>,
+        forex: &dyn ForexProvider,
+        connection: &mut SqliteConnection,
+    ) -> Result<(ImportStatus, Transaction, String, Option<String>)> {
+        let missing_msg =
+            |col: &str, row: usize| format!("Missing '{}' column in record {}", col, row);
+
+        let failed_to_parse_msg =
+            |col: &str, row: usize| format!("Failed to parse '{}' in record {}", col, row);
+
+        let transaction_no_field = rec
+            .get(0)
+            .with_context(|| missing_msg("transaction_no", record))?;
+        let external_id = rec
+            .get(13)
+            .map(str::trim)
+            .filter(|external_id| !external_id.is_empty());
+        let transaction_no = match external_id.and_then(|id| external_ids.get(id)) {
+            Some(transaction_no) => *transaction_no,
+            None if external_id.is_some() && transaction_no_field.is_empty() => {
+                *next_transaction_no += 1;
+                *next_transaction_no - 1
+            }
+            None => transaction_no_field
                 .parse::<i64>()
-                .with_context(|| failed_to_parse_msg("transaction_no", i + 1))?;
+                .with_context(|| failed_to_parse_msg("transaction_no", record))?,
+        };
 
-            let date = parse_datetime(rec.get(1).with_context(|| missing_msg("date", i + 1))?)
-                .with_context(|| failed_to_parse_msg("date", i + 1))?;
+        if !transaction_nos.insert(transaction_no) {
+            return Err(anyhow::anyhow!(
+                "Duplicate transaction_no {} in record {}",
+                transaction_no,
+                record
+            ));
+        }
 
-            if last_transaction_no != 0 && (transaction_no <= last_transaction_no) {
-                continue;
+        let date = parse_datetime(rec.get(1).with_context(|| missing_msg("date", record))?)
+            .with_context(|| failed_to_parse_msg("date", record))?;
+
+        let content_hash = hash_record(rec.iter().take(IMPORT_COLUMNS.len()));
+        let stored = stored_transactions.get(&transaction_no);
+        if let Some(stored) = stored {
+            let replaced = if stored.source == TransactionSource::App {
+                Some("was entered in the app")
+            } else if stored.external_id.as_deref() == external_id {
+                None
+            } else if stored.external_id.is_some() {
+                Some("was imported from a statement")
+            } else {
+                Some("is taken by another transaction")
+            };
+            if let Some(replaced) = replaced {
+                return Err(anyhow::anyhow!(
+                    "Transaction {} in record {} {}: number the record after the stored transactions",
+                    transaction_no,
+                    record,
+                    replaced
+                ));
             }
+        }
+        if let Some(stored) = stored
+            && stored.content_hash.as_ref() == Some(&content_hash)
+        {
+            return Ok((
+                ImportStatus::Unchanged,
+                stored.transaction.clone(),
+                content_hash,
+                stored.external_id.clone(),
+            ));
+        }
 
-            let transaction_type = TransactionType::parse_str(
-                rec.get(2)
-                    .with_context(|| missing_msg("transaction_type", i + 1))?,
-            )
-            .with_context(|| failed_to_parse_msg("transaction_type", i + 1))?;
-            let symbol = rec
-                .get(3)
-                .with_context(|| missing_msg("symbol", i + 1))?
-                .to_string();
-            let quantity = parse_decimal(
-                rec.get(4).with_context(|| missing_msg("quantity", i + 1))?,
-                "quantity",
-            )
-            .with_context(|| failed_to_parse_msg("quantity", i + 1))?;
-            let mut price = parse_decimal(
-                rec.get(5).with_context(|| missing_msg("price", i + 1))?,
-                "price",
+        let transaction_type = TransactionType::parse_str(
+            rec.get(2)
+                .with_context(|| missing_msg("transaction_type", record))?,
+        )
+        .with_context(|| failed_to_parse_msg("transaction_type", record))?;
+        let symbol = rec
+            .get(3)
+            .with_context(|| missing_msg("symbol", record))?
+            .to_string();
+        let quantity_field = rec
+            .get(4)
+            .with_context(|| missing_msg("quantity", record))?;
+        let quantity = if transaction_type == TransactionType::Split {
+            parse_split_ratio(quantity_field)
+        } else if transaction_type.is_cash() && quantity_field.trim().is_empty() {
+            // Cash transactions give the amount as price
+            Ok(dec!(1))
+        } else {
+            parse_decimal(quantity_field, "quantity")
+        }
+        .with_context(|| failed_to_parse_msg("quantity", record))?;
+        let price_field = rec.get(5).with_context(|| missing_msg("price", record))?;
+        let fees_field = rec.get(6).with_context(|| missing_msg("fees", record))?;
+        let (price, fees) = if !transaction_type.has_cash_flow() {
+            (
+                parse_optional_decimal(price_field, "price"),
+                parse_optional_decimal(fees_field, "fees"),
             )
-            .with_context(|| failed_to_parse_msg("price", i + 1))?;
-            let fees = parse_decimal(
-                rec.get(6).with_context(|| missing_msg("fees", i + 1))?,
-                "fees",
+        } else {
+            (
+                parse_decimal(price_field, "price"),
+                parse_decimal(fees_field, "fees"),
             )
-            .with_context(|| failed_to_parse_msg("fees", i + 1))?;
-            let broker = rec
-                .get(7)
-                .with_context(|| missing_msg("broker", i + 1))?
-                .to_string();
-            let alternative_symbol = rec
-                .get(8)
-                .with_context(|| missing_msg("alternative_symbol", i + 1))?
-                .to_string();
-            let mut transaction_currency = rec
-                .get(9)
-                .with_context(|| missing_msg("transaction_currency", i + 1))?
-                .to_string();
+        };
+        let mut price = price.with_context(|| failed_to_parse_msg("price", record))?;
+        let fees = fees.with_context(|| failed_to_parse_msg("fees", record))?;
+        let broker = rec
+            .get(7)
+            .with_context(|| missing_msg("broker", record))?
+            .to_string();
+        let alternative_symbol = rec
+            .get(8)
+            .with_context(|| missing_msg("alternative_symbol", record))?
+            .to_string();
+        let mut transaction_currency = rec
+            .get(9)
+            .with_context(|| missing_msg("transaction_currency", record))?
+            .to_string();
+        // Optional columns, given in the transaction currency like the price
+        let mut withholding_tax =
+            parse_optional_decimal(rec.get(10).unwrap_or_default(), "withholding_tax")
+                .with_context(|| failed_to_parse_msg("withholding_tax", record))?;
+        let mut domestic_tax =
+            parse_optional_decimal(rec.get(11).unwrap_or_default(), "domestic_tax")
+                .with_context(|| failed_to_parse_msg("domestic_tax", record))?;
+        // Units of the transaction currency per unit of the base currency, as
+        // charged by the broker
+        let mut broker_rate =
+            parse_optional_decimal(rec.get(12).unwrap_or_default(), "exchange_rate")
+                .with_context(|| failed_to_parse_msg("exchange_rate", record))?;
+        if broker_rate < Decimal::ZERO {
+            return Err(anyhow::anyhow!(
+                "Exchange rate must not be negative in record {}",
+                record
+            ));
+        }
 
+        let (ticker_id, currency) = if transaction_type.is_cash() {
+            if transaction_currency.is_empty() {
+                transaction_currency = self.base_currency.clone();
+            }
+            (0, transaction_currency.clone())
+        } else {
             let ticker_lookup_value = ticker_map.get(&symbol);
 
             let ticker_with_id = match ticker_lookup_value {
                 Some(value) => value,
                 None => {
-                    if alternative_symbol.len() > 0 {
-                        let alternative_lookup_value =
-                            ticker_map.get(&alternative_symbol).with_context(|| {
-                                format!(
-                                    "Could not find symbols {} and {}",
-                                    &symbol, &alternative_symbol
-                                )
-                            })?;
-                        alternative_lookup_value
+                    if !alternative_symbol.is_empty() {
+                        ticker_map.get(&alternative_symbol).with_context(|| {
+                            format!(
+                                "Could not find symbols {} and {}",
+                                &symbol, &alternative_symbol
+                            )
+                        })?
+                    } else if let Some(e) = lookup_errors.get(&symbol) {
+                        return Err(anyhow::anyhow!("Could not find symbol {}: {}", &symbol, e));
                     } else {
                         return Err(anyhow::anyhow!("Could not find symbol {}", &symbol));
                     }
@@ -445,142 +1465,181 @@ impl Portfolio {
             let ticker_id = ticker_with_id.clone().1;
             let currency = ticker.currency();
 
-            if transaction_currency.len() == 0 {
+            if transaction_currency.is_empty() {
                 transaction_currency = ticker.currency().clone();
             }
 
             if &transaction_currency != currency {
-                let x_rate =
-                    get_exchange_rate(currency, &transaction_currency, &date, &self.client)
-                        .await
-                        .with_context(|| {
-                            format!(
-                                "Failed to get exchange rate for {} to {} in record {}",
-                                currency,
-                                transaction_currency,
-                                i + 1
-                            )
-                        })?;
+                let x_rate = get_exchange_rate(
+                    currency,
+                    &transaction_currency,
+                    &date,
+                    forex,
+                    &self.client,
+                    &mut *connection,
+                )
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to get exchange rate for {} to {} in record {}",
+                        currency, transaction_currency, record
+                    )
+                })?;
                 price *= x_rate;
+                withholding_tax *= x_rate;
+                domestic_tax *= x_rate;
+                broker_rate *= x_rate;
             }
 
-            let existing_forex = forex_map.get(&transaction_no);
-            let exchange_rate = match existing_forex {
-                Some(existing_forex) => *existing_forex,
-                None => get_exchange_rate(currency, &self.base_currency, &date, &self.client)
-                    .await
-                    .with_context(|| {
-                        format!(
-                            "Failed to get exchange rate for {} to {} in record {}",
-                            currency,
-                            self.base_currency,
-                            i + 1
-                        )
-                    })?,
-            };
-
-            let mut transaction = Transaction::new(
-                0,
-                ticker_id,
-                transaction_no,
-                date,
-                transaction_type.clone(),
-                broker.clone(),
-                currency.clone(),
-                exchange_rate,
-                quantity,
-                price,
-                fees,
-                None,
-                None,
-            );
-
-            let mut amounts = Vec::new();
-            let mut quantities = Vec::new();
+            (ticker_id, currency.clone())
+        };
 
-            for t in hist_transactions.iter().filter(|t| {
-                t.ticker_id() == ticker.id()
-                    && (*t.transaction_type() == TransactionType::Buy
-                        || *t.transaction_type() == TransactionType::Sell)
-                    && t.broker() == &broker
-            }) {
-                amounts.push(t.get_amount());
-                quantities.push(t.get_quantity());
+        let exchange_rate = match stored {
+            _ if broker_rate > Decimal::ZERO && currency != self.base_currency => broker_rate,
+            Some(stored)
+                if stored.transaction.currency() == &currency
+                    && stored.transaction.date() == &date =>
+            {
+                *stored.transaction.exchange_rate()
             }
+            _ => get_exchange_rate(
+                &currency,
+                &self.base_currency,
+                &date,
+                forex,
+                &self.client,
+                &mut *connection,
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to get exchange rate for {} to {} in record {}",
+                    currency, self.base_currency, record
+                )
+            })?,
+        };
+
+        let mut transaction = Transaction::new(
+            stored.map_or(0, |stored| *stored.transaction.id()),
+            ticker_id,
+            transaction_no,
+            date,
+            transaction_type.clone(),
+            broker.clone(),
+            currency,
+            exchange_rate,
+            quantity,
+            price,
+            fees,
+            withholding_tax,
+            domestic_tax,
+            None,
+            None,
+        );
+        // Replaced by the chain replay for all but cash transactions
+        let position_state = PositionState::new(Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+        let transaction_gains = calculate_transaction_gains(&transaction, &position_state);
+        transaction.set_position_state(Some(position_state));
+        transaction.set_transaction_gains(Some(transaction_gains));
+
+        let status = match stored {
+            Some(_) => ImportStatus::Changed,
+            None => ImportStatus::New,
+        };
+        Ok((
+            status,
+            transaction,
+            content_hash,
+            external_id.map(String::from),
+        ))
+    }
 
-            amounts.push(transaction.get_amount());
-            quantities.push(transaction.get_quantity());
+    pub async fn update_exchange_rates(&mut self) -> Result<()> {
+        let currency_result =
+            sqlx::query("SELECT currency FROM tickers UNION SELECT currency FROM transactions")
+                .fetch_all(&self.connection)
+                .await?;
 
-            let position_state =
-                calculate_position_state(amounts, quantities).with_context(|| {
-                    format!("Failed to calculate position state in record {}", i + 1)
-                })?;
-            let transaction_gains = calculate_transaction_gains(&transaction, &position_state);
+        // Current rates are not written to the cache, so they are kept for
+        // the rest of the day and only the first refresh of a day hits the
+        // forex provider
+        let forex = self.providers.forex();
+        let now = Local::now();
+        let mut connection = self.connection.acquire().await?;
+        for row in currency_result.iter() {
+            let Ok(currency) = parse_string_from_row(row, "currency") else {
+                continue;
+            };
+            if self.forex_map_dates.get(&currency) == Some(&now.date_naive()) {
+                continue;
+            }
+            if let Ok(exchange_rate) = get_exchange_rate(
+                &currency,
+                &self.base_currency,
+                &now,
+                forex.as_ref(),
+                &self.client,
+                &mut connection,
+            )
+            .await
+            {
+                self.forex_map.insert(currency.clone(), exchange_rate);
+                self.forex_map_dates.insert(currency, now.date_naive());
+            }
+        }
 
-            transaction.set_position_state(Some(position_state));
-            transaction.set_transaction_gains(Some(transaction_gains));
+        Ok(())
+    }
 
-            insert_transaction(&transaction, &ticker_id, &mut tx)
-                .await
-                .with_context(|| format!("Failed to insert transaction in record {}", i + 1))?;
+    /// Loads ECB reference rates from a CSV or XML file into the exchange
+    /// rate cache. Returns the number of rates stored.
+    pub async fn load_fx_rates(&self, path: &str) -> Result<usize> {
+        let rates = parse_ecb_file(path)?;
 
-            transactions.push(transaction);
+        let mut tx = self.connection.begin().await?;
+        for rate in rates.iter() {
+            insert_fx_rate(rate, &mut tx).await?;
         }
-
         tx.commit()
             .await
             .with_context(|| "Failed to commit database transaction")?;
 
-        Ok(())
+        Ok(rates.len())
     }
 
-    pub async fn update_exchange_rates(&mut self) -> Result<()> {
-        let currency_result = sqlx::query("SELECT DISTINCT currency FROM tickers")
-            .fetch_all(&self.connection)
-            .await?;
-
-        let mut handles = Vec::new();
-        for row in currency_result.iter() {
-            let base_currency = self.base_currency.clone();
-            let client = self.client.clone();
-            let currency = parse_string_from_row(row, "currency").ok();
-
-            let handle = tokio::spawn(async move {
-                let exchange_rate = match currency {
-                    Some(ref currency) => {
-                        get_exchange_rate(&currency, &base_currency, &Local::now(), &client)
-                            .await
-                            .ok()
-                    }
-                    None => None,
-                };
-                Ok::<(Option<String>, Option<Decimal>), anyhow::Error>((currency, exchange_rate))
-            });
-            handles.push(handle);
-        }
+    /// Looks up the symbols missing from `existing_tickers` with `api` and adds
+    /// them. Returns why a symbol could not be found, by symbol.
+    pub async fn update_tickers(
+        &self,
+        symbols: &Vec<String>,
+        existing_tickers: &mut HashMap<String, (Ticker, i64)>,
+        api: &ApiProvider,
+    ) -> Result<HashMap<String, String>> {
+        let (found_tickers, lookup_errors) =
+            self.lookup_tickers(symbols, existing_tickers, api).await?;
 
-        for handle in handles {
-            match handle.await? {
-                Ok((currency, exchange_rate)) => {
-                    if let Some(currency) = currency
-                        && let Some(exchange_rate) = exchange_rate
-                    {
-                        self.forex_map.insert(currency, exchange_rate);
-                    }
-                }
-                Err(e) => return Err(e),
-            }
+        // SQLite allows a single writer, so the lookups run concurrently but the
+        // inserts share one transaction.
+        let mut tx = self.connection.begin().await?;
+        for (symbol, ticker, asset) in found_tickers {
+            let ticker_id = insert_ticker(&ticker, &asset, &mut tx).await?;
+            add_ticker(existing_tickers, symbol, ticker, &asset, ticker_id);
         }
+        tx.commit().await?;
 
-        Ok(())
+        Ok(lookup_errors)
     }
 
-    pub async fn update_tickers(
+    /// Looks up the symbols missing from `existing_tickers` with `api` without
+    /// storing them. Returns the tickers found with their assets, and why a
+    /// symbol could not be found, by symbol.
+    async fn lookup_tickers(
         &self,
         symbols: &Vec<String>,
-        existing_tickers: &mut HashMap<String, (Ticker, i64)>,
+        existing_tickers: &HashMap<String, (Ticker, i64)>,
         api: &ApiProvider,
-    ) -> Result<HashMap<String, (Ticker, i64)>> {
+    ) -> Result<(Vec<(String, Ticker, Asset)>, HashMap<String, String>)> {
+        let provider = self.providers.get(api)?;
         let mut handles = Vec::new();
         for symbol in symbols {
             let found_ticker = existing_tickers.get(symbol);
@@ -590,80 +1649,67 @@ impl Portfolio {
 
             let symbol_clone = symbol.clone();
             let client = self.client.clone();
-            let connection = self.connection.clone();
 
-            let provider = api.clone();
+            let provider = provider.clone();
 
             let handle = tokio::spawn(async move {
-                let ticker = find_ticker(&symbol_clone, &client, &provider).await?;
-                let asset = Asset::new(
-                    0,
-                    ticker.name().to_string(),
-                    AssetType::Stock,
-                    None,
-                    None,
-                    None,
-                );
-
-                let mut tx = connection.begin().await?;
-                let new_ticker_id = insert_ticker(&ticker, &asset, &mut tx).await?;
-                tx.commit().await?;
+                let ticker = provider.search_symbol(&symbol_clone, &client).await?;
+                let asset = match provider.get_metadata(ticker.symbol(), &client).await {
+                    Ok(asset) => asset,
+                    Err(_) => Asset::new(
+                        0,
+                        ticker.name().to_string(),
+                        AssetType::Stock,
+                        None,
+                        None,
+                        None,
+                    ),
+                };
 
-                Ok::<(String, Ticker, i64), anyhow::Error>((symbol_clone, ticker, new_ticker_id))
+                Ok::<(String, Ticker, Asset), anyhow::Error>((symbol_clone, ticker, asset))
             });
-            handles.push(handle);
+            handles.push((symbol.clone(), handle));
         }
 
-        for handle in handles {
+        let mut found_tickers = Vec::new();
+        let mut lookup_errors = HashMap::new();
+        for (symbol, handle) in handles {
             match handle.await? {
-                Ok((symbol, ticker, ticker_id)) => {
-                    existing_tickers.insert(symbol, (ticker, ticker_id));
+                Ok(found_ticker) => found_tickers.push(found_ticker),
+                Err(e) => {
+                    lookup_errors.insert(symbol, format!("{:#}", e));
                 }
-                Err(e) => return Err(e),
             }
         }
 
-        Ok(existing_tickers.clone())
+        Ok((found_tickers, lookup_errors))
     }
 
+    /// Refreshes `last_price` of every ticker and records the quote in the
+    /// price history.
     pub async fn update_prices(&self) -> Result<()> {
-        let tickers = sqlx::query("SELECT symbol, api FROM tickers")
+        let tickers = sqlx::query("SELECT id, symbol, api FROM tickers")
             .fetch_all(&self.connection)
             .await?;
 
         let mut ticker_data = Vec::new();
         for row in tickers {
+            let ticker_id = parse_i64_from_row(&row, "id")?;
             let symbol = parse_string_from_row(&row, "symbol")?;
             let api_str = parse_string_from_row(&row, "api")?;
-            let api = ApiProvider::parse_str(&api_str)?;
-            ticker_data.push((symbol, api));
+            let api = self.providers.parse_api(&api_str)?;
+            let provider = self.providers.get(&api)?;
+            ticker_data.push((ticker_id, symbol, provider));
         }
 
         let mut handles = Vec::new();
-        for (symbol, api) in ticker_data {
+        for (ticker_id, symbol, provider) in ticker_data {
             let client = self.client.clone();
-            let connection = self.connection.clone();
 
             let handle = tokio::spawn(async move {
-                let price_result = get_latest_price(&symbol, &client, &api).await;
-                match price_result {
-                    Ok(price) => {
-                        sqlx::query(
-                            r#"
-                            UPDATE tickers
-                            SET
-                                last_price = ?,
-                                last_price_updated_at = DATETIME('now'),
-                                updated_at = DATETIME('now')
-                            WHERE symbol = ?
-                            "#,
-                        )
-                        .bind(price.to_f64())
-                        .bind(&symbol)
-                        .execute(&connection)
-                        .await?;
-                        Ok(())
-                    }
+                let quote_result = provider.get_latest_quote(&symbol, &client).await;
+                match quote_result {
+                    Ok(quote) => Ok((ticker_id, provider.api(), quote)),
                     Err(e) => Err(anyhow::anyhow!(
                         "Failed to fetch price for {}: {}",
                         symbol,
@@ -674,18 +1720,559 @@ impl Portfolio {
             handles.push(handle);
         }
 
+        let mut quotes = Vec::new();
         let mut errors = Vec::new();
         for handle in handles {
             match handle.await? {
-                Ok(()) => {}
+                Ok(quote) => quotes.push(quote),
                 Err(e) => errors.push(format!("{:#}", e)),
             }
         }
 
+        let mut tx = self.connection.begin().await?;
+        for (ticker_id, api, quote) in quotes {
+            sqlx::query(
+                r#"
+                UPDATE tickers
+                SET
+                    last_price = ?,
+                    last_price_updated_at = DATETIME('now'),
+                    updated_at = DATETIME('now')
+                WHERE id = ?
+                "#,
+            )
+            .bind(decimal_to_db(quote.close()))
+            .bind(ticker_id)
+            .execute(&mut *tx)
+            .await?;
+            insert_price(ticker_id, &quote, api.to_str(), &mut tx).await?;
+        }
+        tx.commit().await?;
+
         if !errors.is_empty() {
             return Err(anyhow::anyhow!("\n{}", errors.join("\n")));
         }
 
         Ok(())
     }
+
+    /// Downloads the daily closes of every traded ticker that are missing
+    /// between its first transaction and today. Returns the number of
+    /// quotes stored.
+    pub async fn backfill_prices(&self) -> Result<usize> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                tcr.id,
+                tcr.symbol,
+                tcr.api,
+                MIN(tnx.transaction_date) AS first_date
+            FROM
+                tickers tcr
+            INNER JOIN
+                transactions tnx
+                ON tnx.ticker_id = tcr.id
+            GROUP BY
+                tcr.id
+            "#,
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        let today = Local::now().date_naive();
+        let mut handles = Vec::new();
+        for row in rows {
+            let ticker_id = parse_i64_from_row(&row, "id")?;
+            let symbol = parse_string_from_row(&row, "symbol")?;
+            let api = self
+                .providers
+                .parse_api(&parse_string_from_row(&row, "api")?)?;
+            let first_date = parse_datetime_from_row(&row, "first_date")?.date_naive();
+            let provider = self.providers.get(&api)?;
+
+            let covered = get_price_range(&self.connection, ticker_id).await?;
+            for (start, end) in uncovered_ranges(first_date, today, covered) {
+                let (Some(start), Some(end)) = (local_midnight(start), local_midnight(end)) else {
+                    continue;
+                };
+                let symbol = symbol.clone();
+                let provider = provider.clone();
+                let client = self.client.clone();
+
+                let handle = tokio::spawn(async move {
+                    provider
+                        .get_quote_history(&symbol, &start, &end, &client)
+                        .await
+                        .map(|quotes| (ticker_id, provider.api(), quotes))
+                        .with_context(|| format!("Failed to fetch price history for {}", symbol))
+                });
+                handles.push(handle);
+            }
+        }
+
+        let mut histories = Vec::new();
+        let mut errors = Vec::new();
+        for handle in handles {
+            match handle.await? {
+                Ok(history) => histories.push(history),
+                Err(e) => errors.push(format!("{:#}", e)),
+            }
+        }
+
+        let mut count = 0;
+        let mut tx = self.connection.begin().await?;
+        for (ticker_id, api, quotes) in histories {
+            for quote in quotes.iter() {
+                insert_price(ticker_id, quote, api.to_str(), &mut tx).await?;
+            }
+            count += quotes.len();
+        }
+        tx.commit().await?;
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!("\n{}", errors.join("\n")));
+        }
+
+        Ok(count)
+    }
+
+    /// Time- and money-weighted returns of the securities (cash accounts
+    /// excluded) over `period`, valued with historical quotes where the
+    /// provider has them and the last transaction price otherwise.
+    pub async fn get_performance_report(&self, period: ReturnPeriod) -> Result<PerformanceReport> {
+        let holdings = self.get_holdings().await?;
+
+        let Some(inception) = holdings
+            .values()
+            .flat_map(|(_, _, _, _, transactions)| transactions.iter())
+            .map(|t| t.date().date_naive())
+            .min()
+        else {
+            return Ok(PerformanceReport::new(
+                period,
+                Performance::default(),
+                BTreeMap::new(),
+            ));
+        };
+        let today = Local::now().date_naive();
+        let start = period.start_date(today, inception);
+
+        // Every holding is valued on the same dates so the values add up
+        let mut dates = vec![start, today];
+        dates.extend(
+            holdings
+                .values()
+                .flat_map(|(_, _, _, _, transactions)| transactions.iter())
+                .map(|t| t.date().date_naive())
+                .filter(|date| *date > start && *date <= today),
+        );
+        dates.sort();
+        dates.dedup();
+
+        let mut exchange_rates = HashMap::new();
+        let mut connection = self.connection.acquire().await?;
+        let mut portfolio_points: Vec<PerformancePoint> = dates
+            .iter()
+            .map(|date| PerformancePoint::new(*date, Decimal::ZERO, Decimal::ZERO))
+            .collect();
+        let mut by_position = BTreeMap::new();
+
+        for (ticker_id, (_, symbol, api, name, transactions)) in holdings.iter() {
+            let currency = transactions[0].currency();
+            let units = calculate_units_by_date(transactions);
+            let prices = self
+                .get_price_series(*ticker_id, symbol, api, transactions, start, today)
+                .await?;
+
+            let mut points = Vec::new();
+            for date in dates.iter() {
+                let units = value_on_or_before(&units, *date).unwrap_or_default();
+                let value = if units == Decimal::ZERO {
+                    Decimal::ZERO
+                } else {
+                    let price = value_on_or_before(&prices, *date).unwrap_or_default();
+                    let exchange_rate = self
+                        .get_historical_rate(
+                            currency,
+                            *date,
+                            transactions,
+                            &mut exchange_rates,
+                            &mut connection,
+                        )
+                        .await?;
+                    units * price / exchange_rate
+                };
+                let cash_flow: Decimal = if *date > start {
+                    transactions
+                        .iter()
+                        .filter(|t| t.date().date_naive() == *date)
+                        .map(|t| t.get_amount())
+                        .sum()
+                } else {
+                    Decimal::ZERO
+                };
+                points.push(PerformancePoint::new(*date, value, cash_flow));
+            }
+
+            for (portfolio_point, point) in portfolio_points.iter_mut().zip(points.iter()) {
+                *portfolio_point = PerformancePoint::new(
+                    *point.date(),
+                    portfolio_point.value() + point.value(),
+                    portfolio_point.cash_flow() + point.cash_flow(),
+                );
+            }
+
+            // Positions closed before the period have nothing to report
+            if points.iter().all(|p| *p.value() == Decimal::ZERO) {
+                continue;
+            }
+            by_position.insert(
+                name.clone(),
+                Performance::new(calculate_twr(&points), calculate_xirr(&points)),
+            );
+        }
+        let points = portfolio_points;
+
+        Ok(PerformanceReport::new(
+            period,
+            Performance::new(calculate_twr(&points), calculate_xirr(&points)),
+            by_position,
+        ))
+    }
+
+    /// Security transactions grouped by ticker id, with the symbol, API and
+    /// asset name of the ticker.
+    async fn get_holdings(&self) -> Result<Holdings> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                tnx.*,
+                tcr.asset_id,
+                tcr.symbol,
+                tcr.api,
+                ast.name
+            FROM
+                transactions tnx
+            INNER JOIN
+                tickers tcr
+                ON tnx.ticker_id = tcr.id
+            INNER JOIN
+                assets ast
+                ON tcr.asset_id = ast.id
+            ORDER BY
+                tnx.transaction_date ASC,
+                tnx.transaction_no ASC
+            "#,
+        )
+        .fetch_all(&self.connection)
+        .await?;
+
+        let mut holdings: Holdings = BTreeMap::new();
+        for row in rows {
+            let asset_id = parse_i64_from_row(&row, "asset_id")?;
+            let symbol = parse_string_from_row(&row, "symbol")?;
+            let api = parse_string_from_row(&row, "api")?;
+            let name = parse_string_from_row(&row, "name")?;
+            let transaction = parse_transaction(row)?;
+            holdings
+                .entry(*transaction.ticker_id())
+                .or_insert_with(|| (asset_id, symbol, api, name, Vec::new()))
+                .4
+                .push(transaction);
+        }
+
+        Ok(holdings)
+    }
+
+    /// Daily market value and cost basis of the securities (cash accounts
+    /// excluded) over `period`, by asset. Daily closes come from the stored
+    /// price history and, for the dates before and after it, from the
+    /// provider; exchange rates come from the cache. Transaction prices and
+    /// rates fill the remaining gaps.
+    pub async fn get_value_history(&self, period: ReturnPeriod) -> Result<ValueHistory> {
+        let holdings = self.get_holdings().await?;
+
+        let Some(inception) = holdings
+            .values()
+            .flat_map(|(_, _, _, _, transactions)| transactions.iter())
+            .map(|t| t.date().date_naive())
+            .min()
+        else {
+            return Ok(ValueHistory::new(period, Vec::new(), BTreeMap::new()));
+        };
+        let today = Local::now().date_naive();
+        let start = period.start_date(today, inception);
+        let dates: Vec<NaiveDate> = start.iter_days().take_while(|d| *d <= today).collect();
+
+        let zero_points: Vec<ValuePoint> = dates
+            .iter()
+            .map(|date| ValuePoint::new(*date, Decimal::ZERO, Decimal::ZERO))
+            .collect();
+        let mut portfolio_points = zero_points.clone();
+        // Tickers of the same asset, listed at different exchanges, add up
+        let mut by_asset: BTreeMap<i64, (&String, Vec<ValuePoint>)> = BTreeMap::new();
+
+        for (ticker_id, (asset_id, symbol, api, name, transactions)) in holdings.iter() {
+            let currency = transactions[0].currency();
+            let units = calculate_units_by_date(transactions);
+            let cost = calculate_cost_by_date(transactions);
+            let prices = self
+                .get_price_series(*ticker_id, symbol, api, transactions, start, today)
+                .await?;
+            let exchange_rates = self
+                .get_exchange_rate_series(currency, transactions, start, today)
+                .await?;
+
+            let points: Vec<ValuePoint> = dates
+                .iter()
+                .map(|date| {
+                    let units = value_on_or_before(&units, *date).unwrap_or_default();
+                    let price = value_on_or_before(&prices, *date).unwrap_or_default();
+                    let exchange_rate =
+                        value_on_or_before(&exchange_rates, *date).unwrap_or(Decimal::ONE);
+                    let market_value = if exchange_rate == Decimal::ZERO {
+                        Decimal::ZERO
+                    } else {
+                        units * price / exchange_rate
+                    };
+                    let invested = value_on_or_before(&cost, *date).unwrap_or_default();
+                    ValuePoint::new(*date, market_value, invested)
+                })
+                .collect();
+
+            add_value_points(&mut portfolio_points, &points);
+            let (_, asset_points) = by_asset
+                .entry(*asset_id)
+                .or_insert_with(|| (name, zero_points.clone()));
+            add_value_points(asset_points, &points);
+        }
+
+        let by_position = by_asset
+            .into_iter()
+            .filter(|(_, (_, points))| {
+                !points
+                    .iter()
+                    .all(|p| *p.market_value() == Decimal::ZERO && *p.invested() == Decimal::ZERO)
+            })
+            .map(|(asset_id, (name, points))| {
+                (asset_id, PositionHistory::new(name.clone(), points))
+            })
+            .collect();
+
+        Ok(ValueHistory::new(period, portfolio_points, by_position))
+    }
+
+    /// Daily closes in the ticker currency, read from the stored price
+    /// history and, for the dates before and after it, from the provider. Buy
+    /// and sell prices fill in the remaining gaps.
+    async fn get_price_series(
+        &self,
+        ticker_id: i64,
+        symbol: &str,
+        api: &str,
+        transactions: &[Transaction],
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, Decimal>> {
+        let mut prices: BTreeMap<NaiveDate, Decimal> = transactions
+            .iter()
+            .filter(|t| {
+                matches!(
+                    t.transaction_type(),
+                    TransactionType::Buy | TransactionType::Sell
+                )
+            })
+            .map(|t| (t.date().date_naive(), *t.price()))
+            .collect();
+
+        let stored = get_prices(&self.connection, ticker_id, start, end).await?;
+        let covered = stored
+            .keys()
+            .next()
+            .copied()
+            .zip(stored.keys().next_back().copied());
+
+        let provider = self
+            .providers
+            .parse_api(api)
+            .and_then(|api| self.providers.get(&api));
+        if let Ok(provider) = provider {
+            for (start, end) in uncovered_ranges(start, end, covered) {
+                if let (Some(start), Some(end)) = (local_midnight(start), local_midnight(end))
+                    && let Ok(quotes) = provider
+                        .get_quote_history(symbol, &start, &end, &self.client)
+                        .await
+                {
+                    prices.extend(quotes.iter().map(|q| (q.date().date_naive(), *q.close())));
+                }
+            }
+        }
+        prices.extend(stored);
+
+        Ok(prices)
+    }
+
+    /// Units of `currency` per unit of the base currency by date, from the
+    /// exchange rate cache, the transactions and the current rate.
+    async fn get_exchange_rate_series(
+        &self,
+        currency: &str,
+        transactions: &[Transaction],
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<BTreeMap<NaiveDate, Decimal>> {
+        if currency == self.base_currency {
+            return Ok(BTreeMap::from([(start, Decimal::ONE)]));
+        }
+
+        let mut rates: BTreeMap<NaiveDate, Decimal> = transactions
+            .iter()
+            .map(|t| (t.date().date_naive(), *t.exchange_rate()))
+            .collect();
+        let earliest = rates.keys().next().copied().unwrap_or(start).min(start);
+        rates.extend(
+            get_fx_rate_series(
+                &self.connection,
+                &self.base_currency,
+                currency,
+                earliest,
+                end,
+            )
+            .await?,
+        );
+        if let Some(rate) = self.forex_map.get(currency) {
+            rates.insert(end, *rate);
+        }
+
+        Ok(rates)
+    }
+
+    /// Units of `currency` per unit of the base currency on `date`, falling
+    /// back to the rate of the latest transaction when the provider fails.
+    async fn get_historical_rate(
+        &self,
+        currency: &str,
+        date: NaiveDate,
+        transactions: &[Transaction],
+        cache: &mut HashMap<(String, NaiveDate), Decimal>,
+        connection: &mut SqliteConnection,
+    ) -> Result<Decimal> {
+        let key = (currency.to_string(), date);
+        if let Some(rate) = cache.get(&key) {
+            return Ok(*rate);
+        }
+
+        let forex = self.providers.forex();
+        let datetime = local_midnight(date).unwrap_or_else(Local::now);
+        let rate = match get_exchange_rate(
+            currency,
+            &self.base_currency,
+            &datetime,
+            forex.as_ref(),
+            &self.client,
+            connection,
+        )
+        .await
+        {
+            Ok(rate) => rate,
+            Err(e) => transactions
+                .iter()
+                .rfind(|t| t.date().date_naive() <= date)
+                .or(transactions.first())
+                .map(|t| *t.exchange_rate())
+                .with_context(|| {
+                    format!(
+                        "Failed to get exchange rate for {} on {}: {}",
+                        currency, date, e
+                    )
+                })?,
+        };
+
+        cache.insert(key, rate);
+        Ok(rate)
+    }
+}
+
+/// Adds the values of `points` to those of the same dates in `total`.
+fn add_value_points(total: &mut [ValuePoint], points: &[ValuePoint]) {
+    for (total_point, point) in total.iter_mut().zip(points.iter()) {
+        *total_point = ValuePoint::new(
+            *point.date(),
+            total_point.market_value() + point.market_value(),
+            total_point.invested() + point.invested(),
+        );
+    }
+}
+
+/// Adds a ticker under its symbol and, unless another ticker is known by it,
+/// under the ISIN of its asset.
+fn add_ticker(
+    tickers: &mut HashMap<String, (Ticker, i64)>,
+    symbol: String,
+    ticker: Ticker,
+    asset: &Asset,
+    ticker_id: i64,
+) {
+    if let Some(isin) = asset.isin().as_ref().filter(|isin| !isin.is_empty()) {
+        tickers
+            .entry(isin.clone())
+            .or_insert_with(|| (ticker.clone(), ticker_id));
+    }
+    tickers.insert(symbol, (ticker, ticker_id));
+}
+
+fn local_midnight(date: NaiveDate) -> Option<DateTime<Local>> {
+    Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+}
+
+/// Returns the records of the files to import by file name.
+fn read_import_files(
+    paths: &[String],
+    base_currency: &str,
+    profiles: &BTreeMap<String, ImportProfile>,
+) -> Result<Vec<(String, Vec<Result<StringRecord>>)>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let records = read_import_file(path, base_currency, profiles)?;
+        let file_name = Path::new(path)
+            .file_name()
+            .map_or(path.clone(), |name| name.to_string_lossy().to_string());
+        files.push((file_name, records));
+    }
+
+    Ok(files)
+}
+
+/// Units and cost of the last transaction per ticker and broker.
+fn broker_positions<'a>(
+    transactions: impl Iterator<Item = &'a Transaction>,
+) -> BTreeMap<(i64, String), (Decimal, Decimal)> {
+    let mut last: BTreeMap<(i64, String), &Transaction> = BTreeMap::new();
+    for transaction in transactions {
+        if transaction.transaction_type().is_cash() {
+            continue;
+        }
+        let key = (*transaction.ticker_id(), transaction.broker().clone());
+        if last.get(&key).is_none_or(|previous| {
+            (previous.date(), previous.transaction_no())
+                < (transaction.date(), transaction.transaction_no())
+        }) {
+            last.insert(key, transaction);
+        }
+    }
+
+    last.into_iter()
+        .filter_map(|(key, transaction)| {
+            let position_state = transaction.position_state().as_ref()?;
+            Some((
+                key,
+                (
+                    *position_state.cumulative_units(),
+                    *position_state.cumulative_cost(),
+                ),
+            ))
+        })
+        .collect()
 }
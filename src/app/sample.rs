@@ -0,0 +1,45 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+
+pub const SAMPLE_TRANSACTIONS: &str = include_str!("../../sample_data/transactions.csv");
+
+const SAMPLE_FIXTURES: [(&str, &str); 3] = [
+    (
+        "symbols.csv",
+        include_str!("../../sample_data/fixtures/symbols.csv"),
+    ),
+    (
+        "quotes.csv",
+        include_str!("../../sample_data/fixtures/quotes.csv"),
+    ),
+    (
+        "forex.csv",
+        include_str!("../../sample_data/fixtures/forex.csv"),
+    ),
+];
+
+fn write_file(path: &Path, content: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+    }
+    fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn write_sample_transactions(path: &Path) -> Result<()> {
+    if fs::exists(path)? {
+        return Ok(());
+    }
+    write_file(path, SAMPLE_TRANSACTIONS)
+}
+
+pub fn write_sample_fixtures(dir: &Path) -> Result<()> {
+    for (file, content) in SAMPLE_FIXTURES {
+        let path = dir.join(file);
+        if !fs::exists(&path)? {
+            write_file(&path, content)?;
+        }
+    }
+    Ok(())
+}
@@ -1,15 +1,64 @@
+use derive_new::new;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
+    symbols::Marker,
+    text::Span,
     widgets::{
-        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState,
+        Axis, Block, Borders, Cell, Chart, Clear, Dataset, GraphType, List, ListItem, ListState,
+        Paragraph, Row, Table, TableState, Tabs,
     },
 };
-use rust_decimal::Decimal;
-use strum::IntoEnumIterator;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
 
-use crate::{app::portfolio::Portfolio, models::ticker::ApiProvider};
+use crate::{
+    app::{
+        form::{FORM_FIELDS, TransactionForm},
+        portfolio::Portfolio,
+    },
+    models::{
+        DividendBreakdown, DividendReport, ImportPreview, ImportStatus, LedgerEntry, LedgerFilter,
+        LedgerPage, LedgerSort, Performance, PerformanceReport, PositionDetail, PositionHistory,
+        ReturnPeriod, TransactionType, ValueHistory, ValuePoint,
+    },
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Tab {
+    #[default]
+    Positions,
+    History,
+    Transactions,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 3] = [Tab::Positions, Tab::History, Tab::Transactions];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Tab::Positions => "Positions",
+            Tab::History => "History",
+            Tab::Transactions => "Transactions",
+        }
+    }
+
+    pub fn next(&self) -> Tab {
+        let i = Self::ALL.iter().position(|t| t == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// What the transactions tab shows; `filter_input` is the filter being
+/// edited, if any.
+#[derive(new)]
+pub struct LedgerView<'a> {
+    page: &'a LedgerPage,
+    filter: &'a LedgerFilter,
+    sort: LedgerSort,
+    descending: bool,
+    filter_input: Option<&'a str>,
+}
 
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
@@ -47,23 +96,31 @@ fn format_colored_percentage(value: Decimal) -> (String, Color) {
     (format!("{:.2}%", value.abs()), gain_color(value))
 }
 
-fn render_title(frame: &mut Frame, portfolio: &Portfolio, area: Rect) {
-    let title = Paragraph::new(format!(
-        "Portfolio Tracker (default API: {})",
-        portfolio.default_api().to_str()
-    ))
-    .style(Style::default().fg(Color::Cyan))
-    .block(Block::default().borders(Borders::ALL));
+fn render_title(frame: &mut Frame, portfolio: &Portfolio, tab: Tab, area: Rect) {
+    let selected = Tab::ALL.iter().position(|t| *t == tab).unwrap_or(0);
+    let title = Tabs::new(Tab::ALL.iter().map(|t| t.title()))
+        .select(selected)
+        .style(Style::default().fg(Color::Cyan))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Portfolio Tracker (default API: {})",
+            portfolio.default_api().to_str()
+        )));
 
     frame.render_widget(title, area);
 }
 
 fn render_footer(frame: &mut Frame, area: Rect) {
     let footer = Paragraph::new(concat!(
+        "Enter: Details | ",
         "F4: Import Transactions | ",
         "F5: Update Prices | ",
+        "F6: Dividends | ",
+        "F7: Returns | ",
         "F8: Change default API | ",
+        "F9: Export | ",
         "F12: Reset | ",
+        "Tab: Switch View | ",
         "Q: Quit",
     ))
     .style(Style::default().fg(Color::Yellow))
@@ -154,6 +211,286 @@ fn render_positions_table(
     frame.render_stateful_widget(table, area, table_state);
 }
 
+fn period_titles(selected: &ReturnPeriod) -> String {
+    ReturnPeriod::ALL
+        .iter()
+        .map(|period| {
+            if period == selected {
+                format!("[{}]", period.to_str())
+            } else {
+                period.to_str().to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("  ")
+}
+
+fn chart_data(points: &[ValuePoint], value: fn(&ValuePoint) -> &Decimal) -> Vec<(f64, f64)> {
+    let Some(first) = points.first() else {
+        return Vec::new();
+    };
+    points
+        .iter()
+        .map(|point| {
+            let x = (*point.date() - *first.date()).num_days() as f64;
+            (x, value(point).to_f64().unwrap_or_default())
+        })
+        .collect()
+}
+
+fn render_history_chart(
+    frame: &mut Frame,
+    history: &ValueHistory,
+    overlay: Option<&PositionHistory>,
+    area: Rect,
+) {
+    let points = history.portfolio();
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        let empty_message =
+            Paragraph::new("No history to display. Press F4 to import transactions.")
+                .style(Style::default().fg(Color::Yellow))
+                .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(empty_message, area);
+        return;
+    };
+
+    let value_data = chart_data(points, ValuePoint::market_value);
+    let invested_data = chart_data(points, ValuePoint::invested);
+    let overlay_data = overlay
+        .map(|position| chart_data(position.points(), ValuePoint::market_value))
+        .unwrap_or_default();
+
+    let mut datasets = vec![
+        Dataset::default()
+            .name("Market value")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Cyan))
+            .data(&value_data),
+        Dataset::default()
+            .name("Invested")
+            .marker(Marker::Braille)
+            .graph_type(GraphType::Line)
+            .style(Style::default().fg(Color::Yellow))
+            .data(&invested_data),
+    ];
+    if let Some(position) = overlay {
+        datasets.push(
+            Dataset::default()
+                .name(position.name().clone())
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Magenta))
+                .data(&overlay_data),
+        );
+    }
+
+    let max_x = (*last.date() - *first.date()).num_days().max(1) as f64;
+    let middle = *first.date() + chrono::Days::new((max_x / 2.0) as u64);
+    let (min_y, max_y) = value_data
+        .iter()
+        .chain(invested_data.iter())
+        .chain(overlay_data.iter())
+        .fold((0.0_f64, 0.0_f64), |(min, max), (_, y)| {
+            (min.min(*y), max.max(*y))
+        });
+    let max_y = if max_y > min_y {
+        max_y * 1.05
+    } else {
+        min_y + 1.0
+    };
+
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .title(format!(
+                    "Value history: {} (Left/Right: range, Up/Down: position)",
+                    period_titles(history.period())
+                ))
+                .borders(Borders::ALL),
+        )
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, max_x])
+                .labels([
+                    Span::from(first.date().to_string()),
+                    Span::from(middle.to_string()),
+                    Span::from(last.date().to_string()),
+                ]),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([min_y, max_y])
+                .labels([
+                    Span::from(format!("{:.0}", min_y)),
+                    Span::from(format!("{:.0}", (min_y + max_y) / 2.0)),
+                    Span::from(format!("{:.0}", max_y)),
+                ]),
+        );
+
+    frame.render_widget(chart, area);
+}
+
+fn render_ledger(
+    frame: &mut Frame,
+    ledger: &LedgerView,
+    ledger_state: &mut TableState,
+    area: Rect,
+) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // Filter
+            Constraint::Min(0),    // Table
+        ])
+        .split(area);
+
+    let filter = match ledger.filter_input {
+        Some(input) => Paragraph::new(format!(
+            " Filter: {}_  (symbol: broker: type: from: to:, Enter to apply, Esc to cancel)",
+            input
+        ))
+        .style(Style::default().fg(Color::Yellow)),
+        None => Paragraph::new(format!(
+            " Filter: {}  (/: edit, c: clear, s: sort, d: direction, Left/Right: page, a: add, e: edit, x: delete)",
+            match ledger.filter.to_str() {
+                filter if filter.is_empty() => String::from("none"),
+                filter => filter,
+            }
+        ))
+        .style(Style::default().fg(Color::Gray)),
+    };
+    frame.render_widget(filter, chunks[0]);
+
+    let page = ledger.page;
+    let rows = page.entries().iter().map(|entry| {
+        let transaction = entry.transaction();
+        let (realized_gain, dividend) = transaction
+            .transaction_gains()
+            .as_ref()
+            .map(|gains| (*gains.realized_gain(), *gains.dividend()))
+            .unwrap_or_default();
+        let (realized_gain_str, color_realized) = format_colored_gain(realized_gain);
+        Row::new([
+            Cell::from(transaction.date().format("%Y-%m-%d").to_string()),
+            Cell::from(transaction.transaction_type().to_str().to_string()),
+            Cell::from(entry.symbol().clone()),
+            Cell::from(transaction.broker().clone()),
+            Cell::from(format!("{:.4}", transaction.quantity())),
+            Cell::from(format!("{:.2}", transaction.price())),
+            Cell::from(format!("{:.2}", transaction.fees())),
+            Cell::from(format!("{:.4}", transaction.exchange_rate())),
+            Cell::from(realized_gain_str).style(Style::default().fg(color_realized)),
+            Cell::from(format!("{:.2}", dividend)).style(Style::default().fg(Color::Green)),
+        ])
+    });
+
+    let widths = [
+        Constraint::Length(11),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Length(11),
+        Constraint::Length(9),
+        Constraint::Length(9),
+        Constraint::Length(11),
+        Constraint::Length(11),
+    ];
+
+    let first = (page.page() * page.page_size() + 1).min(*page.total());
+    let last = page.page() * page.page_size() + page.entries().len() as i64;
+    let table = Table::new(rows, widths)
+        .header(header_row(&[
+            "Date",
+            "Type",
+            "Symbol",
+            "Broker",
+            "Quantity",
+            "Price",
+            "Fees",
+            "FX rate",
+            "Real. G/L",
+            "Div.",
+        ]))
+        .block(
+            Block::default()
+                .title(format!(
+                    "Transactions {}-{} of {} (page {}/{}, sorted by {} {})",
+                    first,
+                    last,
+                    page.total(),
+                    page.page() + 1,
+                    page.page_count(),
+                    ledger.sort.to_str(),
+                    if ledger.descending { "desc" } else { "asc" },
+                ))
+                .borders(Borders::ALL),
+        )
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    frame.render_stateful_widget(table, chunks[1], ledger_state);
+}
+
+fn render_transaction_form_popup(frame: &mut Frame, form: &TransactionForm) {
+    let area = centered_rect(60, 50, frame.area());
+    frame.render_widget(Clear, area);
+
+    let rows = FORM_FIELDS.iter().enumerate().map(|(i, label)| {
+        let focused = i == *form.focus();
+        let value = if focused {
+            format!("{}_", form.value(i))
+        } else {
+            form.value(i).to_string()
+        };
+        let style = if focused {
+            Style::default().add_modifier(Modifier::REVERSED)
+        } else {
+            Style::default()
+        };
+        Row::new([Cell::from(*label), Cell::from(value)]).style(style)
+    });
+
+    let title = match form.id() {
+        Some(_) => "Edit transaction",
+        None => "Add transaction",
+    };
+    let table = Table::new(rows, [Constraint::Length(10), Constraint::Min(0)])
+        .block(
+            Block::default()
+                .title(title)
+                .title_bottom(" Tab/Up/Down: field | Left/Right: type | Enter: save | Esc: cancel ")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .style(Style::default().fg(Color::White));
+    frame.render_widget(table, area);
+}
+
+fn render_delete_transaction_popup(frame: &mut Frame, entry: &LedgerEntry) {
+    let area = centered_rect(50, 20, frame.area());
+    frame.render_widget(Clear, area);
+    let transaction = entry.transaction();
+    let popup = Paragraph::new(format!(
+        "Delete {} {} {} of {} at {}?\n\ny: delete | n/Esc: cancel",
+        transaction.transaction_type().to_str(),
+        transaction.quantity().normalize(),
+        entry.symbol(),
+        transaction.date().format("%Y-%m-%d"),
+        transaction.broker(),
+    ))
+    .style(Style::default().fg(Color::White))
+    .block(
+        Block::default()
+            .title("Delete transaction")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Yellow)),
+    );
+    frame.render_widget(popup, area);
+}
+
 fn render_message_popup(frame: &mut Frame, message: &str) {
     let area = centered_rect(50, 20, frame.area());
     let popup = Paragraph::new(message)
@@ -184,10 +521,31 @@ fn render_error_popup(frame: &mut Frame, error_message: &str) {
     frame.render_widget(popup, area);
 }
 
-fn render_api_selection_popup(frame: &mut Frame, default_api_state: &mut ListState) {
+fn render_notice_popup(frame: &mut Frame, notice: &str) {
     let area = centered_rect(60, 25, frame.area());
-    let items: Vec<ListItem> = ApiProvider::iter()
-        .map(|api| ListItem::new(format!("{:?}", api)))
+    frame.render_widget(Clear, area);
+    let popup = Paragraph::new(format!("{}\n\nPress Enter or Esc to dismiss", notice))
+        .style(Style::default().fg(Color::White).bg(Color::Black))
+        .block(
+            Block::default()
+                .title("Done")
+                .borders(Borders::ALL)
+                .style(Style::default().fg(Color::Green).bg(Color::Black)),
+        );
+    frame.render_widget(popup, area);
+}
+
+fn render_api_selection_popup(
+    frame: &mut Frame,
+    portfolio: &Portfolio,
+    default_api_state: &mut ListState,
+) {
+    let area = centered_rect(60, 25, frame.area());
+    let items: Vec<ListItem> = portfolio
+        .providers()
+        .apis()
+        .iter()
+        .map(|api| ListItem::new(api.to_str().to_string()))
         .collect();
     let list = List::new(items)
         .block(
@@ -230,44 +588,577 @@ fn render_database_reset_popup(frame: &mut Frame, default_reset_state: &mut List
     frame.render_stateful_widget(list, area, default_reset_state);
 }
 
+fn dividend_row(label: String, dividend: &DividendBreakdown) -> Row<'static> {
+    Row::new([
+        Cell::from(label),
+        Cell::from(format!("{:.2}", dividend.gross())),
+        Cell::from(format!("{:.2}", dividend.withholding_tax())),
+        Cell::from(format!("{:.2}", dividend.domestic_tax())),
+        Cell::from(format!("{:.2}", dividend.reclaimable())),
+        Cell::from(format!("{:.2}", dividend.net())).style(Style::default().fg(Color::Green)),
+    ])
+}
+
+fn dividend_section_row(title: &str) -> Row<'static> {
+    Row::new([Cell::from(title.to_string()).style(
+        Style::default()
+            .fg(Color::Cyan)
+            .add_modifier(Modifier::BOLD),
+    )])
+}
+
+fn render_dividends_popup(frame: &mut Frame, report: &DividendReport) {
+    let area = centered_rect(80, 80, frame.area());
+    frame.render_widget(Clear, area);
+
+    let header_cells = ["", "Gross", "Withheld", "Domestic", "Reclaimable", "Net"]
+        .iter()
+        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
+    let header = Row::new(header_cells).height(1);
+
+    let mut rows = vec![dividend_section_row("By year")];
+    rows.extend(
+        report
+            .by_year()
+            .iter()
+            .map(|(year, dividend)| dividend_row(year.to_string(), dividend)),
+    );
+    rows.push(dividend_section_row("By country"));
+    rows.extend(
+        report
+            .by_country()
+            .iter()
+            .map(|(country, dividend)| dividend_row(country.clone(), dividend)),
+    );
+    rows.push(dividend_section_row("By position"));
+    rows.extend(
+        report
+            .by_position()
+            .iter()
+            .map(|(name, dividend)| dividend_row(name.clone(), dividend)),
+    );
+    rows.push(dividend_row(String::from("Total"), report.total()));
+
+    let widths = [
+        Constraint::Min(30),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Length(12),
+        Constraint::Length(12),
+    ];
+
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .title("Dividends (Esc to close)")
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Yellow)),
+    );
+    frame.render_widget(table, area);
+}
+
+fn format_return(value: &Option<Decimal>) -> (String, Color) {
+    match value {
+        Some(value) => format_colored_percentage(*value * Decimal::ONE_HUNDRED),
+        None => (String::from("n/a"), Color::Gray),
+    }
+}
+
+fn performance_row(label: String, performance: &Performance) -> Row<'static> {
+    let (twr, twr_color) = format_return(performance.twr());
+    let (xirr, xirr_color) = format_return(performance.xirr());
+    Row::new([
+        Cell::from(label),
+        Cell::from(twr).style(Style::default().fg(twr_color)),
+        Cell::from(xirr).style(Style::default().fg(xirr_color)),
+    ])
+}
+
+fn render_performance_popup(frame: &mut Frame, report: &PerformanceReport) {
+    let area = centered_rect(70, 70, frame.area());
+    frame.render_widget(Clear, area);
+
+    let periods = period_titles(report.period());
+
+    let header_cells = ["", "TWR", "XIRR (p.a.)"]
+        .iter()
+        .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow)));
+    let header = Row::new(header_cells).height(1);
+
+    let mut rows = vec![performance_row(
+        String::from("Portfolio"),
+        report.portfolio(),
+    )];
+    rows.extend(
+        report
+            .by_position()
+            .iter()
+            .map(|(name, performance)| performance_row(name.clone(), performance)),
+    );
+
+    let widths = [
+        Constraint::Min(30),
+        Constraint::Length(14),
+        Constraint::Length(14),
+    ];
+
+    let table = Table::new(rows, widths).header(header).block(
+        Block::default()
+            .title(format!(
+                "Returns: {} (Left/Right to change, Esc to close)",
+                periods
+            ))
+            .borders(Borders::ALL)
+            .style(Style::default().fg(Color::Yellow)),
+    );
+    frame.render_widget(table, area);
+}
+
+fn header_row(titles: &[&'static str]) -> Row<'static> {
+    Row::new(
+        titles
+            .iter()
+            .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow))),
+    )
+    .height(1)
+}
+
+fn render_position_detail_popup(frame: &mut Frame, detail: &PositionDetail) {
+    let area = centered_rect(90, 90, frame.area());
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!("{} (Esc to close)", detail.asset().name()))
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Yellow));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(detail.tickers().len() as u16 + 2), // Metadata
+            Constraint::Percentage(40),                            // Transactions
+            Constraint::Percentage(30),                            // Lots
+            Constraint::Min(0),                                    // Realized gains
+        ])
+        .split(inner);
+
+    let asset = detail.asset();
+    let mut info = vec![format!(
+        "Type: {}   ISIN: {}   Sector: {}   Industry: {}",
+        asset.asset_type().to_str(),
+        asset.isin().as_deref().unwrap_or("-"),
+        asset.sector().as_deref().unwrap_or("-"),
+        asset.industry().as_deref().unwrap_or("-"),
+    )];
+    info.extend(detail.tickers().iter().map(|ticker| {
+        format!(
+            "Ticker: {} ({}, {}, {})   Last price: {}",
+            ticker.symbol(),
+            ticker.exchange().as_deref().unwrap_or("-"),
+            ticker.currency(),
+            ticker.api().to_str(),
+            ticker
+                .last_price()
+                .map(|price| format!("{:.2}", price))
+                .unwrap_or_else(|| String::from("-")),
+        )
+    }));
+    frame.render_widget(
+        Paragraph::new(info.join("\n")).style(Style::default().fg(Color::White)),
+        chunks[0],
+    );
+
+    let symbol_of = |ticker_id: &i64| {
+        detail
+            .tickers()
+            .iter()
+            .find(|ticker| ticker.id() == ticker_id)
+            .map(|ticker| ticker.symbol().clone())
+            .unwrap_or_default()
+    };
+
+    let transaction_rows = detail.transactions().iter().map(|transaction| {
+        let (units, cost) = transaction
+            .position_state()
+            .as_ref()
+            .map(|state| (*state.cumulative_units(), *state.cumulative_cost()))
+            .unwrap_or_default();
+        Row::new([
+            Cell::from(transaction.transaction_no().to_string()),
+            Cell::from(transaction.date().format("%Y-%m-%d").to_string()),
+            Cell::from(transaction.transaction_type().to_str().to_string()),
+            Cell::from(symbol_of(transaction.ticker_id())),
+            Cell::from(transaction.broker().clone()),
+            Cell::from(format!("{:.4}", transaction.quantity())),
+            Cell::from(format!("{:.2}", transaction.price())),
+            Cell::from(format!("{:.2}", transaction.fees())),
+            Cell::from(transaction.currency().clone()),
+            Cell::from(format!("{:.4}", units)),
+            Cell::from(format!("{:.2}", cost)),
+        ])
+    });
+    let transactions = Table::new(
+        transaction_rows,
+        [
+            Constraint::Length(6),
+            Constraint::Length(11),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(11),
+            Constraint::Length(9),
+            Constraint::Length(5),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(header_row(&[
+        "No", "Date", "Type", "Symbol", "Broker", "Quantity", "Price", "Fees", "Cur.", "Units",
+        "Cost",
+    ]))
+    .block(Block::default().title("Transactions").borders(Borders::TOP))
+    .style(Style::default().fg(Color::White));
+    frame.render_widget(transactions, chunks[1]);
+
+    let lot_rows = detail.lots().iter().map(|lot_detail| {
+        let lot = lot_detail.lot();
+        let (gain, color) = format_colored_gain(*lot_detail.unrealized_gain());
+        Row::new([
+            Cell::from(lot.date().format("%Y-%m-%d").to_string()),
+            Cell::from(lot_detail.symbol().clone()),
+            Cell::from(lot_detail.broker().clone()),
+            Cell::from(format!("{:.4}", lot.quantity())),
+            Cell::from(format!("{:.4}", lot.unit_cost())),
+            Cell::from(format!("{:.2}", lot.cost())),
+            Cell::from(format!("{:.2}", lot_detail.market_value())),
+            Cell::from(gain).style(Style::default().fg(color)),
+        ])
+    });
+    let lots = Table::new(
+        lot_rows,
+        [
+            Constraint::Length(11),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(header_row(&[
+        "Acquired",
+        "Symbol",
+        "Broker",
+        "Quantity",
+        "Unit cost",
+        "Cost",
+        "Value",
+        "Unr. G/L",
+    ]))
+    .block(
+        Block::default()
+            .title(format!("Open lots ({})", detail.cost_basis()))
+            .borders(Borders::TOP),
+    )
+    .style(Style::default().fg(Color::White));
+    frame.render_widget(lots, chunks[2]);
+
+    let realized_rows = detail
+        .transactions()
+        .iter()
+        .filter(|transaction| *transaction.transaction_type() == TransactionType::Sell)
+        .map(|transaction| {
+            let cost_sold = transaction
+                .position_state()
+                .as_ref()
+                .map(|state| *state.cost_of_units_sold())
+                .unwrap_or_default();
+            let realized_gain = transaction
+                .transaction_gains()
+                .as_ref()
+                .map(|gains| *gains.realized_gain())
+                .unwrap_or_default();
+            let (gain, color) = format_colored_gain(realized_gain);
+            Row::new([
+                Cell::from(transaction.date().format("%Y-%m-%d").to_string()),
+                Cell::from(symbol_of(transaction.ticker_id())),
+                Cell::from(transaction.broker().clone()),
+                Cell::from(format!("{:.4}", transaction.quantity())),
+                Cell::from(format!("{:.2}", transaction.get_amount())),
+                Cell::from(format!("{:.2}", cost_sold)),
+                Cell::from(gain).style(Style::default().fg(color)),
+            ])
+        });
+    let realized = Table::new(
+        realized_rows,
+        [
+            Constraint::Length(11),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(12),
+        ],
+    )
+    .header(header_row(&[
+        "Sold",
+        "Symbol",
+        "Broker",
+        "Quantity",
+        "Proceeds",
+        "Cost",
+        "Real. G/L",
+    ]))
+    .block(
+        Block::default()
+            .title("Realized gains")
+            .borders(Borders::TOP),
+    )
+    .style(Style::default().fg(Color::White));
+    frame.render_widget(realized, chunks[3]);
+}
+
+fn render_import_preview_popup(
+    frame: &mut Frame,
+    preview: &ImportPreview,
+    preview_state: &mut TableState,
+) {
+    let area = centered_rect(90, 90, frame.area());
+    frame.render_widget(Clear, area);
+
+    let errors = preview.errors();
+    let title = if errors.is_empty() {
+        "Import preview (Enter: import, Esc: abort)"
+    } else {
+        "Import preview (fix the invalid records to import, Esc: close)"
+    };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::Yellow));
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let changes = preview.position_changes();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),                                  // Summary
+            Constraint::Min(5),                                     // Rows
+            Constraint::Length((changes.len() as u16 + 2).min(12)), // Position changes
+            Constraint::Length(if errors.is_empty() {
+                0
+            } else {
+                (errors.len() as u16 + 1).min(10)
+            }), // Errors
+        ])
+        .split(inner);
+
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{} new, {} changed, {} skipped, {} invalid",
+            preview.count(ImportStatus::New),
+            preview.count(ImportStatus::Changed),
+            preview.count(ImportStatus::Unchanged),
+            errors.len(),
+        ))
+        .style(Style::default().fg(Color::White)),
+        chunks[0],
+    );
+
+    let rows = preview
+        .rows()
+        .iter()
+        .filter(|row| *row.status() != ImportStatus::Unchanged)
+        .map(|row| {
+            let transaction = row.transaction();
+            let color = match row.status() {
+                ImportStatus::New => Color::Green,
+                _ => Color::Yellow,
+            };
+            Row::new([
+                Cell::from(row.record().to_string()),
+                Cell::from(row.status().to_str().to_string()).style(Style::default().fg(color)),
+                Cell::from(transaction.transaction_no().to_string()),
+                Cell::from(transaction.date().format("%Y-%m-%d").to_string()),
+                Cell::from(transaction.transaction_type().to_str().to_string()),
+                Cell::from(row.symbol().clone()),
+                Cell::from(transaction.broker().clone()),
+                Cell::from(format!("{:.4}", transaction.quantity())),
+                Cell::from(format!("{:.2}", transaction.price())),
+                Cell::from(transaction.currency().clone()),
+            ])
+        });
+    let rows = Table::new(
+        rows,
+        [
+            Constraint::Length(7),
+            Constraint::Length(8),
+            Constraint::Length(6),
+            Constraint::Length(11),
+            Constraint::Length(12),
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(12),
+            Constraint::Length(11),
+            Constraint::Length(5),
+        ],
+    )
+    .header(header_row(&[
+        "Record", "Status", "No", "Date", "Type", "Symbol", "Broker", "Quantity", "Price", "Cur.",
+    ]))
+    .block(
+        Block::default()
+            .title("New and changed transactions")
+            .borders(Borders::TOP),
+    )
+    .style(Style::default().fg(Color::White))
+    .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(rows, chunks[1], preview_state);
+
+    let change_rows = changes.iter().map(|change| {
+        Row::new([
+            Cell::from(change.symbol().clone()),
+            Cell::from(change.broker().clone()),
+            Cell::from(format!("{:.4}", change.units_before())),
+            Cell::from(format!("{:.4}", change.units_after())),
+            Cell::from(format!("{:.2}", change.cost_before())),
+            Cell::from(format!("{:.2}", change.cost_after())),
+        ])
+    });
+    let change_table = Table::new(
+        change_rows,
+        [
+            Constraint::Length(10),
+            Constraint::Length(12),
+            Constraint::Length(14),
+            Constraint::Length(14),
+            Constraint::Length(14),
+            Constraint::Length(14),
+        ],
+    )
+    .header(header_row(&[
+        "Symbol",
+        "Broker",
+        "Units before",
+        "Units after",
+        "Cost before",
+        "Cost after",
+    ]))
+    .block(
+        Block::default()
+            .title("Position changes")
+            .borders(Borders::TOP),
+    )
+    .style(Style::default().fg(Color::White));
+    frame.render_widget(change_table, chunks[2]);
+
+    if !errors.is_empty() {
+        frame.render_widget(
+            Paragraph::new(errors.join("\n"))
+                .style(Style::default().fg(Color::Red))
+                .block(
+                    Block::default()
+                        .title("Invalid records")
+                        .borders(Borders::TOP),
+                ),
+            chunks[3],
+        );
+    }
+}
+
 pub fn render(
     frame: &mut Frame,
     portfolio: &Portfolio,
     table_state: &mut TableState,
     popup_message: &Option<String>,
     error_popup: &Option<String>,
+    notice_popup: &Option<String>,
     api_selection_popup: bool,
     default_api_state: &mut ListState,
     selection_mode: bool,
     database_reset_popup: bool,
     default_reset_state: &mut ListState,
+    dividends_popup: Option<&DividendReport>,
+    performance_popup: Option<&PerformanceReport>,
+    tab: Tab,
+    value_history: &ValueHistory,
+    history_overlay: Option<&PositionHistory>,
+    position_detail_popup: Option<&PositionDetail>,
+    ledger: LedgerView,
+    ledger_state: &mut TableState,
+    transaction_form_popup: Option<&TransactionForm>,
+    delete_transaction_popup: Option<&LedgerEntry>,
+    import_preview_popup: Option<&ImportPreview>,
+    import_preview_state: &mut TableState,
 ) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3), // Title
-            Constraint::Min(0),    // Table
+            Constraint::Min(0),    // Table or chart
             Constraint::Length(3), // Footer
         ])
         .split(frame.area());
 
-    render_title(frame, portfolio, chunks[0]);
-    render_positions_table(frame, portfolio, table_state, selection_mode, chunks[1]);
+    render_title(frame, portfolio, tab, chunks[0]);
+    match tab {
+        Tab::Positions => {
+            render_positions_table(frame, portfolio, table_state, selection_mode, chunks[1])
+        }
+        Tab::History => render_history_chart(frame, value_history, history_overlay, chunks[1]),
+        Tab::Transactions => render_ledger(frame, &ledger, ledger_state, chunks[1]),
+    }
     render_footer(frame, chunks[2]);
 
     if let Some(message) = popup_message {
         render_message_popup(frame, message);
     }
 
+    // Below the error popup, which reports invalid form values
+    if let Some(form) = transaction_form_popup {
+        render_transaction_form_popup(frame, form);
+    }
+
+    if let Some(entry) = delete_transaction_popup {
+        render_delete_transaction_popup(frame, entry);
+    }
+
     if let Some(error_message) = error_popup {
         render_error_popup(frame, error_message);
     }
 
+    if let Some(notice) = notice_popup {
+        render_notice_popup(frame, notice);
+    }
+
     if api_selection_popup {
-        render_api_selection_popup(frame, default_api_state);
+        render_api_selection_popup(frame, portfolio, default_api_state);
     }
 
     if database_reset_popup {
         render_database_reset_popup(frame, default_reset_state);
     }
+
+    if let Some(report) = dividends_popup {
+        render_dividends_popup(frame, report);
+    }
+
+    if let Some(report) = performance_popup {
+        render_performance_popup(frame, report);
+    }
+
+    if let Some(detail) = position_detail_popup {
+        render_position_detail_popup(frame, detail);
+    }
+
+    if let Some(preview) = import_preview_popup {
+        render_import_preview_popup(frame, preview, import_preview_state);
+    }
 }
@@ -1,15 +1,21 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Local, TimeZone};
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
 use reqwest::Client;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use std::str::FromStr;
+use sha2::{Digest, Sha256};
+use sqlx::SqliteConnection;
 
 use crate::{
-    api::{av, fmp, frank, marketstack},
-    models::{Ticker, ticker::ApiProvider},
+    api::provider::ForexProvider,
+    db::utils::{find_fx_rate, insert_fx_rate},
+    models::FxRate,
 };
 
+/// How far back a cached rate may be used when the forex provider fails, e.g.
+/// offline or on a holiday.
+const FX_FALLBACK_DAYS: u64 = 7;
+
 pub fn parse_datetime(field: &str) -> Result<DateTime<Local>> {
     let date_str = format!("{} 00:00:00", field);
     let naive = chrono::NaiveDateTime::parse_from_str(&date_str, "%Y-%m-%d %H:%M:%S")
@@ -24,88 +30,141 @@ pub fn parse_decimal(field: &str, field_name: &str) -> Result<Decimal> {
         .with_context(|| format!("Failed to parse {} '{}'", field_name, field))
 }
 
-pub async fn find_ticker(symbol: &str, client: &Client, api: &ApiProvider) -> Result<Ticker> {
-    match api {
-        ApiProvider::AlphaVantage => {
-            let api_key = std::env::var("ALPHA_VANTAGE_API_KEY")?;
-            let av_search_result = av::search_symbol(symbol, client, api_key.as_str())
-                .await
-                .with_context(|| format!("Alpha Vantage ({})", symbol))?;
-
-            let first = av_search_result
-                .first()
-                .with_context(|| "Failed to get first value")?;
+/// Like [`parse_decimal`], but an empty field is zero.
+pub fn parse_optional_decimal(field: &str, field_name: &str) -> Result<Decimal> {
+    if field.trim().is_empty() {
+        return Ok(Decimal::ZERO);
+    }
+    parse_decimal(field, field_name)
+}
 
-            Ok(first.to_ticker())
-        }
-        ApiProvider::Fmp => {
-            let api_key = std::env::var("FMP_API_KEY")?;
-            let fmp_search_result = fmp::search_symbol(symbol, client, api_key.as_str())
-                .await
-                .with_context(|| format!("Alpha Vantage ({})", symbol))?;
-            let first = fmp_search_result
-                .first()
-                .with_context(|| "Failed to get first value")?;
-            Ok(first.to_ticker())
-        }
-        ApiProvider::Marketstack => {
-            let api_key = std::env::var("MARKETSTACK_API_KEY")?;
-            let marketstack_search_result =
-                marketstack::search_symbol(symbol, client, api_key.as_str())
-                    .await
-                    .with_context(|| format!("Marketstack ({})", symbol))?;
-            Ok(marketstack_search_result.to_ticker()?)
+/// Parses a split ratio given as `new:old` (e.g. `4:1`, or `1:10` for a reverse
+/// split) or as a plain number of new units per old unit.
+pub fn parse_split_ratio(field: &str) -> Result<Decimal> {
+    let ratio = match field.split_once(':') {
+        Some((new_units, old_units)) => {
+            let new_units = parse_decimal(new_units.trim(), "split ratio")?;
+            let old_units = parse_decimal(old_units.trim(), "split ratio")?;
+            if old_units == Decimal::ZERO {
+                return Err(anyhow::anyhow!("Failed to parse split ratio '{}'", field));
+            }
+            new_units / old_units
         }
+        None => parse_decimal(field.trim(), "split ratio")?,
+    };
+
+    if ratio <= Decimal::ZERO {
+        return Err(anyhow::anyhow!(
+            "Split ratio '{}' must be greater than zero",
+            field
+        ));
     }
+
+    Ok(ratio)
 }
 
-pub async fn get_latest_price(symbol: &str, client: &Client, api: &ApiProvider) -> Result<Decimal> {
-    match api {
-        ApiProvider::AlphaVantage => {
-            let api_key = std::env::var("ALPHA_VANTAGE_API_KEY")?;
-            let av_quote_result = av::get_quote(&symbol, &client, api_key.as_str())
-                .await
-                .with_context(|| format!("Alpha Vantage ({})", &symbol))?;
-            Decimal::from_str(av_quote_result.price())
-                .with_context(|| format!("Alpha Vantage ({}): Failed to parse price", symbol))
-        }
-        ApiProvider::Fmp => {
-            let api_key = std::env::var("FMP_API_KEY")?;
-            let fmp_quote_result = fmp::get_quote(&symbol, &client, &api_key)
-                .await
-                .with_context(|| format!("FMP ({})", &symbol))?;
-            Ok(*fmp_quote_result
-                .first()
-                .with_context(|| format!("FMP ({}): Failed to get first entry", symbol))?
-                .price())
-        }
-        ApiProvider::Marketstack => {
-            let api_key = std::env::var("MARKETSTACK_API_KEY")?;
-            let marketstack_quote_result =
-                marketstack::get_quote(&symbol, &client, api_key.as_str()).await?;
-            let first = marketstack_quote_result
-                .first()
-                .with_context(|| "Failed to get first entry")?;
-            Ok(*first.close())
-        }
+/// Hex SHA-256 of the trimmed fields of an imported record. Trailing empty
+/// fields are ignored, so adding optional columns keeps the hash.
+pub fn hash_record<'a>(fields: impl IntoIterator<Item = &'a str>) -> String {
+    let mut fields: Vec<&str> = fields.into_iter().map(str::trim).collect();
+    while fields.last().is_some_and(|field| field.is_empty()) {
+        fields.pop();
+    }
+
+    Sha256::digest(fields.join("\u{1f}"))
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The parts of `start..=end` before and after `covered`, the first and last
+/// date already stored. Gaps within the covered range are weekends and
+/// holidays rather than missing data.
+pub fn uncovered_ranges(
+    start: NaiveDate,
+    end: NaiveDate,
+    covered: Option<(NaiveDate, NaiveDate)>,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let Some((first, last)) = covered else {
+        return vec![(start, end)];
+    };
+
+    let mut ranges = Vec::new();
+    if start < first {
+        ranges.push((start, first.pred_opt().unwrap_or(first).min(end)));
+    }
+    if last < end {
+        ranges.push((last.succ_opt().unwrap_or(last).max(start), end));
+    }
+    ranges
+}
+
+/// The date itself on weekdays, otherwise the Friday before.
+pub fn previous_business_day(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date - Days::new(1),
+        Weekday::Sun => date - Days::new(2),
+        _ => date,
     }
 }
 
+/// Units of `base_currency` for one unit of `transaction_currency`. Rates are
+/// read from the `fx_rates` cache first; rates fetched from `forex` are added
+/// to it unless requested for today or yesterday, as providers return the rate
+/// of the day before until the new one is published. Weekend dates use the
+/// previous business day, and when the provider fails the latest cached rate
+/// of the preceding week is used.
 pub async fn get_exchange_rate(
     base_currency: &str,
     transaction_currency: &str,
     transaction_date: &DateTime<Local>,
+    forex: &dyn ForexProvider,
     client: &Client,
+    connection: &mut SqliteConnection,
 ) -> Result<Decimal> {
     if base_currency == transaction_currency {
         return Ok(dec!(1.0));
     }
-    let quote_result = frank::get_forex_history(
-        transaction_currency,
-        base_currency,
-        &transaction_date.format("%Y-%m-%d").to_string(),
-        client,
-    )
-    .await?;
-    Ok(quote_result.rates()[base_currency])
+
+    let date = previous_business_day(transaction_date.date_naive());
+    if let Some(rate) =
+        find_fx_rate(connection, transaction_currency, base_currency, date, date).await?
+    {
+        return Ok(rate);
+    }
+
+    let request_date = Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .unwrap_or(*transaction_date);
+    match forex
+        .get_rate(transaction_currency, base_currency, &request_date, client)
+        .await
+    {
+        Ok(rate) => {
+            let fx_rate = FxRate::new(
+                transaction_currency.to_string(),
+                base_currency.to_string(),
+                date,
+                rate,
+                forex.name().to_string(),
+            );
+            if transaction_date.date_naive() < Local::now().date_naive() - Days::new(1) {
+                insert_fx_rate(&fx_rate, connection).await?;
+            }
+            Ok(rate)
+        }
+        Err(e) => {
+            let earliest = date - Days::new(FX_FALLBACK_DAYS);
+            find_fx_rate(
+                connection,
+                transaction_currency,
+                base_currency,
+                earliest,
+                date,
+            )
+            .await?
+            .ok_or(e)
+        }
+    }
 }
@@ -0,0 +1,87 @@
+-- Money and quantity columns were declared REAL, which rounds every value to
+-- a binary float. TEXT affinity keeps the exact decimal representation.
+-- Existing REAL values are converted with CAST, which yields up to 15
+-- significant digits and therefore reproduces the 4 dp values stored so far.
+-- The transactions are moved aside before `tickers` is dropped: dropping a
+-- table that rows still reference fails the foreign key check at commit.
+CREATE TABLE transactions_old AS SELECT * FROM transactions;
+DROP TABLE transactions;
+
+CREATE TABLE tickers_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    symbol TEXT NOT NULL,
+    asset_id INTEGER REFERENCES assets(id),
+    currency TEXT NOT NULL,
+    exchange TEXT,
+    last_price TEXT,
+    last_price_updated_at DATETIME,
+    api TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE(symbol)
+);
+
+INSERT INTO tickers_new
+SELECT
+    id,
+    symbol,
+    asset_id,
+    currency,
+    exchange,
+    CAST(last_price AS TEXT),
+    last_price_updated_at,
+    api,
+    created_at,
+    updated_at
+FROM tickers;
+
+DROP TABLE tickers;
+ALTER TABLE tickers_new RENAME TO tickers;
+
+CREATE TABLE transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    transaction_no INTEGER NOT NULL,
+    transaction_date DATETIME NOT NULL,
+    transaction_type TEXT NOT NULL,
+    ticker_id INTEGER REFERENCES tickers(id),
+    broker TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    exchange_rate TEXT NOT NULL,
+    quantity TEXT NOT NULL,
+    price TEXT NOT NULL,
+    fees TEXT NOT NULL,
+    cumulative_units TEXT NOT NULL,
+    cumulative_cost TEXT NOT NULL,
+    cost_of_units_sold TEXT NOT NULL,
+    realized_gain TEXT NOT NULL,
+    dividend TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE(transaction_no)
+);
+
+INSERT INTO transactions
+SELECT
+    id,
+    transaction_no,
+    transaction_date,
+    transaction_type,
+    ticker_id,
+    broker,
+    currency,
+    CAST(exchange_rate AS TEXT),
+    CAST(quantity AS TEXT),
+    CAST(price AS TEXT),
+    CAST(fees AS TEXT),
+    CAST(cumulative_units AS TEXT),
+    CAST(cumulative_cost AS TEXT),
+    CAST(cost_of_units_sold AS TEXT),
+    CAST(realized_gain AS TEXT),
+    CAST(dividend AS TEXT),
+    created_at,
+    updated_at
+FROM transactions_old;
+
+DROP TABLE transactions_old;
@@ -0,0 +1,2 @@
+ALTER TABLE transactions ADD COLUMN withholding_tax TEXT NOT NULL DEFAULT '0';
+ALTER TABLE transactions ADD COLUMN domestic_tax TEXT NOT NULL DEFAULT '0';
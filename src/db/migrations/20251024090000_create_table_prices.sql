@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS prices (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    ticker_id INTEGER NOT NULL REFERENCES tickers(id),
+    price_date TEXT NOT NULL,
+    close TEXT NOT NULL,
+    adj_close TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE(ticker_id, price_date)
+)
@@ -0,0 +1 @@
+ALTER TABLE transactions ADD COLUMN source TEXT NOT NULL DEFAULT 'Import';
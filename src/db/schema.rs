@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use sqlx::{Pool, Sqlite, migrate::Migrator};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./src/db/migrations");
+
+pub fn latest_schema_version() -> i64 {
+    MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
+
+pub async fn get_schema_version(connection: &Pool<Sqlite>) -> Result<Option<i64>> {
+    let table_exists = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+    )
+    .fetch_one(connection)
+    .await?;
+
+    if table_exists == 0 {
+        return Ok(None);
+    }
+
+    let version = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1",
+    )
+    .fetch_one(connection)
+    .await?;
+
+    Ok(version)
+}
+
+pub async fn run_migrations(connection: &Pool<Sqlite>) -> Result<()> {
+    let latest_version = latest_schema_version();
+    if let Some(version) = get_schema_version(connection).await?
+        && version > latest_version
+    {
+        return Err(anyhow::anyhow!(
+            concat!(
+                "Database schema version {} is newer than the latest version {} ",
+                "supported by this build, please upgrade portfolio-tracker-tui"
+            ),
+            version,
+            latest_version
+        ));
+    }
+
+    MIGRATOR
+        .run(connection)
+        .await
+        .with_context(|| "Failed to run database migrations")?;
+
+    Ok(())
+}
@@ -1,9 +1,18 @@
+use std::{collections::BTreeMap, str::FromStr};
+
 use anyhow::{Context, Result};
-use chrono::{DateTime, Local, TimeZone};
-use rust_decimal::{Decimal, prelude::FromPrimitive, prelude::ToPrimitive};
-use sqlx::{Pool, Row, Sqlite, sqlite::SqliteRow};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use rust_decimal::{Decimal, prelude::FromPrimitive};
+use rust_decimal_macros::dec;
+use sqlx::{Pool, Row, Sqlite, SqliteConnection, sqlite::SqliteRow};
 
-use crate::models::{Asset, PositionState, Ticker, Transaction, TransactionGains, TransactionType};
+use crate::{
+    api::provider::ProviderRegistry,
+    models::{
+        Asset, FxRate, PositionState, Quote, Ticker, Transaction, TransactionGains,
+        TransactionSource, TransactionType,
+    },
+};
 
 pub async fn insert_ticker(
     ticker: &Ticker,
@@ -51,7 +60,7 @@ pub async fn insert_ticker(
     .bind(asset_id)
     .bind(ticker.currency())
     .bind(ticker.exchange())
-    .bind(last_price.round_dp(4).to_f64())
+    .bind(decimal_to_db(&last_price))
     .bind(ticker.last_price_updated_at())
     .bind(ticker.api().to_str())
     .execute(&mut **tx)
@@ -61,9 +70,14 @@ pub async fn insert_ticker(
     Ok(id)
 }
 
+/// Stores a new transaction. `content_hash` identifies the imported record it
+/// came from, if any, and `external_id` the statement entry at the broker.
 pub async fn insert_transaction(
     transaction: &Transaction,
     ticker_id: &i64,
+    source: &TransactionSource,
+    content_hash: Option<&str>,
+    external_id: Option<&str>,
     tx: &mut sqlx::Transaction<'_, Sqlite>,
 ) -> Result<i64> {
     let position_state = transaction
@@ -90,30 +104,41 @@ pub async fn insert_transaction(
             quantity,
             price,
             fees,
+            withholding_tax,
+            domestic_tax,
             cumulative_units,
             cumulative_cost,
             cost_of_units_sold,
             realized_gain,
-            dividend
+            dividend,
+            source,
+            content_hash,
+            external_id
         )
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#,
     )
     .bind(transaction.transaction_no())
     .bind(transaction.date())
     .bind(transaction.transaction_type().to_str())
-    .bind(ticker_id)
+    // Cash account transactions have no ticker
+    .bind((!transaction.transaction_type().is_cash()).then_some(ticker_id))
     .bind(transaction.broker())
     .bind(transaction.currency())
-    .bind(transaction.exchange_rate().round_dp(4).to_f64())
-    .bind(transaction.quantity().round_dp(4).to_f64())
-    .bind(transaction.price().round_dp(4).to_f64())
-    .bind(transaction.fees().round_dp(4).to_f64())
-    .bind(position_state.cumulative_units().round_dp(4).to_f64())
-    .bind(position_state.cumulative_cost().round_dp(4).to_f64())
-    .bind(position_state.cost_of_units_sold().round_dp(4).to_f64())
-    .bind(transaction_gains.realized_gain().round_dp(4).to_f64())
-    .bind(transaction_gains.dividend().round_dp(4).to_f64())
+    .bind(decimal_to_db(transaction.exchange_rate()))
+    .bind(decimal_to_db(transaction.quantity()))
+    .bind(decimal_to_db(transaction.price()))
+    .bind(decimal_to_db(transaction.fees()))
+    .bind(decimal_to_db(transaction.withholding_tax()))
+    .bind(decimal_to_db(transaction.domestic_tax()))
+    .bind(decimal_to_db(position_state.cumulative_units()))
+    .bind(decimal_to_db(position_state.cumulative_cost()))
+    .bind(decimal_to_db(position_state.cost_of_units_sold()))
+    .bind(decimal_to_db(transaction_gains.realized_gain()))
+    .bind(decimal_to_db(transaction_gains.dividend()))
+    .bind(source.to_str())
+    .bind(content_hash)
+    .bind(external_id)
     .execute(&mut **tx)
     .await?
     .last_insert_rowid();
@@ -121,6 +146,404 @@ pub async fn insert_transaction(
     Ok(id)
 }
 
+/// Overwrites every column of the stored transaction with the same id. The
+/// content hash is kept when `content_hash` is `None`.
+pub async fn update_transaction(
+    transaction: &Transaction,
+    ticker_id: &i64,
+    content_hash: Option<&str>,
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+) -> Result<()> {
+    let position_state = transaction
+        .position_state()
+        .as_ref()
+        .with_context(|| "Missing position state")?;
+
+    let transaction_gains = transaction
+        .transaction_gains()
+        .as_ref()
+        .with_context(|| "Missing transaction gains")?;
+
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET
+            transaction_no = ?,
+            transaction_date = ?,
+            transaction_type = ?,
+            ticker_id = ?,
+            broker = ?,
+            currency = ?,
+            exchange_rate = ?,
+            quantity = ?,
+            price = ?,
+            fees = ?,
+            withholding_tax = ?,
+            domestic_tax = ?,
+            cumulative_units = ?,
+            cumulative_cost = ?,
+            cost_of_units_sold = ?,
+            realized_gain = ?,
+            dividend = ?,
+            content_hash = COALESCE(?, content_hash),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(transaction.transaction_no())
+    .bind(transaction.date())
+    .bind(transaction.transaction_type().to_str())
+    .bind((!transaction.transaction_type().is_cash()).then_some(ticker_id))
+    .bind(transaction.broker())
+    .bind(transaction.currency())
+    .bind(decimal_to_db(transaction.exchange_rate()))
+    .bind(decimal_to_db(transaction.quantity()))
+    .bind(decimal_to_db(transaction.price()))
+    .bind(decimal_to_db(transaction.fees()))
+    .bind(decimal_to_db(transaction.withholding_tax()))
+    .bind(decimal_to_db(transaction.domestic_tax()))
+    .bind(decimal_to_db(position_state.cumulative_units()))
+    .bind(decimal_to_db(position_state.cumulative_cost()))
+    .bind(decimal_to_db(position_state.cost_of_units_sold()))
+    .bind(decimal_to_db(transaction_gains.realized_gain()))
+    .bind(decimal_to_db(transaction_gains.dividend()))
+    .bind(content_hash)
+    .bind(transaction.id())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Overwrites the computed columns of an already stored transaction.
+pub async fn update_transaction_state(
+    transaction: &Transaction,
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+) -> Result<()> {
+    let position_state = transaction
+        .position_state()
+        .as_ref()
+        .with_context(|| "Missing position state")?;
+
+    let transaction_gains = transaction
+        .transaction_gains()
+        .as_ref()
+        .with_context(|| "Missing transaction gains")?;
+
+    sqlx::query(
+        r#"
+        UPDATE transactions
+        SET
+            cumulative_units = ?,
+            cumulative_cost = ?,
+            cost_of_units_sold = ?,
+            realized_gain = ?,
+            dividend = ?,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = ?
+        "#,
+    )
+    .bind(decimal_to_db(position_state.cumulative_units()))
+    .bind(decimal_to_db(position_state.cumulative_cost()))
+    .bind(decimal_to_db(position_state.cost_of_units_sold()))
+    .bind(decimal_to_db(transaction_gains.realized_gain()))
+    .bind(decimal_to_db(transaction_gains.dividend()))
+    .bind(transaction.id())
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Stores the close of `quote` for its date, replacing an earlier value for
+/// the same day.
+pub async fn insert_price(
+    ticker_id: i64,
+    quote: &Quote,
+    source: &str,
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO prices (ticker_id, price_date, close, adj_close, source)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(ticker_id, price_date) DO UPDATE SET
+            close = excluded.close,
+            adj_close = excluded.adj_close,
+            source = excluded.source,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(ticker_id)
+    .bind(quote.date().date_naive())
+    .bind(decimal_to_db(quote.close()))
+    .bind(decimal_to_db(quote.adj_close()))
+    .bind(source)
+    .execute(&mut **tx)
+    .await
+    .with_context(|| format!("Failed to store price of {}", quote.symbol()))?;
+
+    Ok(())
+}
+
+/// Stored closes of a ticker between `start` and `end` (inclusive).
+pub async fn get_prices(
+    connection: &Pool<Sqlite>,
+    ticker_id: i64,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, Decimal>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT price_date, close
+        FROM prices
+        WHERE ticker_id = ? AND price_date BETWEEN ? AND ?
+        ORDER BY price_date ASC
+        "#,
+    )
+    .bind(ticker_id)
+    .bind(start)
+    .bind(end)
+    .fetch_all(connection)
+    .await?;
+
+    let mut prices = BTreeMap::new();
+    for row in rows {
+        let date = row
+            .try_get::<NaiveDate, _>("price_date")
+            .with_context(|| "Failed to parse NaiveDate from column 'price_date'")?;
+        prices.insert(date, parse_decimal_from_row(&row, "close")?);
+    }
+
+    Ok(prices)
+}
+
+/// First and last date with a stored price for a ticker.
+pub async fn get_price_range(
+    connection: &Pool<Sqlite>,
+    ticker_id: i64,
+) -> Result<Option<(NaiveDate, NaiveDate)>> {
+    let range = sqlx::query_as::<_, (Option<NaiveDate>, Option<NaiveDate>)>(
+        "SELECT MIN(price_date), MAX(price_date) FROM prices WHERE ticker_id = ?",
+    )
+    .bind(ticker_id)
+    .fetch_one(connection)
+    .await
+    .with_context(|| format!("Failed to read price range of ticker {}", ticker_id))?;
+
+    Ok(range.0.zip(range.1))
+}
+
+pub async fn insert_fx_rate(fx_rate: &FxRate, connection: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO fx_rates (from_currency, to_currency, rate_date, rate, source)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(from_currency, to_currency, rate_date) DO UPDATE SET
+            rate = excluded.rate,
+            source = excluded.source,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(fx_rate.from_currency())
+    .bind(fx_rate.to_currency())
+    .bind(fx_rate.date())
+    .bind(decimal_to_db(fx_rate.rate()))
+    .bind(fx_rate.source())
+    .execute(connection)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to store exchange rate from {} to {} on {}",
+            fx_rate.from_currency(),
+            fx_rate.to_currency(),
+            fx_rate.date()
+        )
+    })?;
+
+    Ok(())
+}
+
+/// Latest cached rate from `from_currency` to `to_currency` dated between
+/// `earliest` and `latest`. Inverse rates and crosses over EUR, the base of
+/// the ECB reference rates, are used when there is no direct rate.
+pub async fn find_fx_rate(
+    connection: &mut SqliteConnection,
+    from_currency: &str,
+    to_currency: &str,
+    earliest: NaiveDate,
+    latest: NaiveDate,
+) -> Result<Option<Decimal>> {
+    if let Some(rate) =
+        find_direct_fx_rate(connection, from_currency, to_currency, earliest, latest).await?
+    {
+        return Ok(Some(rate));
+    }
+
+    if let Some(rate) =
+        find_direct_fx_rate(connection, to_currency, from_currency, earliest, latest).await?
+        && rate != Decimal::ZERO
+    {
+        return Ok(Some(dec!(1) / rate));
+    }
+
+    if from_currency != "EUR" && to_currency != "EUR" {
+        let from_eur = find_direct_fx_rate(connection, "EUR", from_currency, earliest, latest);
+        if let Some(from_rate) = from_eur.await?
+            && let Some(to_rate) =
+                find_direct_fx_rate(connection, "EUR", to_currency, earliest, latest).await?
+            && from_rate != Decimal::ZERO
+        {
+            return Ok(Some(to_rate / from_rate));
+        }
+    }
+
+    Ok(None)
+}
+
+async fn find_direct_fx_rate(
+    connection: &mut SqliteConnection,
+    from_currency: &str,
+    to_currency: &str,
+    earliest: NaiveDate,
+    latest: NaiveDate,
+) -> Result<Option<Decimal>> {
+    let rate = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT rate
+        FROM fx_rates
+        WHERE from_currency = ? AND to_currency = ? AND rate_date BETWEEN ? AND ?
+        ORDER BY rate_date DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(from_currency)
+    .bind(to_currency)
+    .bind(earliest)
+    .bind(latest)
+    .fetch_optional(connection)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to read exchange rate from {} to {}",
+            from_currency, to_currency
+        )
+    })?;
+
+    rate.as_deref().map(parse_decimal_str).transpose()
+}
+
+/// Cached daily rates from `from_currency` to `to_currency` between
+/// `earliest` and `latest`, falling back to inverse rates and crosses over EUR
+/// like [`find_fx_rate`].
+pub async fn get_fx_rate_series(
+    connection: &Pool<Sqlite>,
+    from_currency: &str,
+    to_currency: &str,
+    earliest: NaiveDate,
+    latest: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, Decimal>> {
+    let rates =
+        get_direct_fx_rates(connection, from_currency, to_currency, earliest, latest).await?;
+    if !rates.is_empty() {
+        return Ok(rates);
+    }
+
+    let inverse =
+        get_direct_fx_rates(connection, to_currency, from_currency, earliest, latest).await?;
+    if !inverse.is_empty() {
+        return Ok(inverse
+            .into_iter()
+            .filter(|(_, rate)| *rate != Decimal::ZERO)
+            .map(|(date, rate)| (date, dec!(1) / rate))
+            .collect());
+    }
+
+    if from_currency == "EUR" || to_currency == "EUR" {
+        return Ok(BTreeMap::new());
+    }
+    let from_eur = get_direct_fx_rates(connection, "EUR", from_currency, earliest, latest).await?;
+    let to_eur = get_direct_fx_rates(connection, "EUR", to_currency, earliest, latest).await?;
+    Ok(to_eur
+        .into_iter()
+        .filter_map(|(date, to_rate)| {
+            from_eur
+                .get(&date)
+                .filter(|from_rate| **from_rate != Decimal::ZERO)
+                .map(|from_rate| (date, to_rate / from_rate))
+        })
+        .collect())
+}
+
+async fn get_direct_fx_rates(
+    connection: &Pool<Sqlite>,
+    from_currency: &str,
+    to_currency: &str,
+    earliest: NaiveDate,
+    latest: NaiveDate,
+) -> Result<BTreeMap<NaiveDate, Decimal>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT rate_date, rate
+        FROM fx_rates
+        WHERE from_currency = ? AND to_currency = ? AND rate_date BETWEEN ? AND ?
+        "#,
+    )
+    .bind(from_currency)
+    .bind(to_currency)
+    .bind(earliest)
+    .bind(latest)
+    .fetch_all(connection)
+    .await
+    .with_context(|| {
+        format!(
+            "Failed to read exchange rates from {} to {}",
+            from_currency, to_currency
+        )
+    })?;
+
+    let mut rates = BTreeMap::new();
+    for row in rows {
+        let date = row
+            .try_get::<NaiveDate, _>("rate_date")
+            .with_context(|| "Failed to parse NaiveDate from column 'rate_date'")?;
+        rates.insert(date, parse_decimal_from_row(&row, "rate")?);
+    }
+
+    Ok(rates)
+}
+
+pub async fn get_setting(connection: &Pool<Sqlite>, key: &str) -> Result<Option<String>> {
+    let value = sqlx::query_scalar::<_, String>("SELECT value FROM settings WHERE key = ?")
+        .bind(key)
+        .fetch_optional(connection)
+        .await
+        .with_context(|| format!("Failed to read setting '{}'", key))?;
+
+    Ok(value)
+}
+
+pub async fn set_setting(
+    key: &str,
+    value: &str,
+    tx: &mut sqlx::Transaction<'_, Sqlite>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO settings (key, value)
+        VALUES (?, ?)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(key)
+    .bind(value)
+    .execute(&mut **tx)
+    .await
+    .with_context(|| format!("Failed to write setting '{}'", key))?;
+
+    Ok(())
+}
+
 pub async fn truncate_tables(connection: &Pool<Sqlite>, clear_assets: bool) -> Result<()> {
     let mut tx = connection.begin().await?;
 
@@ -129,6 +552,7 @@ pub async fn truncate_tables(connection: &Pool<Sqlite>, clear_assets: bool) -> R
         .await?;
 
     if clear_assets {
+        sqlx::query("DELETE FROM prices").execute(&mut *tx).await?;
         sqlx::query("DELETE FROM tickers").execute(&mut *tx).await?;
         sqlx::query("DELETE FROM assets").execute(&mut *tx).await?;
     }
@@ -155,13 +579,34 @@ pub fn parse_f64_from_row(row: &SqliteRow, column: &str) -> Result<f64> {
     Ok(value)
 }
 
+/// Formats a decimal for the TEXT columns that hold money and quantities.
+pub fn decimal_to_db(value: &Decimal) -> String {
+    value.normalize().to_string()
+}
+
+pub fn parse_decimal_str(value: &str) -> Result<Decimal> {
+    Decimal::from_str(value)
+        .or_else(|_| Decimal::from_scientific(value))
+        .with_context(|| format!("Failed to parse Decimal from '{}'", value))
+}
+
 pub fn parse_decimal_from_row(row: &SqliteRow, column: &str) -> Result<Decimal> {
+    if let Ok(value) = row.try_get::<String, _>(column) {
+        return parse_decimal_str(&value)
+            .with_context(|| format!("Failed to parse Decimal from column '{}'", column));
+    }
+
+    // REAL values, e.g. the result of arithmetic in a query
     let value = parse_f64_from_row(row, column)?;
     Decimal::from_f64(value)
         .with_context(|| format!("Failed to convert f64 to Decimal for column '{}'", column))
 }
 
 pub fn parse_datetime_from_row(row: &SqliteRow, column: &str) -> Result<DateTime<Local>> {
+    if let Ok(datetime) = row.try_get::<DateTime<Local>, _>(column) {
+        return Ok(datetime);
+    }
+
     let timestamp: i64 = row
         .try_get(column)
         .with_context(|| format!("Failed to parse timestamp from column '{}'", column))?;
@@ -179,9 +624,30 @@ pub fn parse_transaction_type_from_row(row: &SqliteRow, column: &str) -> Result<
         .with_context(|| format!("Failed to parse TransactionType from column '{}'", column))
 }
 
+/// Parses a `tickers` row joined with the `name` of its asset. The `api` must
+/// be registered in `providers`.
+pub fn parse_ticker(row: &SqliteRow, providers: &ProviderRegistry) -> Result<Ticker> {
+    let api_str = parse_string_from_row(row, "api")?;
+
+    Ok(Ticker::new(
+        parse_i64_from_row(row, "id")?,
+        parse_i64_from_row(row, "asset_id")?,
+        parse_string_from_row(row, "symbol")?,
+        parse_string_from_row(row, "name")?,
+        parse_string_from_row(row, "currency")?,
+        parse_string_from_row(row, "exchange").ok(),
+        parse_decimal_from_row(row, "last_price").ok(),
+        parse_datetime_from_row(row, "last_price_updated_at").ok(),
+        providers.parse_api(&api_str)?,
+    ))
+}
+
 pub fn parse_transaction(row: SqliteRow) -> Result<Transaction> {
     let id = parse_i64_from_row(&row, "id")?;
-    let ticker_id = parse_i64_from_row(&row, "ticker_id")?;
+    let ticker_id = row
+        .try_get::<Option<i64>, _>("ticker_id")
+        .with_context(|| "Failed to parse i64 from column 'ticker_id'")?
+        .unwrap_or_default();
     let transaction_no = parse_i64_from_row(&row, "transaction_no")?;
     let date = parse_datetime_from_row(&row, "transaction_date")?;
     let transaction_type = parse_transaction_type_from_row(&row, "transaction_type")?;
@@ -191,6 +657,8 @@ pub fn parse_transaction(row: SqliteRow) -> Result<Transaction> {
     let quantity = parse_decimal_from_row(&row, "quantity")?;
     let price = parse_decimal_from_row(&row, "price")?;
     let fees = parse_decimal_from_row(&row, "fees")?;
+    let withholding_tax = parse_decimal_from_row(&row, "withholding_tax")?;
+    let domestic_tax = parse_decimal_from_row(&row, "domestic_tax")?;
 
     let cumulative_units = parse_decimal_from_row(&row, "cumulative_units")?;
     let cumulative_cost = parse_decimal_from_row(&row, "cumulative_cost")?;
@@ -213,6 +681,8 @@ pub fn parse_transaction(row: SqliteRow) -> Result<Transaction> {
         quantity,
         price,
         fees,
+        withholding_tax,
+        domestic_tax,
         Some(position_state),
         Some(transaction_gains),
     ))
@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments)]
+
 pub mod api;
 pub mod app;
 pub mod db;
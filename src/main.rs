@@ -1,36 +1,87 @@
 use std::{error::Error, fs, path::Path};
 
-use portfolio_tracker_tui::app::{App, Portfolio};
-use sqlx::{
-    migrate::Migrator,
-    sqlite::{SqliteConnectOptions, SqlitePool},
+use clap::Parser;
+use portfolio_tracker_tui::{
+    app::{
+        App, Cli, Config, Portfolio,
+        export::ExportFormat,
+        sample::{write_sample_fixtures, write_sample_transactions},
+    },
+    db::schema::run_migrations,
 };
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let db_dir = shellexpand::tilde("~/.local/share/portfolio-tracker-tui");
-    fs::create_dir_all(db_dir.as_ref())?;
-    let database_url = format!("{}/portfolio.db", db_dir);
+    let cli = Cli::parse();
+    let config = Config::load(&cli)?;
+    let export_format = cli
+        .export
+        .as_deref()
+        .map(ExportFormat::parse_str)
+        .transpose()?;
+
+    let database_path = config.database_path_expanded();
+    if let Some(db_dir) = Path::new(&database_path).parent() {
+        fs::create_dir_all(db_dir)?;
+    }
     let db_connect_options = SqliteConnectOptions::new()
-        .filename(database_url)
+        .filename(&database_path)
         .create_if_missing(true);
     let connection = SqlitePool::connect_with(db_connect_options).await?;
-    let migrator = Migrator::new(Path::new("./src/db/migrations")).await?;
 
-    migrator.run(&connection).await?;
+    run_migrations(&connection).await?;
+
+    if cli.demo
+        && let Some(fixtures_dir) = config.local_fixtures_dir_expanded()
+    {
+        write_sample_fixtures(Path::new(&fixtures_dir))?;
+    }
+
+    let mut portfolio = Portfolio::from_config(&config, connection)?;
+    portfolio.sync_cost_basis().await?;
+
+    if let Some(path) = &cli.fx_rates {
+        match portfolio.load_fx_rates(&shellexpand::tilde(path)).await {
+            Ok(count) => println!("Stored {} exchange rates", count),
+            Err(e) => eprintln!("Failed to load exchange rates: {:#}", e),
+        }
+    }
 
-    let mut portfolio = Portfolio::new(String::from("EUR"), connection);
+    if cli.backfill {
+        match portfolio.backfill_prices().await {
+            Ok(count) => println!("Stored {} historical prices", count),
+            Err(e) => eprintln!("Failed to backfill prices: {:#}", e),
+        }
+    }
 
+    if *config.refresh().update_prices_on_startup()
+        && let Err(e) = portfolio.update_prices().await
+    {
+        eprintln!("Failed to update prices: {:#}", e);
+    }
     portfolio.set_positions().await?;
 
+    let export_directory = config.export_directory_expanded();
+    if let Some(format) = export_format {
+        for path in portfolio
+            .export(Path::new(&export_directory), format)
+            .await?
+        {
+            println!("Wrote {}", path.display());
+        }
+        return Ok(());
+    }
+
     let mut app = App::new(portfolio);
-    let csv_path = shellexpand::tilde("~/.config/portfolio-tracker-tui/transactions.csv");
-    let config_dir = shellexpand::tilde("~/.config/portfolio-tracker-tui");
-    if !fs::exists(&*csv_path)? {
-        fs::create_dir(&*config_dir)?;
-        fs::copy("./sample_data/transactions.csv", &*csv_path)?;
+    app.set_refresh_interval(config.refresh_interval());
+    app.set_export_directory(export_directory);
+
+    let csv_paths = config.transaction_files_expanded();
+    if let Some(csv_path) = csv_paths.first() {
+        write_sample_transactions(Path::new(csv_path))?;
     }
-    app.run(&csv_path).await?;
+    app.run(&csv_paths).await?;
 
     Ok(())
 }
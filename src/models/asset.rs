@@ -20,6 +20,7 @@ pub enum AssetType {
     MutualFund,
     Crypto,
     PreciousMetals,
+    Cash,
     Other,
 }
 
@@ -32,6 +33,7 @@ impl AssetType {
             "MutualFund" => Ok(AssetType::MutualFund),
             "Crypto" => Ok(AssetType::Crypto),
             "PreciousMetals" => Ok(AssetType::PreciousMetals),
+            "Cash" => Ok(AssetType::Cash),
             "Other" => Ok(AssetType::Other),
             _ => Err(anyhow::anyhow!("Unknown asset type")),
         }
@@ -45,6 +47,7 @@ impl AssetType {
             AssetType::MutualFund => "MutualFund",
             AssetType::Crypto => "Crypto",
             AssetType::PreciousMetals => "PreciousMetals",
+            AssetType::Cash => "Cash",
             AssetType::Other => "Other",
         }
     }
@@ -0,0 +1,13 @@
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+/// Cash held at one broker in one currency. `income` is interest minus
+/// account fees.
+#[derive(Clone, Debug, Getters, new)]
+pub struct CashBalance {
+    broker: String,
+    currency: String,
+    balance: Decimal,
+    income: Decimal,
+}
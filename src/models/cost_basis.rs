@@ -0,0 +1,31 @@
+use anyhow::Result;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CostBasisMethod {
+    #[default]
+    Fifo,
+    Lifo,
+    Average,
+    Hifo,
+}
+
+impl CostBasisMethod {
+    pub fn parse_str(s: &str) -> Result<CostBasisMethod> {
+        match s.to_uppercase().as_str() {
+            "FIFO" => Ok(CostBasisMethod::Fifo),
+            "LIFO" => Ok(CostBasisMethod::Lifo),
+            "AVERAGE" | "AVG" => Ok(CostBasisMethod::Average),
+            "HIFO" => Ok(CostBasisMethod::Hifo),
+            _ => Err(anyhow::anyhow!("Unknown cost basis method")),
+        }
+    }
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            CostBasisMethod::Fifo => "FIFO",
+            CostBasisMethod::Lifo => "LIFO",
+            CostBasisMethod::Average => "Average",
+            CostBasisMethod::Hifo => "HIFO",
+        }
+    }
+}
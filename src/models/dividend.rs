@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+/// Dividends in the base currency. `reclaimable` is the part of the foreign
+/// withholding tax above the treaty rate of the source country.
+#[derive(Clone, Debug, Default, Getters, new, PartialEq)]
+pub struct DividendBreakdown {
+    gross: Decimal,
+    withholding_tax: Decimal,
+    domestic_tax: Decimal,
+    reclaimable: Decimal,
+    net: Decimal,
+}
+
+impl DividendBreakdown {
+    pub fn add(&mut self, other: &DividendBreakdown) {
+        self.gross += other.gross;
+        self.withholding_tax += other.withholding_tax;
+        self.domestic_tax += other.domestic_tax;
+        self.reclaimable += other.reclaimable;
+        self.net += other.net;
+    }
+}
+
+#[derive(Clone, Debug, Default, Getters)]
+pub struct DividendReport {
+    by_position: BTreeMap<String, DividendBreakdown>,
+    by_year: BTreeMap<i32, DividendBreakdown>,
+    by_country: BTreeMap<String, DividendBreakdown>,
+    total: DividendBreakdown,
+}
+
+impl DividendReport {
+    pub fn add(&mut self, name: &str, year: i32, country: &str, dividend: &DividendBreakdown) {
+        self.by_position
+            .entry(name.to_string())
+            .or_default()
+            .add(dividend);
+        self.by_year.entry(year).or_default().add(dividend);
+        self.by_country
+            .entry(country.to_string())
+            .or_default()
+            .add(dividend);
+        self.total.add(dividend);
+    }
+}
@@ -0,0 +1,14 @@
+use chrono::NaiveDate;
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+/// Units of `to_currency` for one unit of `from_currency` on `date`.
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct FxRate {
+    from_currency: String,
+    to_currency: String,
+    date: NaiveDate,
+    rate: Decimal,
+    source: String,
+}
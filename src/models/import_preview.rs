@@ -0,0 +1,58 @@
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+use super::Transaction;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportStatus {
+    New,
+    Changed,
+    Unchanged,
+}
+
+impl ImportStatus {
+    pub fn to_str(&self) -> &str {
+        match self {
+            ImportStatus::New => "New",
+            ImportStatus::Changed => "Changed",
+            ImportStatus::Unchanged => "Skipped",
+        }
+    }
+}
+
+/// A parsed CSV record and how it compares with the stored transaction of the
+/// same number. `record` counts from 1 within its file.
+#[derive(Clone, Debug, Getters, new)]
+pub struct ImportRow {
+    record: usize,
+    symbol: String,
+    status: ImportStatus,
+    transaction: Transaction,
+}
+
+/// Units and cost of a ticker at a broker before and after an import.
+#[derive(Clone, Debug, Getters, new)]
+pub struct PositionChange {
+    symbol: String,
+    broker: String,
+    units_before: Decimal,
+    units_after: Decimal,
+    cost_before: Decimal,
+    cost_after: Decimal,
+}
+
+/// The outcome of an import, computed without writing in a dry run. Records
+/// that fail to parse or resolve are listed in `errors` instead of `rows`.
+#[derive(Clone, Debug, Default, Getters, new)]
+pub struct ImportPreview {
+    rows: Vec<ImportRow>,
+    position_changes: Vec<PositionChange>,
+    errors: Vec<String>,
+}
+
+impl ImportPreview {
+    pub fn count(&self, status: ImportStatus) -> usize {
+        self.rows.iter().filter(|row| row.status == status).count()
+    }
+}
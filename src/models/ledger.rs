@@ -0,0 +1,137 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use derive_getters::Getters;
+use derive_new::new;
+
+use super::{Transaction, TransactionType};
+
+/// Restricts the transaction ledger. Written as `key:value` terms, e.g.
+/// `symbol:TSLA broker:IBKR type:Buy from:2025-01-01 to:2025-06-30`; a bare
+/// term filters by symbol.
+#[derive(Clone, Debug, Default, Getters, PartialEq)]
+pub struct LedgerFilter {
+    symbol: Option<String>,
+    broker: Option<String>,
+    transaction_type: Option<TransactionType>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+impl LedgerFilter {
+    pub fn parse_str(s: &str) -> Result<LedgerFilter> {
+        let mut filter = LedgerFilter::default();
+
+        for term in s.split_whitespace() {
+            let (key, value) = term.split_once(':').unwrap_or(("symbol", term));
+            if value.is_empty() {
+                return Err(anyhow::anyhow!("Missing value in filter '{}'", term));
+            }
+
+            match key.to_lowercase().as_str() {
+                "symbol" => filter.symbol = Some(value.to_uppercase()),
+                "broker" => filter.broker = Some(value.to_string()),
+                "type" => {
+                    filter.transaction_type = Some(
+                        TransactionType::parse_str(value)
+                            .with_context(|| format!("Invalid type in filter '{}'", term))?,
+                    )
+                }
+                "from" => filter.from = Some(parse_filter_date(value, term)?),
+                "to" => filter.to = Some(parse_filter_date(value, term)?),
+                _ => return Err(anyhow::anyhow!("Unknown filter '{}'", key)),
+            }
+        }
+
+        Ok(filter)
+    }
+
+    pub fn to_str(&self) -> String {
+        let mut terms = Vec::new();
+        if let Some(symbol) = &self.symbol {
+            terms.push(format!("symbol:{}", symbol));
+        }
+        if let Some(broker) = &self.broker {
+            terms.push(format!("broker:{}", broker));
+        }
+        if let Some(transaction_type) = &self.transaction_type {
+            terms.push(format!("type:{}", transaction_type.to_str()));
+        }
+        if let Some(from) = &self.from {
+            terms.push(format!("from:{}", from));
+        }
+        if let Some(to) = &self.to {
+            terms.push(format!("to:{}", to));
+        }
+        terms.join(" ")
+    }
+}
+
+fn parse_filter_date(value: &str, term: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .with_context(|| format!("Invalid date in filter '{}', expected YYYY-MM-DD", term))
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LedgerSort {
+    #[default]
+    Date,
+    Symbol,
+    Type,
+    Broker,
+    Quantity,
+}
+
+impl LedgerSort {
+    pub const ALL: [LedgerSort; 5] = [
+        LedgerSort::Date,
+        LedgerSort::Symbol,
+        LedgerSort::Type,
+        LedgerSort::Broker,
+        LedgerSort::Quantity,
+    ];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            LedgerSort::Date => "Date",
+            LedgerSort::Symbol => "Symbol",
+            LedgerSort::Type => "Type",
+            LedgerSort::Broker => "Broker",
+            LedgerSort::Quantity => "Quantity",
+        }
+    }
+
+    pub fn next(&self) -> LedgerSort {
+        let i = Self::ALL.iter().position(|s| s == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+/// A transaction with the symbol of its ticker, empty for cash transactions,
+/// the ISIN of its asset as alternative symbol and the id of the statement
+/// entry it was imported from.
+#[derive(Clone, Debug, Getters, new)]
+pub struct LedgerEntry {
+    symbol: String,
+    alternative_symbol: Option<String>,
+    external_id: Option<String>,
+    transaction: Transaction,
+}
+
+/// One page of the filtered and sorted ledger. `total` counts the matching
+/// transactions across all pages.
+#[derive(Clone, Debug, Default, Getters, new)]
+pub struct LedgerPage {
+    entries: Vec<LedgerEntry>,
+    page: i64,
+    page_size: i64,
+    total: i64,
+}
+
+impl LedgerPage {
+    pub fn page_count(&self) -> i64 {
+        if self.page_size <= 0 {
+            return 1;
+        }
+        ((self.total + self.page_size - 1) / self.page_size).max(1)
+    }
+}
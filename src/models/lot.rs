@@ -0,0 +1,46 @@
+use chrono::{DateTime, Local};
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+/// Units bought in one transaction that are still held. `cost` is the total
+/// cost of the remaining units in the base currency, including fees.
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct Lot {
+    transaction_no: i64,
+    date: DateTime<Local>,
+    quantity: Decimal,
+    cost: Decimal,
+}
+
+impl Lot {
+    pub fn unit_cost(&self) -> Decimal {
+        if self.quantity == Decimal::ZERO {
+            Decimal::ZERO
+        } else {
+            self.cost / self.quantity
+        }
+    }
+
+    /// Splits off `quantity` units, returning them as a new lot with the
+    /// proportional share of the cost. Taking the whole lot keeps its exact cost.
+    pub fn split_off(&mut self, quantity: Decimal) -> Lot {
+        let taken_cost = if quantity >= self.quantity {
+            self.cost
+        } else {
+            self.cost * quantity / self.quantity
+        };
+        let taken_quantity = quantity.min(self.quantity);
+
+        self.quantity -= taken_quantity;
+        self.cost -= taken_cost;
+
+        Lot::new(self.transaction_no, self.date, taken_quantity, taken_cost)
+    }
+
+    /// Rescales the units by `ratio` (new units per old unit); the cost stays
+    /// the same, so the unit cost is divided by `ratio`.
+    pub fn split(&mut self, ratio: Decimal) {
+        self.quantity *= ratio;
+    }
+}
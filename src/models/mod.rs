@@ -1,13 +1,37 @@
 pub mod asset;
+pub mod cash_balance;
+pub mod cost_basis;
+pub mod dividend;
+pub mod fx_rate;
+pub mod import_preview;
+pub mod ledger;
+pub mod lot;
+pub mod performance;
 pub mod position;
+pub mod position_detail;
 pub mod position_state;
+pub mod quote;
 pub mod ticker;
 pub mod transaction;
 pub mod transaction_gains;
+pub mod transaction_input;
+pub mod value_history;
 
 pub use asset::{Asset, AssetType};
+pub use cash_balance::CashBalance;
+pub use cost_basis::CostBasisMethod;
+pub use dividend::{DividendBreakdown, DividendReport};
+pub use fx_rate::FxRate;
+pub use import_preview::{ImportPreview, ImportRow, ImportStatus, PositionChange};
+pub use ledger::{LedgerEntry, LedgerFilter, LedgerPage, LedgerSort};
+pub use lot::Lot;
+pub use performance::{Performance, PerformancePoint, PerformanceReport, ReturnPeriod};
 pub use position::Position;
+pub use position_detail::{LotDetail, PositionDetail};
 pub use position_state::PositionState;
+pub use quote::Quote;
 pub use ticker::Ticker;
-pub use transaction::{Transaction, TransactionType};
+pub use transaction::{Transaction, TransactionSource, TransactionType};
 pub use transaction_gains::TransactionGains;
+pub use transaction_input::TransactionInput;
+pub use value_history::{PositionHistory, ValueHistory, ValuePoint};
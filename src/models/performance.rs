@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Months, NaiveDate};
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ReturnPeriod {
+    Ytd,
+    OneYear,
+    ThreeYears,
+    #[default]
+    SinceInception,
+}
+
+impl ReturnPeriod {
+    pub const ALL: [ReturnPeriod; 4] = [
+        ReturnPeriod::Ytd,
+        ReturnPeriod::OneYear,
+        ReturnPeriod::ThreeYears,
+        ReturnPeriod::SinceInception,
+    ];
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            ReturnPeriod::Ytd => "YTD",
+            ReturnPeriod::OneYear => "1Y",
+            ReturnPeriod::ThreeYears => "3Y",
+            ReturnPeriod::SinceInception => "Since inception",
+        }
+    }
+
+    /// The valuation date the period starts from: transactions on that day
+    /// are part of the starting value. Never earlier than the day before the
+    /// first transaction.
+    pub fn start_date(&self, today: NaiveDate, inception: NaiveDate) -> NaiveDate {
+        let before_inception = inception.pred_opt().unwrap_or(inception);
+        let start = match self {
+            ReturnPeriod::Ytd => NaiveDate::from_ymd_opt(today.year(), 1, 1)
+                .and_then(|date| date.pred_opt())
+                .unwrap_or(today),
+            ReturnPeriod::OneYear => today.checked_sub_months(Months::new(12)).unwrap_or(today),
+            ReturnPeriod::ThreeYears => today.checked_sub_months(Months::new(36)).unwrap_or(today),
+            ReturnPeriod::SinceInception => before_inception,
+        };
+        start.max(before_inception)
+    }
+
+    pub fn next(&self) -> ReturnPeriod {
+        let i = Self::ALL.iter().position(|p| p == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(&self) -> ReturnPeriod {
+        let i = Self::ALL.iter().position(|p| p == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// The market value in the base currency at the end of `date` and the net
+/// cash returned to the investor on that day (negative for purchases).
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct PerformancePoint {
+    date: NaiveDate,
+    value: Decimal,
+    cash_flow: Decimal,
+}
+
+#[derive(Clone, Debug, Default, Getters, new, PartialEq)]
+pub struct Performance {
+    twr: Option<Decimal>,
+    xirr: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, Default, Getters, new)]
+pub struct PerformanceReport {
+    period: ReturnPeriod,
+    portfolio: Performance,
+    by_position: BTreeMap<String, Performance>,
+}
@@ -0,0 +1,26 @@
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+use super::{Asset, Lot, Ticker, Transaction};
+
+/// An open lot valued at the current price, in the base currency.
+#[derive(Clone, Debug, Getters, new)]
+pub struct LotDetail {
+    symbol: String,
+    broker: String,
+    lot: Lot,
+    market_value: Decimal,
+    unrealized_gain: Decimal,
+}
+
+/// Everything recorded for one asset, across its tickers and brokers.
+/// `cost_basis` describes the method the lots were matched with.
+#[derive(Clone, Debug, Getters, new)]
+pub struct PositionDetail {
+    asset: Asset,
+    tickers: Vec<Ticker>,
+    transactions: Vec<Transaction>,
+    cost_basis: String,
+    lots: Vec<LotDetail>,
+}
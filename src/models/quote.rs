@@ -0,0 +1,12 @@
+use chrono::{DateTime, Local};
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+#[derive(Clone, Debug, Getters, new)]
+pub struct Quote {
+    symbol: String,
+    date: DateTime<Local>,
+    close: Decimal,
+    adj_close: Decimal,
+}
@@ -3,7 +3,6 @@ use chrono::{DateTime, Local};
 use derive_getters::Getters;
 use derive_new::new;
 use rust_decimal::Decimal;
-use strum_macros::EnumIter;
 
 #[derive(Clone, Debug, Getters, new)]
 pub struct Ticker {
@@ -25,20 +24,25 @@ impl Ticker {
     }
 }
 
-#[derive(Clone, Debug, EnumIter, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ApiProvider {
     AlphaVantage,
     Fmp,
     Marketstack,
+    Local,
+    Custom(String),
 }
 
 impl ApiProvider {
+    /// Parses the name of a built-in provider. Custom providers are resolved
+    /// with [`crate::api::provider::ProviderRegistry::parse_api`].
     pub fn parse_str(s: &str) -> Result<ApiProvider> {
         match s {
             "Alpha Vantage" => Ok(ApiProvider::AlphaVantage),
             "Financial Modeling Prep" => Ok(ApiProvider::Fmp),
             "Marketstack" => Ok(ApiProvider::Marketstack),
-            _ => Err(anyhow::anyhow!("Unknown API provider")),
+            "Local" => Ok(ApiProvider::Local),
+            _ => Err(anyhow::anyhow!("Unknown API provider '{}'", s)),
         }
     }
 
@@ -47,6 +51,8 @@ impl ApiProvider {
             ApiProvider::AlphaVantage => "Alpha Vantage",
             ApiProvider::Fmp => "Financial Modeling Prep",
             ApiProvider::Marketstack => "Marketstack",
+            ApiProvider::Local => "Local",
+            ApiProvider::Custom(name) => name,
         }
     }
 }
@@ -20,6 +20,8 @@ pub struct Transaction {
     quantity: Decimal,
     price: Decimal,
     fees: Decimal,
+    withholding_tax: Decimal,
+    domestic_tax: Decimal,
     position_state: Option<PositionState>,
     transaction_gains: Option<TransactionGains>,
 }
@@ -27,21 +29,52 @@ pub struct Transaction {
 impl Transaction {
     pub fn get_amount(&self) -> Decimal {
         let amount = self.price * (dec!(1) / self.exchange_rate) * self.quantity;
-        if self.transaction_type == TransactionType::Buy {
+        if self.transaction_type.is_debit() {
             -amount - self.fees
         } else {
-            amount - self.fees
+            amount - self.fees - self.get_taxes()
         }
     }
 
-    pub fn get_quantity(&self) -> Decimal {
-        if self.transaction_type == TransactionType::Buy {
-            self.quantity
+    /// Taxes withheld at source and domestically, in the base currency.
+    pub fn get_taxes(&self) -> Decimal {
+        (self.withholding_tax + self.domestic_tax) * (dec!(1) / self.exchange_rate)
+    }
+
+    /// Cash booked to the broker account in `currency`, positive when money
+    /// comes in. Fees are given in the base currency and converted back.
+    pub fn get_cash_flow(&self) -> Decimal {
+        let fees = self.fees * self.exchange_rate;
+        if !self.transaction_type.has_cash_flow() {
+            return -fees;
+        }
+
+        let amount = self.price * self.quantity;
+        if self.transaction_type.is_debit() {
+            -amount - fees
         } else {
-            -self.quantity
+            amount - fees - self.withholding_tax - self.domestic_tax
         }
     }
 
+    /// Change in units held. Splits rescale the lots instead, see
+    /// [`crate::app::calc::TickerLots::apply`].
+    pub fn get_quantity(&self) -> Decimal {
+        match self.transaction_type {
+            TransactionType::Buy | TransactionType::TransferIn => self.quantity,
+            TransactionType::Sell | TransactionType::TransferOut => -self.quantity,
+            _ => Decimal::ZERO,
+        }
+    }
+
+    pub fn set_id(&mut self, id: i64) {
+        self.id = id;
+    }
+
+    pub fn set_ticker_id(&mut self, ticker_id: i64) {
+        self.ticker_id = ticker_id;
+    }
+
     pub fn set_position_state(&mut self, position_state: Option<PositionState>) {
         self.position_state = position_state;
     }
@@ -56,23 +89,125 @@ pub enum TransactionType {
     Buy,
     Sell,
     Div,
+    /// `quantity` holds the split ratio: new units per old unit.
+    Split,
+    /// Moves units with their lots to another broker, matched with the next
+    /// `TransferIn` of the same ticker.
+    TransferOut,
+    /// Receives the units in transit, or opens a lot at `price` when there
+    /// are none.
+    TransferIn,
+    Deposit,
+    Withdrawal,
+    Interest,
+    Fee,
 }
 
 impl TransactionType {
+    pub const ALL: [TransactionType; 10] = [
+        TransactionType::Buy,
+        TransactionType::Sell,
+        TransactionType::Div,
+        TransactionType::Split,
+        TransactionType::TransferOut,
+        TransactionType::TransferIn,
+        TransactionType::Deposit,
+        TransactionType::Withdrawal,
+        TransactionType::Interest,
+        TransactionType::Fee,
+    ];
+
     pub fn parse_str(s: &str) -> Result<TransactionType> {
         match s {
             "Buy" => Ok(TransactionType::Buy),
             "Sell" => Ok(TransactionType::Sell),
             "Div" => Ok(TransactionType::Div),
+            "Split" => Ok(TransactionType::Split),
+            "TransferOut" => Ok(TransactionType::TransferOut),
+            "TransferIn" => Ok(TransactionType::TransferIn),
+            "Deposit" => Ok(TransactionType::Deposit),
+            "Withdrawal" => Ok(TransactionType::Withdrawal),
+            "Interest" => Ok(TransactionType::Interest),
+            "Fee" => Ok(TransactionType::Fee),
             _ => Err(anyhow::anyhow!("Unknown transaction type")),
         }
     }
 
+    /// Whether the transaction moves money; price and fees are optional otherwise.
+    pub fn has_cash_flow(&self) -> bool {
+        !matches!(
+            self,
+            TransactionType::Split | TransactionType::TransferOut | TransactionType::TransferIn
+        )
+    }
+
+    /// Whether money leaves the broker account.
+    pub fn is_debit(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Buy | TransactionType::Withdrawal | TransactionType::Fee
+        )
+    }
+
+    /// Cash account transactions, which have no ticker.
+    pub fn is_cash(&self) -> bool {
+        matches!(
+            self,
+            TransactionType::Deposit
+                | TransactionType::Withdrawal
+                | TransactionType::Interest
+                | TransactionType::Fee
+        )
+    }
+
+    pub fn next(&self) -> TransactionType {
+        let i = Self::ALL.iter().position(|t| t == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()].clone()
+    }
+
+    pub fn previous(&self) -> TransactionType {
+        let i = Self::ALL.iter().position(|t| t == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()].clone()
+    }
+
     pub fn to_str(&self) -> &str {
         match self {
             TransactionType::Buy => "Buy",
             TransactionType::Sell => "Sell",
             TransactionType::Div => "Div",
+            TransactionType::Split => "Split",
+            TransactionType::TransferOut => "TransferOut",
+            TransactionType::TransferIn => "TransferIn",
+            TransactionType::Deposit => "Deposit",
+            TransactionType::Withdrawal => "Withdrawal",
+            TransactionType::Interest => "Interest",
+            TransactionType::Fee => "Fee",
+        }
+    }
+}
+
+/// Where a stored transaction came from. Imports match their records with
+/// stored transactions by number, so they must not replace those entered in
+/// the app.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransactionSource {
+    Import,
+    App,
+}
+
+impl TransactionSource {
+    pub fn parse_str(s: &str) -> Result<TransactionSource> {
+        match s {
+            "Import" => Ok(TransactionSource::Import),
+            "App" => Ok(TransactionSource::App),
+            _ => Err(anyhow::anyhow!("Unknown transaction source '{}'", s)),
+        }
+    }
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            TransactionSource::Import => "Import",
+            TransactionSource::App => "App",
         }
     }
 }
@@ -0,0 +1,21 @@
+use chrono::{DateTime, Local};
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+use super::TransactionType;
+
+/// A transaction entered in the TUI, before its ticker is resolved and its
+/// position state computed. `price` is given in `currency`, which defaults to
+/// the ticker currency when empty; `fees` are in the base currency.
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct TransactionInput {
+    date: DateTime<Local>,
+    transaction_type: TransactionType,
+    symbol: String,
+    broker: String,
+    currency: String,
+    quantity: Decimal,
+    price: Decimal,
+    fees: Decimal,
+}
@@ -0,0 +1,33 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use derive_getters::Getters;
+use derive_new::new;
+use rust_decimal::Decimal;
+
+use super::ReturnPeriod;
+
+/// Market value and cost basis of the holdings at the end of `date`, both in
+/// the base currency.
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct ValuePoint {
+    date: NaiveDate,
+    market_value: Decimal,
+    invested: Decimal,
+}
+
+/// Daily values of an asset, summed over its tickers.
+#[derive(Clone, Debug, Getters, new, PartialEq)]
+pub struct PositionHistory {
+    name: String,
+    points: Vec<ValuePoint>,
+}
+
+/// Daily values of the portfolio and of each position, by asset id, over
+/// `period`.
+#[derive(Clone, Debug, Default, Getters, new)]
+pub struct ValueHistory {
+    period: ReturnPeriod,
+    portfolio: Vec<ValuePoint>,
+    by_position: BTreeMap<i64, PositionHistory>,
+}
@@ -1,40 +1,348 @@
 #[cfg(test)]
 mod tests {
+    use chrono::{Local, NaiveDate, TimeZone};
     use rust_decimal::Decimal;
     use rust_decimal_macros::dec;
 
-    use crate::app::calc::calculate_position_state;
+    use crate::{
+        app::{
+            CostBasisConfig,
+            calc::{
+                calculate_cost_by_date, calculate_lots, calculate_position_state, calculate_twr,
+                calculate_units_by_date, calculate_xirr,
+            },
+            utils::parse_split_ratio,
+        },
+        models::{CostBasisMethod, PerformancePoint, Transaction, TransactionType},
+    };
 
-    fn set_sample_data() -> (Vec<Decimal>, Vec<Decimal>) {
-        let amounts: Vec<Decimal> = vec![
-            dec!(-1777.02),
-            dec!(-1659.08),
-            dec!(-2190.06),
-            dec!(-1768.21),
-            dec!(-1612.08),
-            dec!(2275.64),
-        ];
-        let quantities: Vec<Decimal> = vec![
-            dec!(20.00),
-            dec!(20.00),
-            dec!(20.00),
-            dec!(20.00),
-            dec!(20.00),
-            dec!(-20.00),
-        ];
+    fn transaction(
+        transaction_no: i64,
+        transaction_type: TransactionType,
+        quantity: Decimal,
+        price: Decimal,
+    ) -> Transaction {
+        transaction_at("Broker", transaction_no, transaction_type, quantity, price)
+    }
 
-        (amounts, quantities)
+    fn transaction_at(
+        broker: &str,
+        transaction_no: i64,
+        transaction_type: TransactionType,
+        quantity: Decimal,
+        price: Decimal,
+    ) -> Transaction {
+        Transaction::new(
+            0,
+            1,
+            transaction_no,
+            Local
+                .with_ymd_and_hms(2024, 1, transaction_no as u32, 0, 0, 0)
+                .unwrap(),
+            transaction_type,
+            String::from(broker),
+            String::from("USD"),
+            dec!(1),
+            quantity,
+            price,
+            dec!(0),
+            dec!(0),
+            dec!(0),
+            None,
+            None,
+        )
+    }
+
+    fn set_sample_data() -> Vec<Transaction> {
+        vec![
+            transaction(1, TransactionType::Buy, dec!(20.00), dec!(88.851)),
+            transaction(2, TransactionType::Buy, dec!(20.00), dec!(82.954)),
+            transaction(3, TransactionType::Buy, dec!(20.00), dec!(109.503)),
+            transaction(4, TransactionType::Buy, dec!(20.00), dec!(88.4105)),
+            transaction(5, TransactionType::Buy, dec!(20.00), dec!(80.604)),
+            transaction(6, TransactionType::Sell, dec!(20.00), dec!(113.782)),
+        ]
     }
 
     #[test]
     fn fifo_works() {
-        let (amounts, quantities) = set_sample_data();
-        let result = calculate_position_state(amounts, quantities).unwrap();
-
-        // println!("Result: {:#?}", result);
+        let transactions = set_sample_data();
+        let result =
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo))
+                .unwrap();
 
         assert_eq!(result.cumulative_units().normalize(), dec!(80.0));
         assert_eq!(result.cumulative_cost().normalize(), dec!(7229.43));
         assert_eq!(result.cost_of_units_sold().normalize(), dec!(1777.02));
     }
+
+    #[test]
+    fn fractional_lots_are_split() {
+        let transactions = vec![
+            transaction(1, TransactionType::Buy, dec!(0.5), dec!(100)),
+            transaction(2, TransactionType::Buy, dec!(1.25), dec!(120)),
+            transaction(3, TransactionType::Div, dec!(1.75), dec!(0.4)),
+            transaction(4, TransactionType::Sell, dec!(0.75), dec!(130)),
+        ];
+        let result =
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo))
+                .unwrap();
+
+        assert_eq!(result.cumulative_units().normalize(), dec!(1));
+        assert_eq!(result.cumulative_cost().normalize(), dec!(120));
+        assert_eq!(result.cost_of_units_sold().normalize(), dec!(80));
+
+        let ticker_lots =
+            calculate_lots(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo)).unwrap();
+        let lots = ticker_lots.queue("Broker").unwrap();
+        assert_eq!(lots.lots().len(), 1);
+        assert_eq!(*lots.lots()[0].transaction_no(), 2);
+        assert_eq!(lots.lots()[0].unit_cost().normalize(), dec!(120));
+    }
+
+    #[test]
+    fn selling_everything_closes_position() {
+        let transactions = vec![
+            transaction(1, TransactionType::Buy, dec!(0.333333), dec!(30)),
+            transaction(2, TransactionType::Buy, dec!(0.666667), dec!(30)),
+            transaction(3, TransactionType::Sell, dec!(1), dec!(40)),
+        ];
+        let result =
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo))
+                .unwrap();
+
+        assert_eq!(result.cumulative_units().normalize(), dec!(0));
+        assert_eq!(result.cumulative_cost().normalize(), dec!(0));
+        assert_eq!(result.cost_of_units_sold().normalize(), dec!(30));
+    }
+
+    #[test]
+    fn overselling_fails() {
+        let transactions = vec![
+            transaction(1, TransactionType::Buy, dec!(1.5), dec!(10)),
+            transaction(2, TransactionType::Sell, dec!(2), dec!(10)),
+        ];
+        assert!(
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn lifo_works() {
+        let transactions = set_sample_data();
+        let result =
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Lifo))
+                .unwrap();
+
+        assert_eq!(result.cumulative_units().normalize(), dec!(80));
+        assert_eq!(result.cumulative_cost().normalize(), dec!(7394.37));
+        assert_eq!(result.cost_of_units_sold().normalize(), dec!(1612.08));
+    }
+
+    #[test]
+    fn hifo_works() {
+        let transactions = set_sample_data();
+        let result =
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Hifo))
+                .unwrap();
+
+        assert_eq!(result.cumulative_units().normalize(), dec!(80));
+        assert_eq!(result.cumulative_cost().normalize(), dec!(6816.39));
+        assert_eq!(result.cost_of_units_sold().normalize(), dec!(2190.06));
+    }
+
+    #[test]
+    fn average_cost_works() {
+        let transactions = set_sample_data();
+        let result = calculate_position_state(
+            &transactions,
+            &CostBasisConfig::new(CostBasisMethod::Average),
+        )
+        .unwrap();
+
+        assert_eq!(result.cumulative_units().normalize(), dec!(80));
+        assert_eq!(result.cumulative_cost().round_dp(2), dec!(7205.16));
+        assert_eq!(result.cost_of_units_sold().round_dp(2), dec!(1801.29));
+
+        let ticker_lots = calculate_lots(
+            &transactions,
+            &CostBasisConfig::new(CostBasisMethod::Average),
+        )
+        .unwrap();
+        let lots = ticker_lots.queue("Broker").unwrap();
+        assert_eq!(lots.lots().len(), 5);
+        assert_eq!(lots.lots()[0].quantity().normalize(), dec!(16));
+    }
+
+    #[test]
+    fn splits_rescale_lots() {
+        let transactions = vec![
+            transaction(1, TransactionType::Buy, dec!(10), dec!(400)),
+            transaction(2, TransactionType::Buy, dec!(5), dec!(460)),
+            transaction(3, TransactionType::Split, dec!(4), dec!(0)),
+            transaction(4, TransactionType::Sell, dec!(50), dec!(120)),
+        ];
+        let result = calculate_position_state(
+            &transactions[..3],
+            &CostBasisConfig::new(CostBasisMethod::Fifo),
+        )
+        .unwrap();
+
+        assert_eq!(result.cumulative_units().normalize(), dec!(60));
+        assert_eq!(result.cumulative_cost().normalize(), dec!(6300));
+        assert_eq!(result.cost_of_units_sold().normalize(), dec!(0));
+
+        let ticker_lots =
+            calculate_lots(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo)).unwrap();
+        let lots = ticker_lots.queue("Broker").unwrap();
+        assert_eq!(lots.units().normalize(), dec!(10));
+        assert_eq!(lots.lots()[0].unit_cost().normalize(), dec!(115));
+
+        let result =
+            calculate_position_state(&transactions, &CostBasisConfig::new(CostBasisMethod::Fifo))
+                .unwrap();
+        assert_eq!(result.cost_of_units_sold().normalize(), dec!(5150));
+    }
+
+    #[test]
+    fn split_ratios_parse() {
+        assert_eq!(parse_split_ratio("4:1").unwrap(), dec!(4));
+        assert_eq!(parse_split_ratio("1:10").unwrap(), dec!(0.1));
+        assert_eq!(parse_split_ratio("1.5").unwrap(), dec!(1.5));
+        assert!(parse_split_ratio("0:1").is_err());
+        assert!(parse_split_ratio("1:0").is_err());
+    }
+
+    #[test]
+    fn transfers_keep_cost_basis_and_dates() {
+        let transactions = vec![
+            transaction_at("A", 1, TransactionType::Buy, dec!(10), dec!(100)),
+            transaction_at("A", 2, TransactionType::Buy, dec!(10), dec!(200)),
+            transaction_at("B", 3, TransactionType::Buy, dec!(5), dec!(150)),
+            transaction_at("A", 4, TransactionType::TransferOut, dec!(15), dec!(0)),
+            transaction_at("B", 5, TransactionType::TransferIn, dec!(15), dec!(0)),
+            transaction_at("B", 6, TransactionType::Sell, dec!(12), dec!(300)),
+        ];
+        let cost_basis = CostBasisConfig::new(CostBasisMethod::Fifo);
+
+        let transfer_out = calculate_position_state(&transactions[..4], &cost_basis).unwrap();
+        assert_eq!(transfer_out.cumulative_units().normalize(), dec!(5));
+        assert_eq!(transfer_out.cumulative_cost().normalize(), dec!(1000));
+        assert_eq!(transfer_out.cost_of_units_sold().normalize(), dec!(0));
+
+        let transfer_in = calculate_position_state(&transactions[..5], &cost_basis).unwrap();
+        assert_eq!(transfer_in.cumulative_units().normalize(), dec!(20));
+        assert_eq!(transfer_in.cumulative_cost().normalize(), dec!(2750));
+
+        // The transferred lots are older than the lot bought at B
+        let sale = calculate_position_state(&transactions, &cost_basis).unwrap();
+        assert_eq!(sale.cost_of_units_sold().normalize(), dec!(1400));
+
+        let ticker_lots = calculate_lots(&transactions, &cost_basis).unwrap();
+        assert!(ticker_lots.in_transit().is_empty());
+        let lots = ticker_lots.queue("B").unwrap();
+        assert_eq!(*lots.lots()[0].transaction_no(), 2);
+        assert_eq!(lots.lots()[0].quantity().normalize(), dec!(3));
+        assert_eq!(*lots.lots()[1].transaction_no(), 3);
+    }
+
+    #[test]
+    fn transfer_in_without_transfer_out_opens_a_lot() {
+        let transactions = vec![
+            transaction_at("A", 1, TransactionType::Buy, dec!(10), dec!(100)),
+            transaction_at("B", 2, TransactionType::TransferIn, dec!(5), dec!(120)),
+        ];
+        let cost_basis = CostBasisConfig::new(CostBasisMethod::Fifo);
+
+        let ticker_lots = calculate_lots(&transactions, &cost_basis).unwrap();
+        let lots = ticker_lots.queue("B").unwrap();
+        assert_eq!(*lots.lots()[0].transaction_no(), 2);
+        assert_eq!(lots.lots()[0].cost().normalize(), dec!(600));
+    }
+
+    #[test]
+    fn transfer_in_of_more_than_in_transit_fails() {
+        let transactions = vec![
+            transaction_at("A", 1, TransactionType::Buy, dec!(10), dec!(100)),
+            transaction_at("A", 2, TransactionType::TransferOut, dec!(3), dec!(0)),
+            transaction_at("B", 3, TransactionType::TransferIn, dec!(5), dec!(0)),
+        ];
+        let cost_basis = CostBasisConfig::new(CostBasisMethod::Fifo);
+
+        assert!(calculate_position_state(&transactions, &cost_basis).is_err());
+    }
+
+    #[test]
+    fn cost_by_date_sums_brokers() {
+        let mut transactions = vec![
+            transaction_at("A", 1, TransactionType::Buy, dec!(10), dec!(100)),
+            transaction_at("B", 2, TransactionType::Buy, dec!(5), dec!(150)),
+            transaction_at("A", 3, TransactionType::Sell, dec!(5), dec!(120)),
+        ];
+        let cost_basis = CostBasisConfig::new(CostBasisMethod::Fifo);
+        for i in 0..transactions.len() {
+            let state = calculate_position_state(&transactions[..=i], &cost_basis).unwrap();
+            transactions[i].set_position_state(Some(state));
+        }
+
+        let cost = calculate_cost_by_date(&transactions);
+        let costs: Vec<Decimal> = cost.values().map(|c| c.normalize()).collect();
+        assert_eq!(costs, vec![dec!(1000), dec!(1750), dec!(1250)]);
+    }
+
+    #[test]
+    fn units_by_date_split_each_broker_once() {
+        let transactions = vec![
+            transaction_at("A", 1, TransactionType::Buy, dec!(10), dec!(100)),
+            transaction_at("B", 2, TransactionType::Buy, dec!(5), dec!(150)),
+            transaction_at("A", 3, TransactionType::Split, dec!(2), dec!(0)),
+            transaction_at("B", 4, TransactionType::Split, dec!(2), dec!(0)),
+            transaction_at("A", 5, TransactionType::Sell, dec!(4), dec!(60)),
+        ];
+
+        let units = calculate_units_by_date(&transactions);
+        let units: Vec<Decimal> = units.values().map(|u| u.normalize()).collect();
+        assert_eq!(
+            units,
+            vec![dec!(10), dec!(15), dec!(25), dec!(30), dec!(26)]
+        );
+    }
+
+    fn point(date: &str, value: Decimal, cash_flow: Decimal) -> PerformancePoint {
+        PerformancePoint::new(
+            NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap(),
+            value,
+            cash_flow,
+        )
+    }
+
+    #[test]
+    fn twr_ignores_cash_flows() {
+        let points = vec![
+            point("2024-01-01", dec!(100), dec!(0)),
+            point("2024-06-01", dec!(220), dec!(-100)),
+            point("2024-12-01", dec!(0), dec!(264)),
+        ];
+
+        assert_eq!(calculate_twr(&points).unwrap(), dec!(0.44));
+        assert_eq!(calculate_twr(&points[..1]), None);
+    }
+
+    #[test]
+    fn xirr_is_annualized() {
+        let points = vec![
+            point("2022-12-31", dec!(0), dec!(0)),
+            point("2023-01-01", dec!(1000), dec!(-1000)),
+            point("2024-01-01", dec!(1100), dec!(0)),
+        ];
+        assert_eq!(calculate_xirr(&points).unwrap().round_dp(4), dec!(0.1));
+
+        let points = vec![
+            point("2023-01-01", dec!(1000), dec!(0)),
+            point("2023-07-02", dec!(500), dec!(500)),
+            point("2024-01-01", dec!(400), dec!(0)),
+        ];
+        assert!(calculate_xirr(&points).unwrap() < Decimal::ZERO);
+    }
 }
@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        app::{Cli, Config},
+        models::{CostBasisMethod, ticker::ApiProvider},
+    };
+
+    const SAMPLE_CONFIG: &str = include_str!("../../sample_data/config.toml");
+
+    #[test]
+    fn sample_config_parses() {
+        let config = Config::parse_str(SAMPLE_CONFIG).unwrap();
+
+        assert_eq!(config.base_currency(), "EUR");
+        assert_eq!(config.providers().default_api(), "Marketstack");
+        assert_eq!(config.refresh_interval(), None);
+    }
+
+    #[test]
+    fn missing_settings_use_defaults() {
+        let config = Config::parse_str(
+            r#"
+            base_currency = "CHF"
+
+            [refresh]
+            interval_minutes = 15
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.base_currency(), "CHF");
+        assert_eq!(config.transaction_files().len(), 1);
+        assert_eq!(config.providers().forex(), "Frankfurter");
+        assert_eq!(config.refresh_interval(), Some(Duration::from_secs(900)));
+    }
+
+    #[test]
+    fn cli_flags_override_config() {
+        let mut config = Config::parse_str(SAMPLE_CONFIG).unwrap();
+        let cli = Cli {
+            base_currency: Some(String::from("usd")),
+            transaction_files: vec![String::from("a.csv"), String::from("b.csv")],
+            demo: true,
+            ..Cli::default()
+        };
+        config.apply_cli(&cli);
+
+        assert_eq!(config.base_currency(), "USD");
+        assert_eq!(config.transaction_files_expanded(), vec!["a.csv", "b.csv"]);
+        assert_eq!(
+            config.providers().default_api(),
+            ApiProvider::Local.to_str()
+        );
+        assert!(config.providers().use_local_forex());
+    }
+
+    #[test]
+    fn unknown_forex_provider_is_rejected() {
+        let result = Config::parse_str(
+            r#"
+            [providers]
+            forex = "ECB"
+            "#,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn base_currency_is_upper_cased_and_validated() {
+        let config = Config::parse_str(r#"base_currency = " chf ""#).unwrap();
+        assert_eq!(config.base_currency(), "CHF");
+
+        assert!(Config::parse_str(r#"base_currency = "Euro""#).is_err());
+    }
+
+    #[test]
+    fn cost_basis_can_be_set_per_broker() {
+        let config = Config::parse_str(
+            r#"
+            [cost_basis]
+            method = "average"
+
+            [cost_basis.brokers]
+            IBKR = "HIFO"
+            "#,
+        )
+        .unwrap();
+        let cost_basis = config.cost_basis();
+
+        assert_eq!(
+            cost_basis.method_for_broker("IBKR").unwrap(),
+            CostBasisMethod::Hifo
+        );
+        assert_eq!(
+            cost_basis.method_for_broker("Degiro").unwrap(),
+            CostBasisMethod::Average
+        );
+        assert_eq!(cost_basis.signature().unwrap(), "Average;IBKR=HIFO");
+
+        let result = Config::parse_str(
+            r#"
+            [cost_basis]
+            method = "random"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+}
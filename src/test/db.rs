@@ -0,0 +1,196 @@
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use rust_decimal_macros::dec;
+    use sqlx::{
+        migrate::Migrator,
+        sqlite::{SqliteConnectOptions, SqlitePool},
+    };
+    use tempfile::TempDir;
+
+    use crate::{
+        app::utils::parse_datetime,
+        db::{
+            schema::{MIGRATOR, get_schema_version, latest_schema_version, run_migrations},
+            utils::{insert_transaction, parse_decimal_from_row, parse_transaction},
+        },
+        models::{
+            PositionState, Transaction, TransactionGains, TransactionSource, TransactionType,
+        },
+    };
+
+    async fn connect(db_dir: &TempDir) -> SqlitePool {
+        let options = SqliteConnectOptions::new()
+            .filename(db_dir.path().join("portfolio.db"))
+            .create_if_missing(true);
+        SqlitePool::connect_with(options).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn embedded_migrations_run_from_any_directory() {
+        let db_dir = TempDir::new().unwrap();
+        let connection = connect(&db_dir).await;
+
+        assert_eq!(get_schema_version(&connection).await.unwrap(), None);
+
+        run_migrations(&connection).await.unwrap();
+        run_migrations(&connection).await.unwrap();
+
+        assert_eq!(
+            get_schema_version(&connection).await.unwrap(),
+            Some(latest_schema_version())
+        );
+    }
+
+    #[tokio::test]
+    async fn newer_schema_is_refused() {
+        let db_dir = TempDir::new().unwrap();
+        let connection = connect(&db_dir).await;
+        run_migrations(&connection).await.unwrap();
+
+        sqlx::query(
+            r#"
+            INSERT INTO _sqlx_migrations
+            (version, description, success, checksum, execution_time)
+            VALUES (?, 'from the future', 1, X'00', 0)
+            "#,
+        )
+        .bind(latest_schema_version() + 1)
+        .execute(&connection)
+        .await
+        .unwrap();
+
+        let error = run_migrations(&connection).await.unwrap_err();
+
+        assert!(error.to_string().contains("is newer than"));
+    }
+
+    #[tokio::test]
+    async fn decimals_round_trip_exactly() {
+        let db_dir = TempDir::new().unwrap();
+        let connection = connect(&db_dir).await;
+        run_migrations(&connection).await.unwrap();
+        sqlx::query(
+            "INSERT INTO tickers (id, symbol, currency, api) VALUES (1, 'TSLA', 'USD', 'Local')",
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
+
+        let mut transaction = Transaction::new(
+            0,
+            1,
+            1,
+            parse_datetime("2025-03-14").unwrap(),
+            TransactionType::Buy,
+            String::from("IBKR"),
+            String::from("USD"),
+            dec!(1.08314159),
+            dec!(0.123456789),
+            dec!(123.456789012345),
+            dec!(0.99),
+            dec!(0),
+            dec!(0),
+            None,
+            None,
+        );
+        transaction.set_position_state(Some(PositionState::new(
+            dec!(0.123456789),
+            dec!(15.231214638290124500001),
+            dec!(0),
+        )));
+        transaction.set_transaction_gains(Some(TransactionGains::new(dec!(0), dec!(0))));
+
+        let mut tx = connection.begin().await.unwrap();
+        insert_transaction(
+            &transaction,
+            &1,
+            &TransactionSource::Import,
+            None,
+            None,
+            &mut tx,
+        )
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+
+        let row = sqlx::query("SELECT * FROM transactions")
+            .fetch_one(&connection)
+            .await
+            .unwrap();
+        let stored = parse_transaction(row).unwrap();
+        let position_state = stored.position_state().as_ref().unwrap();
+
+        assert_eq!(stored.date(), transaction.date());
+        assert_eq!(*stored.exchange_rate(), dec!(1.08314159));
+        assert_eq!(*stored.quantity(), dec!(0.123456789));
+        assert_eq!(*stored.price(), dec!(123.456789012345));
+        assert_eq!(
+            *position_state.cumulative_cost(),
+            dec!(15.231214638290124500001)
+        );
+    }
+
+    #[tokio::test]
+    async fn real_columns_are_converted() {
+        let db_dir = TempDir::new().unwrap();
+        let connection = connect(&db_dir).await;
+
+        let legacy = Migrator {
+            migrations: Cow::Owned(MIGRATOR.iter().take(3).cloned().collect()),
+            ..Migrator::DEFAULT
+        };
+        legacy.run(&connection).await.unwrap();
+
+        sqlx::query(
+            r#"
+            INSERT INTO tickers (symbol, currency, last_price, api)
+            VALUES ('TSLA', 'USD', 336.51, 'Local')
+            "#,
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
+        sqlx::query(
+            r#"
+            INSERT INTO transactions (
+                transaction_no, transaction_date, transaction_type, ticker_id, broker,
+                currency, exchange_rate, quantity, price, fees, cumulative_units,
+                cumulative_cost, cost_of_units_sold, realized_gain, dividend
+            )
+            VALUES (1, '2025-02-12', 'Buy', 1, 'IBKR', 'USD', 1.0375, 10, 330.0, 10,
+                10, 3190.7229, 0, 0, 0)
+            "#,
+        )
+        .execute(&connection)
+        .await
+        .unwrap();
+
+        run_migrations(&connection).await.unwrap();
+
+        let row = sqlx::query(
+            "SELECT ticker_id, cumulative_cost, typeof(cumulative_cost) AS t FROM transactions",
+        )
+        .fetch_one(&connection)
+        .await
+        .unwrap();
+        assert_eq!(sqlx::Row::get::<i64, _>(&row, "ticker_id"), 1);
+        assert_eq!(
+            parse_decimal_from_row(&row, "cumulative_cost").unwrap(),
+            dec!(3190.7229)
+        );
+        assert_eq!(sqlx::Row::get::<String, _>(&row, "t"), String::from("text"));
+
+        let row = sqlx::query("SELECT last_price, typeof(last_price) AS t FROM tickers")
+            .fetch_one(&connection)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            parse_decimal_from_row(&row, "last_price").unwrap(),
+            dec!(336.51)
+        );
+        assert_eq!(sqlx::Row::get::<String, _>(&row, "t"), String::from("text"));
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        app::importers::degiro::{is_account_statement, parse_account_statement},
+        models::TransactionType,
+    };
+
+    const ACCOUNT: &str = "\
+Date,Time,Value date,Product,ISIN,Description,FX,Change,,Balance,,Order Id
+02-01-2025,09:00,02-01-2025,,,iDEAL Deposit,,EUR,3000.00,EUR,3000.00,
+15-01-2025,15:31,15-01-2025,APPLE INC,US0378331005,Buy 6 Apple Inc@230.5 USD (US0378331005),,USD,-1383.00,USD,-1383.00,0b1c2d3e-aaaa
+15-01-2025,15:31,15-01-2025,APPLE INC,US0378331005,Buy 4 Apple Inc@231 USD (US0378331005),,USD,-924.00,USD,-2307.00,0b1c2d3e-aaaa
+15-01-2025,15:31,15-01-2025,APPLE INC,US0378331005,DEGIRO Transaction and/or third party fees,,EUR,-2.00,EUR,998.00,0b1c2d3e-aaaa
+15-01-2025,15:31,15-01-2025,,,FX Debit,1.0250,USD,2307.00,USD,0.00,0b1c2d3e-aaaa
+15-01-2025,15:31,15-01-2025,,,FX Credit,,EUR,-2250.73,EUR,-1252.73,0b1c2d3e-aaaa
+03-02-2025,08:00,03-02-2025,,,DEGIRO Exchange Connection Fee 2025 (Nasdaq - NDQ),,EUR,-2.50,EUR,-1255.23,
+15-05-2025,07:30,15-05-2025,APPLE INC,US0378331005,Dividend,,USD,2.50,USD,2.50,
+15-05-2025,07:30,15-05-2025,APPLE INC,US0378331005,Dividend Tax,,USD,-0.38,USD,2.12,
+15-05-2025,07:31,15-05-2025,,,FX Debit,1.1200,USD,-2.12,USD,0.00,
+15-05-2025,07:31,15-05-2025,,,FX Credit,,EUR,1.89,EUR,1.89,
+16-05-2025,07:31,16-05-2025,,,Degiro Cash Sweep Transfer,,EUR,-1.89,EUR,0.00,
+20-05-2025,10:00,20-05-2025,,,Flatex Interest Income,,EUR,0.42,EUR,0.42,
+21-05-2025,10:00,21-05-2025,,,Something new,,EUR,1.00,EUR,1.42,
+";
+
+    #[test]
+    fn trade_descriptions_separate_thousands_with_commas() {
+        let account = "\
+Date,Time,Value date,Product,ISIN,Description,FX,Change,,Balance,,Order Id
+15-01-2025,15:31,15-01-2025,APPLE INC,US0378331005,\"Sell 1,000 Apple Inc@1,230.5 USD (US0378331005)\",,USD,\"1230500,00\",USD,\"1230500,00\",0b1c2d3e-bbbb
+";
+        let records: Vec<_> = parse_account_statement(account, "USD")
+            .unwrap()
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(*records[0].quantity(), dec!(1000));
+        assert_eq!(*records[0].price(), dec!(1230.5));
+    }
+
+    #[test]
+    fn account_statement_folds_fees_and_conversions_into_trades() {
+        assert!(is_account_statement(ACCOUNT));
+        assert!(!is_account_statement("Date,Product,ISIN\n"));
+
+        let results = parse_account_statement(ACCOUNT, "EUR").unwrap();
+        let errors: Vec<String> = results
+            .iter()
+            .filter_map(|result| result.as_ref().err().map(|e| format!("{:#}", e)))
+            .collect();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("Something new"));
+        let records: Vec<_> = results.into_iter().flatten().collect();
+        assert_eq!(records.len(), 5);
+
+        let buy = &records[0];
+        assert_eq!(*buy.transaction_type(), TransactionType::Buy);
+        assert_eq!(*buy.date(), NaiveDate::from_ymd_opt(2025, 1, 15).unwrap());
+        assert_eq!(buy.symbol(), "US0378331005");
+        assert_eq!(buy.currency(), "USD");
+        assert_eq!(*buy.quantity(), dec!(10));
+        assert_eq!(*buy.price(), dec!(230.7));
+        assert_eq!(*buy.fees(), dec!(2));
+        assert_eq!(*buy.exchange_rate(), Some(dec!(1.025)));
+
+        let types: Vec<_> = records[1..]
+            .iter()
+            .map(|record| (record.transaction_type().clone(), *record.price()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                (TransactionType::Deposit, dec!(3000)),
+                (TransactionType::Fee, dec!(2.5)),
+                (TransactionType::Div, dec!(2.5)),
+                (TransactionType::Interest, dec!(0.42)),
+            ]
+        );
+        assert_eq!(*records[3].withholding_tax(), dec!(0.38));
+
+        // Without a conversion into the base currency the broker's rate is
+        // not used and fees in the trade currency cannot be converted
+        let results = parse_account_statement(ACCOUNT, "CHF").unwrap();
+        let error = format!("{:#}", results[0].as_ref().unwrap_err());
+        assert!(error.contains("Cannot convert the fee"), "{}", error);
+    }
+}
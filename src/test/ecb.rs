@@ -0,0 +1,69 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::app::ecb::{parse_ecb_csv, parse_ecb_xml};
+
+    #[test]
+    fn ecb_csv_rates_are_per_euro() {
+        let rates = parse_ecb_csv(
+            "Date,USD,JPY,CYP,CHF,\n\
+             2025-02-14,1.0478,159.73,N/A,0.9424,\n\
+             2025-02-13,1.0414,160.09,N/A,0.9437,\n",
+        )
+        .unwrap();
+
+        assert_eq!(rates.len(), 6);
+        assert_eq!(rates[0].from_currency(), "EUR");
+        assert_eq!(rates[0].to_currency(), "USD");
+        assert_eq!(
+            rates[0].date(),
+            &NaiveDate::from_ymd_opt(2025, 2, 14).unwrap()
+        );
+        assert_eq!(rates[0].rate(), &dec!(1.0478));
+        assert_eq!(rates[2].to_currency(), "CHF");
+        assert_eq!(rates[2].source(), "ECB");
+    }
+
+    #[test]
+    fn ecb_daily_csv_dates_are_parsed() {
+        let rates =
+            parse_ecb_csv("Date, USD, JPY, \n14 February 2025, 1.0478, 159.73, \n").unwrap();
+
+        assert_eq!(rates.len(), 2);
+        assert_eq!(
+            rates[1].date(),
+            &NaiveDate::from_ymd_opt(2025, 2, 14).unwrap()
+        );
+        assert_eq!(rates[1].rate(), &dec!(159.73));
+    }
+
+    #[test]
+    fn ecb_xml_rates_are_per_euro() {
+        let rates = parse_ecb_xml(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01" xmlns="http://www.ecb.int/vocabulary/2002-08-01/eurofxref">
+                <gesmes:subject>Reference rates</gesmes:subject>
+                <Cube>
+                    <Cube time="2025-02-14">
+                        <Cube currency="USD" rate="1.0478"/>
+                        <Cube currency="CHF" rate="0.9424"/>
+                    </Cube>
+                    <Cube time="2025-02-13">
+                        <Cube currency="USD" rate="1.0414"/>
+                    </Cube>
+                </Cube>
+            </gesmes:Envelope>"#,
+        )
+        .unwrap();
+
+        assert_eq!(rates.len(), 3);
+        assert_eq!(rates[1].to_currency(), "CHF");
+        assert_eq!(rates[1].rate(), &dec!(0.9424));
+        assert_eq!(
+            rates[2].date(),
+            &NaiveDate::from_ymd_opt(2025, 2, 13).unwrap()
+        );
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeZone};
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use serde_json::Value;
+
+    use crate::{
+        app::export::{ExportFormat, realized_gains_table, transactions_table},
+        models::{LedgerEntry, PositionState, Transaction, TransactionGains, TransactionType},
+    };
+
+    fn entry(
+        symbol: &str,
+        transaction_no: i64,
+        transaction_type: TransactionType,
+        quantity: Decimal,
+        price: Decimal,
+        position_state: PositionState,
+        transaction_gains: TransactionGains,
+    ) -> LedgerEntry {
+        LedgerEntry::new(
+            String::from(symbol),
+            Some(String::from("US88160R1014")),
+            None,
+            Transaction::new(
+                transaction_no,
+                1,
+                transaction_no,
+                Local.with_ymd_and_hms(2025, 3, 31, 0, 0, 0).unwrap(),
+                transaction_type,
+                String::from("IBKR"),
+                String::from("USD"),
+                dec!(1.08),
+                quantity,
+                price,
+                dec!(5),
+                dec!(0),
+                dec!(0),
+                Some(position_state),
+                Some(transaction_gains),
+            ),
+        )
+    }
+
+    fn sample_entries() -> Vec<LedgerEntry> {
+        vec![
+            entry(
+                "TSLA",
+                1,
+                TransactionType::Buy,
+                dec!(10),
+                dec!(330.00),
+                PositionState::new(dec!(10), dec!(3060.5556), dec!(0)),
+                TransactionGains::new(dec!(0), dec!(0)),
+            ),
+            entry(
+                "TSLA",
+                2,
+                TransactionType::Sell,
+                dec!(4),
+                dec!(270),
+                PositionState::new(dec!(6), dec!(1836.3333), dec!(1224.2222)),
+                TransactionGains::new(dec!(-229.2222), dec!(0)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn export_formats_are_parsed() {
+        assert_eq!(ExportFormat::parse_str("csv").unwrap(), ExportFormat::Csv);
+        assert_eq!(ExportFormat::parse_str("JSON").unwrap(), ExportFormat::Json);
+        assert!(ExportFormat::parse_str("xlsx").is_err());
+    }
+
+    #[test]
+    fn transactions_are_written_in_the_import_format() {
+        let csv = transactions_table(&sample_entries()).to_csv().unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(
+            lines[0],
+            "transaction_no,date,transaction_type,symbol,quantity,price,fees,broker,alternative_symbol,transaction_currency,withholding_tax,domestic_tax,exchange_rate,external_id,cumulative_units,cumulative_cost,cost_of_units_sold,realized_gain,dividend"
+        );
+        assert_eq!(
+            lines[1],
+            "1,2025-03-31,Buy,TSLA,10,330,5,IBKR,US88160R1014,USD,0,0,1.08,,10,3060.5556,0,0,0"
+        );
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn realized_gains_list_sales_as_json() {
+        let json = realized_gains_table(&sample_entries()).to_json().unwrap();
+        let rows: Vec<Value> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["transaction_no"], 2);
+        assert_eq!(rows[0]["symbol"], "TSLA");
+        assert_eq!(rows[0]["cost_of_units_sold"].as_f64(), Some(1224.2222));
+        assert_eq!(rows[0]["realized_gain"].as_f64(), Some(-229.2222));
+        // 4 × 270 / 1.08 less 5 in fees
+        assert_eq!(rows[0]["proceeds"].as_f64(), Some(995.0));
+    }
+}
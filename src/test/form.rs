@@ -0,0 +1,101 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::{app::form::TransactionForm, models::TransactionType};
+
+    fn fill(form: &mut TransactionForm, values: &[&str]) {
+        for value in values {
+            for c in value.chars() {
+                form.push(c);
+            }
+            form.next_field();
+        }
+    }
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, 30).unwrap()
+    }
+
+    #[test]
+    fn form_values_are_validated() {
+        let mut form = TransactionForm::new(today());
+        assert_eq!(form.value(0), "2025-06-30");
+        for _ in 0..10 {
+            form.pop();
+        }
+        fill(
+            &mut form,
+            &["2025-03-31", "", " tsla", "4", "259.16", "", "IBKR", "usd"],
+        );
+        form.next_field();
+        form.cycle_type(true);
+        assert_eq!(form.value(1), "Sell");
+
+        let input = form.to_input(today()).unwrap();
+        assert_eq!(*input.transaction_type(), TransactionType::Sell);
+        assert_eq!(input.symbol(), "TSLA");
+        assert_eq!(*input.quantity(), dec!(4));
+        assert_eq!(*input.price(), dec!(259.16));
+        assert_eq!(*input.fees(), dec!(0));
+        assert_eq!(input.currency(), "USD");
+        assert_eq!(input.date().format("%Y-%m-%d").to_string(), "2025-03-31");
+    }
+
+    #[test]
+    fn invalid_form_values_are_rejected() {
+        let cases = [
+            (
+                ["2025-07-01", "", "TSLA", "4", "250", "", "IBKR", ""],
+                "future",
+            ),
+            (
+                ["31.03.2025", "", "TSLA", "4", "250", "", "IBKR", ""],
+                "date",
+            ),
+            (["2025-03-31", "", "", "4", "250", "", "IBKR", ""], "Symbol"),
+            (
+                ["2025-03-31", "", "TSLA", "0", "250", "", "IBKR", ""],
+                "Quantity",
+            ),
+            (["2025-03-31", "", "TSLA", "4", "", "", "IBKR", ""], "price"),
+            (
+                ["2025-03-31", "", "TSLA", "4", "250", "-1", "IBKR", ""],
+                "Fees",
+            ),
+            (["2025-03-31", "", "TSLA", "4", "250", "", "", ""], "Broker"),
+            (
+                ["2025-03-31", "", "TSLA", "4", "250", "", "IBKR", "US"],
+                "currency",
+            ),
+        ];
+
+        for (values, expected) in cases {
+            let mut form = TransactionForm::new(today());
+            for _ in 0..10 {
+                form.pop();
+            }
+            fill(&mut form, &values);
+            let error = format!("{:#}", form.to_input(today()).unwrap_err());
+            assert!(error.contains(expected), "{} not in '{}'", expected, error);
+        }
+    }
+
+    #[test]
+    fn cash_forms_need_no_symbol_or_quantity() {
+        let mut form = TransactionForm::new(today());
+        form.next_field();
+        for _ in 0..6 {
+            form.cycle_type(true);
+        }
+        assert_eq!(form.value(1), "Deposit");
+        form.next_field();
+        fill(&mut form, &["", "", "500", "", "Degiro", "EUR"]);
+
+        let input = form.to_input(today()).unwrap();
+        assert_eq!(*input.quantity(), dec!(1));
+        assert_eq!(*input.price(), dec!(500));
+        assert!(input.symbol().is_empty());
+    }
+}
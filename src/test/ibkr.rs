@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::{app::importers::ibkr::parse_flex_query, models::TransactionType};
+
+    const FLEX_QUERY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FlexQueryResponse queryName="Activity" type="AF">
+  <FlexStatements count="1">
+    <FlexStatement accountId="U1234567" fromDate="20250101" toDate="20250630">
+      <AccountInformation accountId="U1234567" currency="EUR" />
+      <Trades>
+        <Trade assetCategory="STK" symbol="AAPL" isin="US0378331005" conid="265598" currency="USD" fxRateToBase="0.92" tradeDate="20250115" dateTime="20250115;093512" transactionID="1001" buySell="BUY" quantity="10" tradePrice="230.5" ibCommission="-1" ibCommissionCurrency="USD" levelOfDetail="EXECUTION" />
+        <Trade assetCategory="STK" symbol="AAPL" isin="US0378331005" conid="265598" currency="USD" tradeDate="20250115" buySell="BUY" quantity="10" tradePrice="230.5" levelOfDetail="ORDER" />
+        <Trade assetCategory="STK" symbol="SAP" isin="DE0007164600" conid="14204" currency="EUR" fxRateToBase="1" tradeDate="20250301" transactionID="1002" buySell="SELL" quantity="-5" tradePrice="250" ibCommission="-3" ibCommissionCurrency="EUR" levelOfDetail="EXECUTION" />
+        <Trade assetCategory="CASH" symbol="EUR.USD" currency="USD" tradeDate="20250114" transactionID="1003" buySell="BUY" quantity="2000" tradePrice="1.03" levelOfDetail="EXECUTION" />
+        <Trade assetCategory="OPT" symbol="AAPL 250620C00250000" currency="USD" tradeDate="20250116" transactionID="1004" buySell="BUY" quantity="1" tradePrice="2.5" levelOfDetail="EXECUTION" />
+      </Trades>
+      <CashTransactions>
+        <CashTransaction type="Dividends" symbol="AAPL" isin="US0378331005" conid="265598" currency="USD" dateTime="20250515" amount="2.5" description="AAPL(US0378331005) CASH DIVIDEND USD 0.25 PER SHARE (Ordinary Dividend)" transactionID="2001" levelOfDetail="DETAIL" />
+        <CashTransaction type="Withholding Tax" symbol="AAPL" isin="US0378331005" conid="265598" currency="USD" dateTime="20250515" amount="-0.38" description="AAPL(US0378331005) CASH DIVIDEND USD 0.25 PER SHARE - US TAX" transactionID="2002" levelOfDetail="DETAIL" />
+        <CashTransaction type="Withholding Tax" symbol="AAPL" isin="US0378331005" conid="265598" currency="USD" dateTime="20250601" amount="0.38" description="AAPL(US0378331005) US TAX REFUND" transactionID="2003" levelOfDetail="DETAIL" />
+        <CashTransaction type="Deposits/Withdrawals" currency="EUR" dateTime="20250102" amount="5000" description="CASH RECEIPTS" transactionID="2004" levelOfDetail="DETAIL" />
+        <CashTransaction type="Other Fees" currency="USD" dateTime="20250203" amount="-10" description="MARKET DATA FEE" transactionID="2005" levelOfDetail="DETAIL" />
+        <CashTransaction type="Broker Interest Received" currency="EUR" dateTime="20250203" amount="4.12" transactionID="2006" levelOfDetail="DETAIL" />
+        <CashTransaction type="Bond Coupons" currency="EUR" dateTime="20250203" amount="4.12" transactionID="2007" levelOfDetail="DETAIL" />
+      </CashTransactions>
+      <CorporateActions>
+        <CorporateAction type="FS" symbol="NVDA" isin="US67066G1040" currency="USD" dateTime="20240610;202500" actionID="3001" transactionID="3002" description="NVDA(US67066G1040) SPLIT 10 FOR 1 (NVDA, NVIDIA CORP, US67066G1040)" levelOfDetail="DETAIL" />
+        <CorporateAction type="TC" symbol="XYZ" currency="USD" dateTime="20240611" actionID="3003" transactionID="3004" description="XYZ MERGED" levelOfDetail="DETAIL" />
+      </CorporateActions>
+    </FlexStatement>
+  </FlexStatements>
+</FlexQueryResponse>"#;
+
+    #[test]
+    fn flex_query_is_mapped_to_records() {
+        let results = parse_flex_query(FLEX_QUERY, "EUR").unwrap();
+        let errors: Vec<String> = results
+            .iter()
+            .filter_map(|result| result.as_ref().err().map(|e| format!("{:#}", e)))
+            .collect();
+        let records: Vec<_> = results.into_iter().flatten().collect();
+
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert!(errors[0].contains("OPT"));
+        assert!(errors[1].contains("Bond Coupons"));
+        assert!(errors[2].contains("TC"));
+
+        let buy = &records[0];
+        assert_eq!(*buy.transaction_type(), TransactionType::Buy);
+        assert_eq!(*buy.date(), NaiveDate::from_ymd_opt(2025, 1, 15).unwrap());
+        assert_eq!(buy.external_id(), "IBKR:1001");
+        assert_eq!(buy.symbol(), "AAPL");
+        assert_eq!(buy.alternative_symbol(), "US0378331005");
+        assert_eq!(buy.broker(), "IBKR");
+        assert_eq!(*buy.fees(), dec!(0.92));
+
+        let sell = &records[1];
+        assert_eq!(*sell.transaction_type(), TransactionType::Sell);
+        assert_eq!(*sell.quantity(), dec!(5));
+        assert_eq!(*sell.fees(), dec!(3));
+
+        let dividend = &records[2];
+        assert_eq!(*dividend.transaction_type(), TransactionType::Div);
+        assert_eq!(*dividend.price() * *dividend.quantity(), dec!(2.5));
+        assert_eq!(*dividend.withholding_tax(), dec!(0.38));
+        let refund = &records[3];
+        assert_eq!(*refund.price(), dec!(0));
+        assert_eq!(*refund.withholding_tax(), dec!(-0.38));
+
+        let cash: Vec<_> = records[4..7]
+            .iter()
+            .map(|record| (record.transaction_type().clone(), *record.price()))
+            .collect();
+        assert_eq!(
+            cash,
+            vec![
+                (TransactionType::Deposit, dec!(5000)),
+                (TransactionType::Fee, dec!(10)),
+                (TransactionType::Interest, dec!(4.12)),
+            ]
+        );
+
+        let split = &records[7];
+        assert_eq!(*split.transaction_type(), TransactionType::Split);
+        assert_eq!(*split.quantity(), dec!(10));
+        assert_eq!(records.len(), 8);
+
+        let record = split.to_record();
+        assert_eq!(&record[1], "2024-06-10");
+        assert_eq!(&record[2], "Split");
+        assert_eq!(&record[4], "10");
+    }
+
+    #[test]
+    fn commissions_need_a_rate_to_the_base_currency() {
+        let results = parse_flex_query(FLEX_QUERY, "USD").unwrap();
+        let sell = results[1].as_ref().unwrap_err();
+        assert!(format!("{:#}", sell).contains("Cannot convert"));
+
+        assert!(parse_flex_query("<Statement />", "EUR").is_err());
+        assert!(parse_flex_query("not xml", "EUR").is_err());
+    }
+}
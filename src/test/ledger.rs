@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::models::{LedgerFilter, LedgerPage, TransactionType};
+
+    #[test]
+    fn filter_terms_are_parsed() {
+        let filter =
+            LedgerFilter::parse_str("tsla broker:IBKR type:Sell from:2025-01-01 TO:2025-06-30")
+                .unwrap();
+
+        assert_eq!(filter.symbol().as_deref(), Some("TSLA"));
+        assert_eq!(filter.broker().as_deref(), Some("IBKR"));
+        assert_eq!(filter.transaction_type(), &Some(TransactionType::Sell));
+        assert_eq!(*filter.from(), NaiveDate::from_ymd_opt(2025, 1, 1));
+        assert_eq!(*filter.to(), NaiveDate::from_ymd_opt(2025, 6, 30));
+        assert_eq!(
+            filter.to_str(),
+            "symbol:TSLA broker:IBKR type:Sell from:2025-01-01 to:2025-06-30"
+        );
+        assert_eq!(LedgerFilter::parse_str(&filter.to_str()).unwrap(), filter);
+        assert_eq!(
+            LedgerFilter::parse_str("").unwrap(),
+            LedgerFilter::default()
+        );
+    }
+
+    #[test]
+    fn invalid_filter_terms_are_rejected() {
+        assert!(LedgerFilter::parse_str("type:Gift").is_err());
+        assert!(LedgerFilter::parse_str("from:2025-13-01").is_err());
+        assert!(LedgerFilter::parse_str("currency:USD").is_err());
+        assert!(LedgerFilter::parse_str("broker:").is_err());
+    }
+
+    #[test]
+    fn page_count_rounds_up() {
+        assert_eq!(LedgerPage::new(Vec::new(), 0, 50, 0).page_count(), 1);
+        assert_eq!(LedgerPage::new(Vec::new(), 0, 50, 50).page_count(), 1);
+        assert_eq!(LedgerPage::new(Vec::new(), 0, 50, 51).page_count(), 2);
+    }
+}
@@ -0,0 +1,1121 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use chrono::{DateTime, Local};
+    use reqwest::Client;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+    use tempfile::TempDir;
+
+    use crate::{
+        api::{local::LocalProvider, provider::ForexProvider},
+        app::{
+            Config, CostBasisConfig, Portfolio,
+            export::ExportFormat,
+            importers::IMPORT_COLUMNS,
+            utils::{get_exchange_rate, parse_datetime},
+        },
+        db::{schema::run_migrations, utils::parse_decimal_from_row},
+        models::{
+            CostBasisMethod, ImportStatus, LedgerFilter, LedgerSort, ReturnPeriod,
+            TransactionInput, TransactionType, ticker::ApiProvider,
+        },
+    };
+
+    const FIXTURES_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/sample_data/fixtures");
+    const TRANSACTIONS_CSV: &str =
+        concat!(env!("CARGO_MANIFEST_DIR"), "/sample_data/transactions.csv");
+
+    async fn set_up_portfolio(db_dir: &TempDir) -> Portfolio {
+        let options = SqliteConnectOptions::new()
+            .filename(db_dir.path().join("portfolio.db"))
+            .create_if_missing(true);
+        let connection = SqlitePool::connect_with(options).await.unwrap();
+        run_migrations(&connection).await.unwrap();
+
+        let local = Arc::new(LocalProvider::new(Some(FIXTURES_DIR.into())));
+        let mut portfolio = Portfolio::new(String::from("EUR"), connection).unwrap();
+        portfolio.register_provider(local.clone());
+        portfolio.set_forex_provider(local);
+        portfolio.set_default_api(ApiProvider::Local);
+        portfolio
+    }
+
+    /// Writes `rows` below a header of the import columns to
+    /// `transactions.csv` in `db_dir` and returns its path.
+    fn write_csv(db_dir: &TempDir, rows: &str) -> String {
+        let path = db_dir.path().join("transactions.csv");
+        std::fs::write(&path, format!("{}\n{}", IMPORT_COLUMNS.join(","), rows)).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    async fn import_csv(portfolio: &mut Portfolio, db_dir: &TempDir, rows: &str) -> Result<()> {
+        portfolio
+            .import_transactions(&write_csv(db_dir, rows), &ApiProvider::Local)
+            .await
+    }
+
+    #[tokio::test]
+    async fn local_forex_falls_back_to_previous_date() {
+        let local = LocalProvider::new(Some(FIXTURES_DIR.into()));
+        let client = Client::new();
+        let saturday = parse_datetime("2025-02-15").unwrap();
+
+        let rate = local
+            .get_rate("EUR", "USD", &saturday, &client)
+            .await
+            .unwrap();
+        let inverse = local
+            .get_rate("USD", "EUR", &saturday, &client)
+            .await
+            .unwrap();
+
+        assert_eq!(rate, dec!(1.0375));
+        assert_eq!((rate * inverse).round_dp(8), dec!(1));
+    }
+
+    #[tokio::test]
+    async fn pipeline_runs_offline() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+
+        for _ in 0..2 {
+            portfolio
+                .import_transactions(TRANSACTIONS_CSV, &ApiProvider::Local)
+                .await
+                .unwrap();
+        }
+        portfolio.update_prices().await.unwrap();
+        portfolio.set_positions().await.unwrap();
+
+        let positions = portfolio.positions();
+        assert_eq!(positions.len(), 2);
+
+        let tesla = positions
+            .iter()
+            .find(|p| p.asset().name() == "Tesla Inc")
+            .unwrap();
+        assert_eq!(tesla.quantity().normalize(), dec!(10));
+        assert_eq!(tesla.asset().isin().as_deref(), Some("US88160R1014"));
+        assert_eq!(*tesla.market_value(), dec!(2710));
+    }
+
+    #[tokio::test]
+    async fn fractional_crypto_holdings_stay_open() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-03-31,Buy,BTC-EUR,0.0001,76150,0,Kraken,,\n",
+                "2,2025-06-30,Sell,BTC-EUR,0.00005,91400,0,Kraken,,\n",
+            ),
+        )
+        .await
+        .unwrap();
+        portfolio.set_positions().await.unwrap();
+
+        let bitcoin = &portfolio.positions()[0];
+        assert_eq!(bitcoin.asset().name(), "Bitcoin EUR");
+        assert_eq!(bitcoin.quantity().normalize(), dec!(0.00005));
+        assert_eq!(bitcoin.total_cost().normalize(), dec!(3.8075));
+    }
+
+    #[tokio::test]
+    async fn changing_cost_basis_recalculates_transactions() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-02-12,Buy,TSLA,5,300,0,IBKR,,EUR\n",
+                "2,2025-02-13,Buy,TSLA,5,340,0,IBKR,,EUR\n",
+                "3,2025-02-14,Sell,TSLA,5,350,0,IBKR,,EUR\n",
+            ),
+        )
+        .await
+        .unwrap();
+
+        let cost_of_sale = async |portfolio: &Portfolio| {
+            let row = sqlx::query(
+                "SELECT cumulative_cost, cost_of_units_sold FROM transactions WHERE transaction_no = 3",
+            )
+            .fetch_one(portfolio.connection())
+            .await
+            .unwrap();
+            (
+                parse_decimal_from_row(&row, "cumulative_cost").unwrap(),
+                parse_decimal_from_row(&row, "cost_of_units_sold").unwrap(),
+            )
+        };
+
+        let (fifo_cost, fifo_cost_sold) = cost_of_sale(&portfolio).await;
+        assert_eq!(fifo_cost.round_dp(2), dec!(1700));
+        assert_eq!(fifo_cost_sold.round_dp(2), dec!(1500));
+
+        let lifo = CostBasisConfig::new(CostBasisMethod::Lifo);
+        assert!(portfolio.set_cost_basis(lifo.clone()).await.unwrap());
+        assert!(!portfolio.set_cost_basis(lifo).await.unwrap());
+
+        let (lifo_cost, lifo_cost_sold) = cost_of_sale(&portfolio).await;
+        assert_eq!(lifo_cost.round_dp(2), dec!(1500));
+        assert_eq!(lifo_cost_sold.round_dp(2), dec!(1700));
+
+        let per_broker = CostBasisConfig::new(CostBasisMethod::Lifo)
+            .with_broker("IBKR", CostBasisMethod::Average);
+        assert!(portfolio.set_cost_basis(per_broker).await.unwrap());
+
+        let (average_cost, average_cost_sold) = cost_of_sale(&portfolio).await;
+        assert_eq!(average_cost.round_dp(2), dec!(1600));
+        assert_eq!(average_cost_sold.round_dp(2), dec!(1600));
+    }
+
+    #[tokio::test]
+    async fn cash_rows_track_broker_accounts() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-02-10,Deposit,,,5000,0,IBKR,,USD\n",
+                "2,2025-02-12,Buy,TSLA,10,300,0,IBKR,,\n",
+                "3,2025-02-13,Interest,,,12.5,0,IBKR,,USD\n",
+                "4,2025-02-14,Withdrawal,,,1000,0,IBKR,,USD\n",
+                "5,2025-02-14,Buy,BABA,10,100,0,Degiro,,\n",
+            ),
+        )
+        .await
+        .unwrap();
+        portfolio.set_positions().await.unwrap();
+
+        let positions = portfolio.positions();
+        assert_eq!(positions.len(), 3);
+
+        let cash = positions
+            .iter()
+            .find(|p| p.asset().asset_type().to_str() == "Cash")
+            .unwrap();
+        assert_eq!(cash.asset().name(), "Cash USD (IBKR)");
+        assert_eq!(cash.quantity().normalize(), dec!(1012.5));
+        assert_eq!(
+            cash.dividend().round_dp(2),
+            (dec!(12.5) * cash.price()).round_dp(2)
+        );
+    }
+
+    #[tokio::test]
+    async fn dividends_are_split_into_gross_and_net() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        let config = Config::parse_str(
+            r#"
+            [dividends.treaty_rates]
+            US = 0.15
+            "#,
+        )
+        .unwrap();
+        portfolio.set_dividend_config(config.dividends().clone());
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-01-21,Buy,TSLA,10,300,0,IBKR,,EUR,,\n",
+                "2,2025-02-12,Div,TSLA,10,10,0,IBKR,,EUR,30,5\n",
+                "3,2025-02-12,Div,TSLA,10,2,0,IBKR,,EUR,,\n",
+            ),
+        )
+        .await
+        .unwrap();
+
+        let report = portfolio.get_dividend_report().await.unwrap();
+        let total = report.total();
+        assert_eq!(total.gross().round_dp(2), dec!(120));
+        assert_eq!(total.withholding_tax().round_dp(2), dec!(30));
+        assert_eq!(total.domestic_tax().round_dp(2), dec!(5));
+        assert_eq!(total.reclaimable().round_dp(2), dec!(15));
+        assert_eq!(total.net().round_dp(2), dec!(85));
+        assert_eq!(report.by_year().keys().collect::<Vec<_>>(), vec![&2025]);
+        assert_eq!(report.by_country().keys().collect::<Vec<_>>(), vec!["US"]);
+        assert!(report.by_position().contains_key("Tesla Inc"));
+
+        portfolio.set_positions().await.unwrap();
+        let tesla = &portfolio.positions()[0];
+        assert_eq!(tesla.dividend().round_dp(2), dec!(85));
+    }
+
+    #[tokio::test]
+    async fn returns_use_quote_history() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        portfolio
+            .import_transactions(TRANSACTIONS_CSV, &ApiProvider::Local)
+            .await
+            .unwrap();
+
+        let report = portfolio
+            .get_performance_report(ReturnPeriod::SinceInception)
+            .await
+            .unwrap();
+
+        let tesla = report.by_position().get("Tesla Inc").unwrap();
+        let expected = (dec!(3176.6) / dec!(1.1720)) / (dec!(3365.1) / dec!(1.0375)) - dec!(1);
+        assert_eq!(tesla.twr().unwrap().round_dp(6), expected.round_dp(6));
+        assert!(tesla.xirr().is_some());
+        assert!(report.portfolio().twr().is_some());
+        assert!(report.portfolio().xirr().is_some());
+    }
+
+    #[tokio::test]
+    async fn backfill_stores_missing_prices_once() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        portfolio
+            .import_transactions(TRANSACTIONS_CSV, &ApiProvider::Local)
+            .await
+            .unwrap();
+
+        let stored = portfolio.backfill_prices().await.unwrap();
+        assert_eq!(portfolio.backfill_prices().await.unwrap(), 0);
+        portfolio.update_prices().await.unwrap();
+
+        // From the first purchase on, TSLA on 2025-02-12 and BABA on 2025-01-21
+        let dates: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT tcr.symbol, prc.price_date
+            FROM prices prc
+            INNER JOIN tickers tcr ON prc.ticker_id = tcr.id
+            ORDER BY tcr.symbol, prc.price_date
+            "#,
+        )
+        .fetch_all(portfolio.connection())
+        .await
+        .unwrap();
+        let first_dates: Vec<&(String, String)> = dates
+            .iter()
+            .filter(|(symbol, date)| !dates.iter().any(|(s, d)| s == symbol && d < date))
+            .collect();
+        assert_eq!(
+            first_dates,
+            vec![
+                &(String::from("BABA"), String::from("2025-01-21")),
+                &(String::from("TSLA"), String::from("2025-02-12")),
+            ]
+        );
+        assert_eq!(dates.len(), stored);
+    }
+
+    #[tokio::test]
+    async fn stored_latest_prices_do_not_hide_the_quote_history() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        portfolio
+            .import_transactions(TRANSACTIONS_CSV, &ApiProvider::Local)
+            .await
+            .unwrap();
+        let before = portfolio
+            .get_performance_report(ReturnPeriod::SinceInception)
+            .await
+            .unwrap();
+
+        // Stores the latest close of each ticker
+        portfolio.update_prices().await.unwrap();
+        let after = portfolio
+            .get_performance_report(ReturnPeriod::SinceInception)
+            .await
+            .unwrap();
+
+        for (name, performance) in before.by_position() {
+            assert_eq!(after.by_position()[name].twr(), performance.twr());
+        }
+        assert_eq!(after.portfolio().twr(), before.portfolio().twr());
+    }
+
+    #[tokio::test]
+    async fn exchange_rates_are_cached_and_loaded_from_ecb_files() {
+        let db_dir = TempDir::new().unwrap();
+        let portfolio = set_up_portfolio(&db_dir).await;
+        let client = Client::new();
+        let mut connection = portfolio.connection().acquire().await.unwrap();
+
+        let local = LocalProvider::new(Some(FIXTURES_DIR.into()));
+        let date = parse_datetime("2025-02-12").unwrap();
+        let rate = get_exchange_rate("USD", "EUR", &date, &local, &client, &mut connection)
+            .await
+            .unwrap();
+        assert_eq!(rate, dec!(1.0375));
+        let source: String = sqlx::query_scalar("SELECT source FROM fx_rates")
+            .fetch_one(&mut *connection)
+            .await
+            .unwrap();
+        assert_eq!(source, "Local");
+
+        let ecb_path = db_dir.path().join("eurofxref-hist.csv");
+        std::fs::write(
+            &ecb_path,
+            "Date,USD,CHF,\n2025-02-14,1.0478,0.9424,\n2025-02-13,1.0414,0.9437,\n",
+        )
+        .unwrap();
+        let count = portfolio
+            .load_fx_rates(ecb_path.to_str().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(count, 4);
+
+        // Without fixtures the provider fails, so only the cache can answer
+        let offline = LocalProvider::new(None);
+        for day in ["2025-02-14", "2025-02-15", "2025-02-17"] {
+            let date = parse_datetime(day).unwrap();
+            let rate = get_exchange_rate("USD", "EUR", &date, &offline, &client, &mut connection)
+                .await
+                .unwrap();
+            assert_eq!(rate, dec!(1.0478));
+        }
+
+        let date = parse_datetime("2025-02-13").unwrap();
+        let cross = get_exchange_rate("CHF", "USD", &date, &offline, &client, &mut connection)
+            .await
+            .unwrap();
+        assert_eq!(cross, dec!(0.9437) / dec!(1.0414));
+
+        let date = parse_datetime("2025-03-03").unwrap();
+        let missing =
+            get_exchange_rate("USD", "EUR", &date, &offline, &client, &mut connection).await;
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn recent_exchange_rates_are_not_cached() {
+        let db_dir = TempDir::new().unwrap();
+        let portfolio = set_up_portfolio(&db_dir).await;
+        let client = Client::new();
+        let mut connection = portfolio.connection().acquire().await.unwrap();
+
+        // Falls back to the last fixture, as providers do before publishing
+        let local = LocalProvider::new(Some(FIXTURES_DIR.into()));
+        get_exchange_rate(
+            "USD",
+            "EUR",
+            &Local::now(),
+            &local,
+            &client,
+            &mut connection,
+        )
+        .await
+        .unwrap();
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM fx_rates")
+            .fetch_one(&mut *connection)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    /// Counts the rates requested from the local fixtures.
+    struct CountingForex {
+        local: LocalProvider,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ForexProvider for CountingForex {
+        fn name(&self) -> &'static str {
+            "Counting"
+        }
+
+        async fn get_rate(
+            &self,
+            from_currency: &str,
+            to_currency: &str,
+            date: &DateTime<Local>,
+            client: &Client,
+        ) -> Result<Decimal> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.local
+                .get_rate(from_currency, to_currency, date, client)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn current_exchange_rates_are_fetched_once_a_day() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            "1,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+        )
+        .await
+        .unwrap();
+
+        let forex = Arc::new(CountingForex {
+            local: LocalProvider::new(Some(FIXTURES_DIR.into())),
+            calls: AtomicUsize::new(0),
+        });
+        portfolio.set_forex_provider(forex.clone());
+        portfolio.update_exchange_rates().await.unwrap();
+        portfolio.update_exchange_rates().await.unwrap();
+
+        assert_eq!(forex.calls.load(Ordering::SeqCst), 1);
+        assert_eq!(portfolio.forex_map().get("USD"), Some(&dec!(1.172)));
+    }
+
+    #[tokio::test]
+    async fn value_history_ends_at_current_positions() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        portfolio
+            .import_transactions(TRANSACTIONS_CSV, &ApiProvider::Local)
+            .await
+            .unwrap();
+        portfolio.update_prices().await.unwrap();
+        portfolio.set_positions().await.unwrap();
+
+        let history = portfolio
+            .get_value_history(ReturnPeriod::SinceInception)
+            .await
+            .unwrap();
+
+        // Daily from the day before the first purchase on 2025-01-21
+        let points = history.portfolio();
+        assert_eq!(points[0].date().to_string(), "2025-01-20");
+        assert_eq!(*points[0].market_value(), dec!(0));
+        assert_eq!((*points[1].date() - *points[0].date()).num_days(), 1);
+
+        let last = points.last().unwrap();
+        let positions = portfolio.positions();
+        let market_value: Decimal = positions.iter().map(|p| p.market_value()).sum();
+        let invested: Decimal = positions.iter().map(|p| p.total_cost()).sum();
+        // Position values are rounded to whole units
+        assert!((*last.market_value() - market_value).abs() < dec!(1));
+        assert_eq!(last.invested().round_dp(6), invested.round_dp(6));
+        assert_eq!(history.by_position().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn value_history_adds_up_the_tickers_of_an_asset() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+                "2,2025-03-31,Buy,TL0,2,250,5,Xetra,,\n",
+            ),
+        )
+        .await
+        .unwrap();
+
+        let history = portfolio
+            .get_value_history(ReturnPeriod::SinceInception)
+            .await
+            .unwrap();
+
+        assert_eq!(history.by_position().len(), 1);
+        let tesla = history.by_position().values().next().unwrap();
+        assert_eq!(tesla.name(), "Tesla Inc");
+        assert_eq!(tesla.points(), history.portfolio());
+    }
+
+    #[tokio::test]
+    async fn position_detail_lists_transactions_and_lots() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        portfolio
+            .import_transactions(TRANSACTIONS_CSV, &ApiProvider::Local)
+            .await
+            .unwrap();
+        portfolio.update_prices().await.unwrap();
+        portfolio.set_positions().await.unwrap();
+
+        let tesla = portfolio
+            .positions()
+            .iter()
+            .find(|p| p.asset().name() == "Tesla Inc")
+            .unwrap()
+            .clone();
+        let detail = portfolio
+            .get_position_detail(*tesla.asset().id())
+            .await
+            .unwrap();
+
+        assert_eq!(detail.asset().isin().as_deref(), Some("US88160R1014"));
+        assert_eq!(detail.tickers().len(), 1);
+        assert_eq!(detail.tickers()[0].symbol(), "TSLA");
+        assert_eq!(detail.transactions().len(), 1);
+        assert_eq!(detail.cost_basis(), "FIFO");
+
+        let lots = detail.lots();
+        assert_eq!(lots.len(), 1);
+        assert_eq!(lots[0].broker(), "IBKR");
+        assert_eq!(lots[0].lot().quantity().normalize(), dec!(10));
+        assert_eq!(lots[0].lot().cost(), tesla.total_cost());
+        assert!((*lots[0].unrealized_gain() - tesla.unrealized_gain()).abs() < dec!(1));
+    }
+
+    #[tokio::test]
+    async fn ledger_filters_sorts_and_pages() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-01-21,Buy,BABA,100,84.92,10,IBKR,,\n",
+                "2,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+                "3,2025-03-31,Sell,TSLA,4,259.16,5,IBKR,,\n",
+                "4,2025-04-01,Deposit,,,500,0,Degiro,,EUR\n",
+            ),
+        )
+        .await
+        .unwrap();
+
+        let all = LedgerFilter::default();
+        let page = portfolio
+            .get_ledger(&all, LedgerSort::Date, true, 0, 3)
+            .await
+            .unwrap();
+        assert_eq!(*page.total(), 4);
+        assert_eq!(page.page_count(), 2);
+        let numbers: Vec<i64> = page
+            .entries()
+            .iter()
+            .map(|e| *e.transaction().transaction_no())
+            .collect();
+        assert_eq!(numbers, vec![4, 3, 2]);
+        assert_eq!(page.entries()[0].symbol(), "");
+
+        let second = portfolio
+            .get_ledger(&all, LedgerSort::Date, true, 1, 3)
+            .await
+            .unwrap();
+        assert_eq!(second.entries().len(), 1);
+
+        let filter = LedgerFilter::parse_str("symbol:TSLA from:2025-03-01 to:2025-03-31").unwrap();
+        let page = portfolio
+            .get_ledger(&filter, LedgerSort::Date, false, 0, 50)
+            .await
+            .unwrap();
+        assert_eq!(*page.total(), 1);
+        let sale = page.entries()[0].transaction();
+        assert_eq!(*sale.transaction_type(), TransactionType::Sell);
+        assert!(sale.transaction_gains().is_some());
+
+        let filter = LedgerFilter::parse_str("type:Buy").unwrap();
+        let page = portfolio
+            .get_ledger(&filter, LedgerSort::Quantity, true, 0, 50)
+            .await
+            .unwrap();
+        let symbols: Vec<&str> = page.entries().iter().map(|e| e.symbol().as_str()).collect();
+        assert_eq!(symbols, vec!["BABA", "TSLA"]);
+    }
+
+    #[tokio::test]
+    async fn edited_transactions_replay_their_chain() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            "1,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+        )
+        .await
+        .unwrap();
+
+        let units = |rows: &[(i64, Decimal)], id: i64| {
+            rows.iter().find(|(row_id, _)| *row_id == id).unwrap().1
+        };
+        let stored_units = async |portfolio: &Portfolio| {
+            let rows = sqlx::query("SELECT id, cumulative_units FROM transactions")
+                .fetch_all(portfolio.connection())
+                .await
+                .unwrap();
+            rows.iter()
+                .map(|row| {
+                    (
+                        sqlx::Row::get::<i64, _>(row, "id"),
+                        parse_decimal_from_row(row, "cumulative_units").unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let input = |transaction_type, quantity| {
+            TransactionInput::new(
+                parse_datetime("2025-03-31").unwrap(),
+                transaction_type,
+                String::from("TSLA"),
+                String::from("IBKR"),
+                String::new(),
+                quantity,
+                dec!(259.16),
+                dec!(5),
+            )
+        };
+
+        let sell_id = portfolio
+            .save_transaction(None, &input(TransactionType::Sell, dec!(4)))
+            .await
+            .unwrap();
+        let rows = stored_units(&portfolio).await;
+        assert_eq!(units(&rows, sell_id), dec!(6));
+
+        let ledger = portfolio
+            .get_ledger(&LedgerFilter::default(), LedgerSort::Date, false, 0, 50)
+            .await
+            .unwrap();
+        let buy = ledger.entries()[0].transaction().clone();
+        let sell = ledger.entries()[1].transaction().clone();
+        assert_eq!(*sell.transaction_no(), 2);
+        assert_eq!(*sell.exchange_rate(), dec!(1.0815));
+
+        // Buying more earlier in the chain changes the units after the sale
+        let buy_input = TransactionInput::new(
+            *buy.date(),
+            TransactionType::Buy,
+            String::from("TSLA"),
+            String::from("IBKR"),
+            String::new(),
+            dec!(20),
+            *buy.price(),
+            *buy.fees(),
+        );
+        portfolio
+            .save_transaction(Some(*buy.id()), &buy_input)
+            .await
+            .unwrap();
+        let rows = stored_units(&portfolio).await;
+        assert_eq!(units(&rows, *buy.id()), dec!(20));
+        assert_eq!(units(&rows, sell_id), dec!(16));
+
+        // A backdated buy is numbered last but replayed before the sale, and
+        // moving it after the sale replays it there
+        let backdated = |date| {
+            TransactionInput::new(
+                parse_datetime(date).unwrap(),
+                TransactionType::Buy,
+                String::from("TSLA"),
+                String::from("IBKR"),
+                String::new(),
+                dec!(5),
+                dec!(290),
+                dec!(5),
+            )
+        };
+        let backdated_id = portfolio
+            .save_transaction(None, &backdated("2025-03-03"))
+            .await
+            .unwrap();
+        let rows = stored_units(&portfolio).await;
+        assert_eq!(units(&rows, backdated_id), dec!(25));
+        assert_eq!(units(&rows, sell_id), dec!(21));
+
+        portfolio
+            .save_transaction(Some(backdated_id), &backdated("2025-06-30"))
+            .await
+            .unwrap();
+        let rows = stored_units(&portfolio).await;
+        assert_eq!(units(&rows, sell_id), dec!(16));
+        assert_eq!(units(&rows, backdated_id), dec!(21));
+
+        // Imports do not replace transactions entered in the app
+        let error = import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-02-12,Buy,TSLA,20,330.0,10,IBKR,,\n",
+                "3,2025-02-12,Buy,TSLA,1,330.0,10,IBKR,,\n",
+            ),
+        )
+        .await
+        .unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("Transaction 3 in record 2 was entered in the app")
+        );
+        portfolio.delete_transaction(backdated_id).await.unwrap();
+
+        // Selling more than held fails and leaves the chain as it was
+        let oversold = portfolio
+            .save_transaction(None, &input(TransactionType::Sell, dec!(100)))
+            .await;
+        assert!(oversold.is_err());
+        assert_eq!(stored_units(&portfolio).await.len(), 2);
+
+        portfolio.delete_transaction(sell_id).await.unwrap();
+        let rows = stored_units(&portfolio).await;
+        assert_eq!(rows.len(), 1);
+        assert!(portfolio.delete_transaction(sell_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn reimport_applies_corrected_and_backdated_rows() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        let mut import = async |rows: &str| {
+            import_csv(&mut portfolio, &db_dir, rows).await.unwrap();
+            let rows = sqlx::query(
+                "SELECT transaction_no, cumulative_units FROM transactions ORDER BY transaction_no",
+            )
+            .fetch_all(portfolio.connection())
+            .await
+            .unwrap();
+            rows.iter()
+                .map(|row| {
+                    (
+                        sqlx::Row::get::<i64, _>(row, "transaction_no"),
+                        parse_decimal_from_row(row, "cumulative_units").unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let original = concat!(
+            "1,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+            "2,2025-01-21,Buy,BABA,100,84.92,10,IBKR,,\n",
+            "4,2025-03-31,Sell,TSLA,4,259.16,5,IBKR,,\n",
+        );
+        let expected = vec![(1, dec!(10)), (2, dec!(100)), (4, dec!(6))];
+        assert_eq!(import(original).await, expected);
+        assert_eq!(import(original).await, expected);
+
+        // A corrected quantity and a missing trade filled into the gap
+        let corrected = concat!(
+            "1,2025-02-12,Buy,TSLA,20,330.0,10,IBKR,,\n",
+            "2,2025-01-21,Buy,BABA,100,84.92,10,IBKR,,\n",
+            "3,2025-03-01,Buy,TSLA,5,280.0,5,IBKR,,\n",
+            "4,2025-03-31,Sell,TSLA,4,259.16,5,IBKR,,\n",
+        );
+        assert_eq!(
+            import(corrected).await,
+            vec![(1, dec!(20)), (2, dec!(100)), (3, dec!(25)), (4, dec!(21))]
+        );
+    }
+
+    #[tokio::test]
+    async fn import_preview_lists_changes_without_writing() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        let count_transactions = async |portfolio: &Portfolio| {
+            sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM transactions")
+                .fetch_one(portfolio.connection())
+                .await
+                .unwrap()
+        };
+
+        let paths = vec![write_csv(
+            &db_dir,
+            concat!(
+                "1,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+                "2,2025-03-31,Sell,TSLA,ten,259.16,5,IBKR,,\n",
+                "3,2025-04-01,Buy,NOSUCHSYMBOL,1,10,0,IBKR,,\n",
+                "1,2025-04-02,Deposit,,,500,0,Degiro,,EUR\n",
+            ),
+        )];
+        let preview = portfolio
+            .preview_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+        assert_eq!(preview.count(ImportStatus::New), 1);
+        assert_eq!(preview.errors().len(), 3, "{:?}", preview.errors());
+        assert!(preview.errors()[0].contains("record 2"));
+        assert!(preview.errors()[1].contains("NOSUCHSYMBOL"));
+        assert!(preview.errors()[2].contains("Duplicate"));
+        assert_eq!(count_transactions(&portfolio).await, 0);
+        assert!(
+            portfolio
+                .commit_import(&paths, &ApiProvider::Local)
+                .await
+                .is_err()
+        );
+        assert_eq!(count_transactions(&portfolio).await, 0);
+
+        write_csv(
+            &db_dir,
+            concat!(
+                "1,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,\n",
+                "2,2025-03-31,Sell,TSLA,4,259.16,5,IBKR,,\n",
+            ),
+        );
+        let preview = portfolio
+            .preview_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+        assert!(preview.errors().is_empty());
+        assert_eq!(preview.count(ImportStatus::New), 2);
+        let change = &preview.position_changes()[0];
+        assert_eq!(change.symbol(), "TSLA");
+        assert_eq!(*change.units_before(), dec!(0));
+        assert_eq!(*change.units_after(), dec!(6));
+        assert_eq!(count_transactions(&portfolio).await, 0);
+        let tickers = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM tickers")
+            .fetch_one(portfolio.connection())
+            .await
+            .unwrap();
+        assert_eq!(tickers, 0);
+
+        portfolio
+            .commit_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+        assert_eq!(count_transactions(&portfolio).await, 2);
+        let preview = portfolio
+            .preview_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+        assert_eq!(preview.count(ImportStatus::Unchanged), 2);
+        assert!(preview.position_changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn flex_queries_resolve_tickers_by_isin() {
+        let db_dir = TempDir::new().unwrap();
+        let xml_path = db_dir.path().join("statement.xml");
+        let paths = vec![xml_path.to_str().unwrap().to_string()];
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        let statement = |trades: &str| {
+            format!(
+                r#"<FlexQueryResponse><FlexStatements><FlexStatement>
+                <AccountInformation currency="EUR" /><Trades>{}</Trades>
+                </FlexStatement></FlexStatements></FlexQueryResponse>"#,
+                trades
+            )
+        };
+
+        std::fs::write(
+            &xml_path,
+            statement(
+                r#"<Trade assetCategory="STK" symbol="TSLA" isin="US88160R1014" currency="USD" fxRateToBase="0.95" tradeDate="20250212" transactionID="1000000000011" buySell="BUY" quantity="10" tradePrice="330" ibCommission="-1" />"#,
+            ),
+        )
+        .unwrap();
+        portfolio
+            .commit_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+
+        // The same security under its symbol at another exchange, and entries
+        // whose ids only differ in their leading digits
+        std::fs::write(
+            &xml_path,
+            statement(concat!(
+                r#"<Trade assetCategory="STK" symbol="TSLA" isin="US88160R1014" currency="USD" fxRateToBase="0.95" tradeDate="20250212" transactionID="1000000000011" buySell="BUY" quantity="10" tradePrice="330" ibCommission="-1" />"#,
+                r#"<Trade assetCategory="STK" symbol="TL0" isin="US88160R1014" currency="USD" fxRateToBase="0.96" tradeDate="20250331" transactionID="2000000000012" buySell="SELL" quantity="-4" tradePrice="259.16" ibCommission="-1" />"#,
+                r#"<Trade assetCategory="STK" symbol="TSLA" isin="US88160R1014" currency="USD" fxRateToBase="0.96" tradeDate="20250331" transactionID="3000000000012" buySell="BUY" quantity="1" tradePrice="259.16" ibCommission="-1" />"#,
+            )),
+        )
+        .unwrap();
+        let preview = portfolio
+            .commit_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+        assert_eq!(preview.count(ImportStatus::Unchanged), 1);
+        assert_eq!(preview.count(ImportStatus::New), 2);
+
+        let rows = sqlx::query(
+            "SELECT transaction_no, external_id, fees, cumulative_units FROM transactions ORDER BY transaction_no",
+        )
+        .fetch_all(portfolio.connection())
+        .await
+        .unwrap();
+        let transaction_nos: Vec<i64> = rows
+            .iter()
+            .map(|row| sqlx::Row::get(row, "transaction_no"))
+            .collect();
+        assert_eq!(transaction_nos, vec![1, 2, 3]);
+        let sell = &rows[1];
+        assert_eq!(
+            sqlx::Row::get::<String, _>(sell, "external_id"),
+            "IBKR:2000000000012"
+        );
+        assert_eq!(parse_decimal_from_row(sell, "fees").unwrap(), dec!(0.96));
+        assert_eq!(
+            parse_decimal_from_row(sell, "cumulative_units").unwrap(),
+            dec!(6)
+        );
+        assert_eq!(
+            parse_decimal_from_row(&rows[2], "cumulative_units").unwrap(),
+            dec!(7)
+        );
+
+        // TL0 is known to the provider but not looked up
+        let tickers: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tickers")
+            .fetch_one(portfolio.connection())
+            .await
+            .unwrap();
+        assert_eq!(tickers, 1);
+    }
+
+    #[tokio::test]
+    async fn broker_exchange_rates_replace_reference_rates() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-02-12,Buy,TSLA,10,330.0,10,Degiro,,,,,1.05\n",
+                "2,2025-03-31,Sell,TSLA,4,259.16,5,Degiro,,\n",
+            ),
+        )
+        .await
+        .unwrap();
+
+        let rates: Vec<Decimal> =
+            sqlx::query("SELECT exchange_rate FROM transactions ORDER BY transaction_no")
+                .fetch_all(portfolio.connection())
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| parse_decimal_from_row(row, "exchange_rate").unwrap())
+                .collect();
+        assert_eq!(rates[0], dec!(1.05));
+        assert_eq!(rates[1], dec!(1.0815));
+    }
+
+    #[tokio::test]
+    async fn ofx_securities_resolve_by_cusip() {
+        let db_dir = TempDir::new().unwrap();
+        let csv_path = write_csv(&db_dir, "1,2025-02-12,Buy,TSLA,10,330.0,10,OFX,,\n");
+        let ofx_path = db_dir.path().join("statement.qfx");
+        std::fs::write(
+            &ofx_path,
+            concat!(
+                "OFXHEADER:100\n\n<OFX><INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS><CURDEF>EUR\n",
+                "<INVTRANLIST><SELLSTOCK><INVSELL><INVTRAN><FITID>S-1<DTTRADE>20250331</INVTRAN>\n",
+                "<SECID><UNIQUEID>88160R101<UNIQUEIDTYPE>CUSIP</SECID>\n",
+                "<UNITS>-4<UNITPRICE>259.16<COMMISSION>5<CURRENCY><CURRATE>0.92<CURSYM>USD</CURRENCY>\n",
+                "</INVSELL><SELLTYPE>SELL</SELLSTOCK></INVTRANLIST></INVSTMTRS>\n",
+                "</INVSTMTTRNRS></INVSTMTMSGSRSV1></OFX>\n",
+            ),
+        )
+        .unwrap();
+        let paths = vec![csv_path, ofx_path.to_str().unwrap().to_string()];
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        let preview = portfolio
+            .commit_import(&paths, &ApiProvider::Local)
+            .await
+            .unwrap();
+        assert_eq!(preview.count(ImportStatus::New), 2);
+
+        let row = sqlx::query(
+            "SELECT fees, cumulative_units FROM transactions WHERE transaction_type = 'Sell'",
+        )
+        .fetch_one(portfolio.connection())
+        .await
+        .unwrap();
+        assert_eq!(parse_decimal_from_row(&row, "fees").unwrap(), dec!(4.6));
+        assert_eq!(
+            parse_decimal_from_row(&row, "cumulative_units").unwrap(),
+            dec!(6)
+        );
+    }
+
+    #[tokio::test]
+    async fn exported_transactions_import_into_the_same_ledger() {
+        let db_dir = TempDir::new().unwrap();
+        let mut portfolio = set_up_portfolio(&db_dir).await;
+        import_csv(
+            &mut portfolio,
+            &db_dir,
+            concat!(
+                "1,2025-01-21,Buy,BABA,100,84.92,10,IBKR,,\n",
+                "2,2025-02-12,Buy,TSLA,10,330.0,10,IBKR,,,,,1.05\n",
+                "3,2025-03-31,Sell,TSLA,4,259.16,5,IBKR,,\n",
+                "4,2025-03-31,Div,BABA,100,1.05,0,IBKR,,,0.1575\n",
+                "5,2025-04-01,Deposit,,,500,0,Degiro,,EUR\n",
+            ),
+        )
+        .await
+        .unwrap();
+        portfolio.set_positions().await.unwrap();
+
+        let export_dir = db_dir.path().join("export");
+        let paths = portfolio
+            .export(&export_dir, ExportFormat::Csv)
+            .await
+            .unwrap();
+        assert_eq!(paths.len(), 3);
+        let exported = std::fs::read_to_string(export_dir.join("transactions.csv")).unwrap();
+        let gains = std::fs::read_to_string(export_dir.join("realized_gains.csv")).unwrap();
+        assert_eq!(gains.lines().count(), 2);
+        assert!(exported.contains(",Buy,TSLA,10,330,10,IBKR,US88160R1014,"));
+
+        let copy_dir = TempDir::new().unwrap();
+        let mut copy = set_up_portfolio(&copy_dir).await;
+        let exported_path = export_dir.join("transactions.csv");
+        let preview = copy
+            .commit_import(
+                &[exported_path.to_str().unwrap().to_string()],
+                &ApiProvider::Local,
+            )
+            .await
+            .unwrap();
+        assert_eq!(preview.count(ImportStatus::New), 5);
+        copy.set_positions().await.unwrap();
+        let ledger = async |portfolio: &Portfolio| {
+            portfolio
+                .get_ledger(&LedgerFilter::default(), LedgerSort::Date, false, 0, 50)
+                .await
+                .unwrap()
+                .entries()
+                .iter()
+                .map(|entry| {
+                    let transaction = entry.transaction();
+                    (
+                        entry.symbol().clone(),
+                        entry.alternative_symbol().clone(),
+                        *transaction.transaction_no(),
+                        *transaction.date(),
+                        transaction.transaction_type().clone(),
+                        *transaction.quantity(),
+                        *transaction.price(),
+                        *transaction.fees(),
+                        *transaction.exchange_rate(),
+                        *transaction.withholding_tax(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ledger(&copy).await, ledger(&portfolio).await);
+
+        let copy_export_dir = copy_dir.path().join("export");
+        copy.export(&copy_export_dir, ExportFormat::Json)
+            .await
+            .unwrap();
+        copy.export(&copy_export_dir, ExportFormat::Csv)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(copy_export_dir.join("transactions.csv")).unwrap(),
+            exported
+        );
+        let positions: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(copy_export_dir.join("positions.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            positions.as_array().unwrap().len(),
+            portfolio.positions().len()
+        );
+
+        // Only the import columns count towards the content hash
+        let preview = copy
+            .preview_import(
+                &[exported_path.to_str().unwrap().to_string()],
+                &ApiProvider::Local,
+            )
+            .await
+            .unwrap();
+        assert_eq!(preview.count(ImportStatus::Unchanged), 5);
+    }
+}
@@ -7,6 +7,7 @@ mod tests {
     const SYMBOL: &str = "BABA";
 
     #[tokio::test]
+    #[ignore = "requires MARKETSTACK_API_KEY and network access"]
     async fn search_symbol_works() {
         let client = Client::new();
         let api_key = std::env::var("MARKETSTACK_API_KEY").unwrap();
@@ -18,6 +19,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[ignore = "requires MARKETSTACK_API_KEY and network access"]
     async fn get_quote_works() {
         let client = Client::new();
         let api_key = std::env::var("MARKETSTACK_API_KEY").unwrap();
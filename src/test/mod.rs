@@ -1,2 +1,15 @@
 pub mod calc;
+pub mod config;
+pub mod db;
+pub mod degiro;
+pub mod ecb;
+pub mod export;
+pub mod form;
+pub mod ibkr;
+pub mod ledger;
+pub mod local;
 pub mod marketstack;
+pub mod ofx;
+pub mod profile;
+pub mod provider;
+pub mod trading212;
@@ -0,0 +1,197 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        app::{
+            calc::calculate_cash_balances,
+            importers::{
+                StatementRecord,
+                ofx::{is_ofx, isin_from_cusip, parse_ofx},
+            },
+            utils::parse_datetime,
+        },
+        models::{Transaction, TransactionType},
+    };
+
+    const STATEMENT: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<SIGNONMSGSRSV1><SONRS><STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<DTSERVER>20250630120000<LANGUAGE>ENG<FI><ORG>Fidelity<FID>7776</FI></SONRS></SIGNONMSGSRSV1>
+<INVSTMTMSGSRSV1><INVSTMTTRNRS><TRNUID>1<STATUS><CODE>0<SEVERITY>INFO</STATUS>
+<INVSTMTRS><DTASOF>20250630<CURDEF>USD
+<INVACCTFROM><BROKERID>fidelity.com<ACCTID>X1234</INVACCTFROM>
+<INVTRANLIST><DTSTART>20250101<DTEND>20250630
+<BUYSTOCK><INVBUY><INVTRAN><FITID>B-1<DTTRADE>20250115093000.000[-5:EST]</INVTRAN>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<UNITS>10<UNITPRICE>230.50<COMMISSION>4.95<FEES>0.05<TOTAL>-2310.00<SUBACCTSEC>CASH<SUBACCTFUND>CASH</INVBUY>
+<BUYTYPE>BUY</BUYSTOCK>
+<SELLSTOCK><INVSELL><INVTRAN><FITID>S-1<DTTRADE>20250303</INVTRAN>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<UNITS>-4<UNITPRICE>240<COMMISSION>4.95<TOTAL>955.05<SUBACCTSEC>CASH<SUBACCTFUND>CASH</INVSELL>
+<SELLTYPE>SELL</SELLSTOCK>
+<INCOME><INVTRAN><FITID>I-1<DTTRADE>20250515</INVTRAN>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<INCOMETYPE>DIV<TOTAL>1.50<SUBACCTSEC>CASH<SUBACCTFUND>CASH<WITHHOLDING>0.23</INCOME>
+<REINVEST><INVTRAN><FITID>R-1<DTTRADE>20250520</INVTRAN>
+<SECID><UNIQUEID>922908769<UNIQUEIDTYPE>CUSIP</SECID>
+<INCOMETYPE>DIV<TOTAL>-12.30<SUBACCTSEC>CASH<UNITS>0.041<UNITPRICE>300.00</REINVEST>
+<SPLIT><INVTRAN><FITID>SP-1<DTTRADE>20250610</INVTRAN>
+<SECID><UNIQUEID>67066G104<UNIQUEIDTYPE>CUSIP</SECID>
+<SUBACCTSEC>CASH<OLDUNITS>5<NEWUNITS>50<NUMERATOR>10<DENOMINATOR>1</SPLIT>
+<TRANSFER><INVTRAN><FITID>T-1<DTTRADE>20250611</INVTRAN>
+<SECID><UNIQUEID>67066G104<UNIQUEIDTYPE>CUSIP</SECID>
+<SUBACCTSEC>CASH<UNITS>50<TFERACTION>OUT<POSTYPE>LONG</TRANSFER>
+<TRANSFER><INVTRAN><FITID>T-2<DTTRADE>20250612</INVTRAN>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<SUBACCTSEC>CASH<UNITS>3<TFERACTION>IN<POSTYPE>LONG<AVGCOSTBASIS>150.25</TRANSFER>
+<TRANSFER><INVTRAN><FITID>T-3<DTTRADE>20250612</INVTRAN>
+<SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID>
+<SUBACCTSEC>CASH<UNITS>2<TFERACTION>IN<POSTYPE>LONG</TRANSFER>
+<INVBANKTRAN><STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20250102<TRNAMT>5000.00<FITID>D-1<NAME>Deposit &amp; more</STMTTRN>
+<SUBACCTFUND>CASH</INVBANKTRAN>
+<MARGININTEREST><INVTRAN><FITID>M-1<DTTRADE>20250612</INVTRAN><TOTAL>-3.10<SUBACCTFUND>CASH</MARGININTEREST>
+</INVTRANLIST>
+</INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1>
+<SECLISTMSGSRSV1><SECLIST>
+<STOCKINFO><SECINFO><SECID><UNIQUEID>037833100<UNIQUEIDTYPE>CUSIP</SECID><SECNAME>APPLE INC<TICKER>AAPL</SECINFO></STOCKINFO>
+<MFINFO><SECINFO><SECID><UNIQUEID>922908769<UNIQUEIDTYPE>CUSIP</SECID><SECNAME>VANGUARD TOTAL STOCK MKT<TICKER>VTI</SECINFO></MFINFO>
+</SECLIST></SECLISTMSGSRSV1>
+</OFX>
+";
+
+    fn transaction(record: &StatementRecord) -> Transaction {
+        Transaction::new(
+            0,
+            1,
+            0,
+            parse_datetime(&record.date().to_string()).unwrap(),
+            record.transaction_type().clone(),
+            record.broker().clone(),
+            record.currency().clone(),
+            dec!(1),
+            *record.quantity(),
+            *record.price(),
+            *record.fees(),
+            *record.withholding_tax(),
+            *record.domestic_tax(),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn cusips_convert_to_isins() {
+        assert_eq!(isin_from_cusip("037833100").unwrap(), "US0378331005");
+        assert_eq!(isin_from_cusip("88160R101").unwrap(), "US88160R1014");
+        assert_eq!(isin_from_cusip("67066G104").unwrap(), "US67066G1040");
+        assert!(isin_from_cusip("0378331").is_none());
+    }
+
+    #[test]
+    fn investment_statements_are_mapped_to_records() {
+        assert!(is_ofx(STATEMENT));
+        let results = parse_ofx(STATEMENT, "USD").unwrap();
+        let errors: Vec<String> = results
+            .iter()
+            .filter_map(|result| result.as_ref().err().map(|e| format!("{:#}", e)))
+            .collect();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("MARGININTEREST M-1"), "{}", errors[0]);
+        let records: Vec<_> = results.into_iter().flatten().collect();
+        assert_eq!(records.len(), 10);
+
+        let buy = &records[0];
+        assert_eq!(*buy.transaction_type(), TransactionType::Buy);
+        assert_eq!(*buy.date(), NaiveDate::from_ymd_opt(2025, 1, 15).unwrap());
+        assert_eq!(buy.symbol(), "AAPL");
+        assert_eq!(buy.alternative_symbol(), "US0378331005");
+        assert_eq!(buy.broker(), "Fidelity");
+        assert_eq!(buy.currency(), "USD");
+        assert_eq!(*buy.quantity(), dec!(10));
+        assert_eq!(*buy.fees(), dec!(5));
+
+        let sell = &records[1];
+        assert_eq!(*sell.transaction_type(), TransactionType::Sell);
+        assert_eq!(*sell.quantity(), dec!(4));
+
+        let dividend = &records[2];
+        assert_eq!(*dividend.transaction_type(), TransactionType::Div);
+        assert_eq!(*dividend.price(), dec!(1.5));
+        assert_eq!(*dividend.withholding_tax(), dec!(0.23));
+
+        let (reinvested, bought) = (&records[3], &records[4]);
+        assert_eq!(*reinvested.transaction_type(), TransactionType::Div);
+        assert_eq!(*reinvested.price(), dec!(12.3));
+        assert_eq!(*bought.transaction_type(), TransactionType::Buy);
+        assert_eq!(bought.symbol(), "VTI");
+        assert_eq!(*bought.quantity(), dec!(0.041));
+        assert_ne!(reinvested.external_id(), bought.external_id());
+
+        // Not in the security list, so named by the ISIN of its CUSIP
+        let split = &records[5];
+        assert_eq!(*split.transaction_type(), TransactionType::Split);
+        assert_eq!(split.symbol(), "US67066G1040");
+        assert_eq!(split.alternative_symbol(), "US67066G1040");
+        assert_eq!(*split.quantity(), dec!(10));
+
+        let transfer = &records[6];
+        assert_eq!(*transfer.transaction_type(), TransactionType::TransferOut);
+        assert_eq!(*transfer.quantity(), dec!(50));
+
+        // The cost basis opens the lot if no units are in transit
+        let transferred = &records[7];
+        assert_eq!(*transferred.transaction_type(), TransactionType::TransferIn);
+        assert_eq!(transferred.symbol(), "AAPL");
+        assert_eq!(*transferred.quantity(), dec!(3));
+        assert_eq!(*transferred.price(), dec!(150.25));
+        assert_eq!(*records[8].transaction_type(), TransactionType::TransferIn);
+        assert_eq!(*records[8].price(), dec!(0));
+
+        let deposit = &records[9];
+        assert_eq!(*deposit.transaction_type(), TransactionType::Deposit);
+        assert_eq!(*deposit.price(), dec!(5000));
+        assert!(deposit.symbol().is_empty());
+
+        // Transfers move no cash: the deposit less the purchases, plus the
+        // sale and the dividends
+        let transactions: Vec<Transaction> = records.iter().map(transaction).collect();
+        let balances = calculate_cash_balances(&transactions);
+        assert_eq!(balances.len(), 1);
+        assert_eq!(*balances[0].balance(), dec!(3646.32));
+    }
+
+    #[test]
+    fn fees_must_be_in_the_base_currency() {
+        let results = parse_ofx(STATEMENT, "EUR").unwrap();
+        let error = format!("{:#}", results[0].as_ref().unwrap_err());
+        assert!(error.contains("Cannot convert the fees"), "{}", error);
+
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX><INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS><CURDEF>EUR</CURDEF>
+<INVTRANLIST><BUYSTOCK><INVBUY><INVTRAN><FITID>X1</FITID><DTTRADE>20250115</DTTRADE></INVTRAN>
+<SECID><UNIQUEID>US0378331005</UNIQUEID><UNIQUEIDTYPE>ISIN</UNIQUEIDTYPE></SECID>
+<UNITS>2</UNITS><UNITPRICE>230</UNITPRICE><COMMISSION>1</COMMISSION>
+<CURRENCY><CURRATE>0.95</CURRATE><CURSYM>USD</CURSYM></CURRENCY></INVBUY><BUYTYPE>BUY</BUYTYPE></BUYSTOCK>
+</INVTRANLIST></INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1></OFX>"#;
+        let records: Vec<_> = parse_ofx(xml, "EUR")
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records[0].symbol(), "US0378331005");
+        assert_eq!(records[0].alternative_symbol(), "US0378331005");
+        assert_eq!(records[0].currency(), "USD");
+        assert_eq!(*records[0].fees(), dec!(0.95));
+        assert_eq!(
+            records[0].exchange_rate().unwrap().round_dp(4),
+            dec!(1.0526)
+        );
+        assert_eq!(records[0].broker(), "OFX");
+    }
+}
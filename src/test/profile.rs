@@ -0,0 +1,125 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        app::{
+            Config,
+            importers::profile::{detect_profile, parse_export},
+        },
+        models::TransactionType,
+    };
+
+    const PROFILES: &str = r#"
+        [import_profiles.comdirect]
+        delimiter = ";"
+        decimal_separator = ","
+        date_format = "%d.%m.%Y"
+        broker = "Comdirect"
+
+        [import_profiles.comdirect.columns]
+        date = "Datum"
+        transaction_type = "Geschäftsart"
+        symbol = "Symbol"
+        alternative_symbol = "ISIN"
+        quantity = "Stück"
+        price = "Kurs"
+        fees = "Provision"
+        transaction_currency = "Währung"
+
+        [import_profiles.comdirect.types]
+        Kauf = "Buy"
+        Verkauf = "Sell"
+        Einzahlung = "Deposit"
+        Dividende = "Div"
+    "#;
+
+    const EXPORT: &str = "\u{feff}Datum;Geschäftsart;Symbol;ISIN;Stück;Kurs;Provision;Währung\n\
+        12.02.2025;Kauf;tsla;US88160R1014;10;330,00;9,90;USD\n\
+        31.03.2025;verkauf;TSLA;US88160R1014;-4;1.259,16;-4,90;USD\n\
+        31.03.2025;verkauf;TSLA;US88160R1014;-4;1.259,16;-4,90;USD\n\
+        01.04.2025;Einzahlung;;;;500;;EUR\n\
+        02.04.2025;Dividende;TSLA;US88160R1014;;2,50;;USD\n\
+        02.04.2025;Umbuchung;TSLA;;1;1;;USD\n";
+
+    #[test]
+    fn exports_are_read_with_the_matching_profile() {
+        let config = Config::parse_str(PROFILES).unwrap();
+        let profile = detect_profile(EXPORT, config.import_profiles()).unwrap();
+        assert!(detect_profile("date;type;price\n", config.import_profiles()).is_none());
+
+        let results = parse_export(EXPORT, profile).unwrap();
+        assert_eq!(results.len(), 6);
+        let error = format!("{:#}", results[5].as_ref().unwrap_err());
+        assert!(error.contains("Umbuchung"), "{}", error);
+        let records: Vec<_> = results.into_iter().flatten().collect();
+
+        let buy = &records[0];
+        assert_eq!(*buy.date(), NaiveDate::from_ymd_opt(2025, 2, 12).unwrap());
+        assert_eq!(*buy.transaction_type(), TransactionType::Buy);
+        assert_eq!(buy.symbol(), "TSLA");
+        assert_eq!(buy.alternative_symbol(), "US88160R1014");
+        assert_eq!(buy.broker(), "Comdirect");
+        assert_eq!(*buy.price(), dec!(330));
+        assert_eq!(*buy.fees(), dec!(9.9));
+        assert!(buy.external_id().starts_with("Comdirect:"));
+
+        let sell = &records[1];
+        assert_eq!(*sell.transaction_type(), TransactionType::Sell);
+        assert_eq!(*sell.quantity(), dec!(4));
+        assert_eq!(*sell.price(), dec!(1259.16));
+        assert_eq!(*sell.fees(), dec!(4.9));
+        // Identical rows are distinct transactions
+        assert_ne!(sell.external_id(), records[2].external_id());
+
+        let deposit = &records[3];
+        assert_eq!(*deposit.transaction_type(), TransactionType::Deposit);
+        assert_eq!(*deposit.quantity(), dec!(1));
+        assert_eq!(*deposit.price(), dec!(500));
+
+        let dividend = &records[4];
+        assert_eq!(*dividend.transaction_type(), TransactionType::Div);
+        assert_eq!(*dividend.quantity(), dec!(1));
+        assert_eq!(*dividend.price(), dec!(2.5));
+
+        // Ids stay the same when the export is read again, also with
+        // corrected amounts
+        let again: Vec<_> = parse_export(EXPORT, profile)
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(records, again);
+        let corrected: Vec<_> = parse_export(&EXPORT.replace("330,00", "331,00"), profile)
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(*corrected[0].price(), dec!(331));
+        assert_eq!(corrected[0].external_id(), buy.external_id());
+    }
+
+    #[test]
+    fn invalid_profiles_are_rejected() {
+        for (profile, expected) in [
+            (
+                "decimal_separator = \";\"\nbroker = \"X\"\n[import_profiles.x.columns]\ndate = \"d\"\ntransaction_type = \"t\"\nprice = \"p\"",
+                "decimal separator",
+            ),
+            (
+                "[import_profiles.x.columns]\ndate = \"d\"\ntransaction_type = \"t\"\nprice = \"p\"",
+                "broker",
+            ),
+            (
+                "broker = \"X\"\n[import_profiles.x.columns]\ndate = \"d\"\ntransaction_type = \"t\"\nprice = \"p\"\n[import_profiles.x.types]\nKauf = \"Purchase\"",
+                "Purchase",
+            ),
+        ] {
+            let error =
+                Config::parse_str(&format!("[import_profiles.x]\n{}", profile)).unwrap_err();
+            let error = format!("{:#}", error);
+            assert!(error.contains(expected), "{} not in '{}'", expected, error);
+        }
+    }
+}
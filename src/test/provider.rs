@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use chrono::{DateTime, Local};
+    use reqwest::Client;
+    use rust_decimal_macros::dec;
+
+    use sqlx::SqlitePool;
+
+    use crate::{
+        api::provider::{ProviderRegistry, QuoteProvider},
+        app::{Config, Portfolio},
+        models::{Asset, AssetType, Quote, Ticker, ticker::ApiProvider},
+    };
+
+    struct InHouseProvider;
+
+    #[async_trait]
+    impl QuoteProvider for InHouseProvider {
+        fn api(&self) -> ApiProvider {
+            ApiProvider::Custom(String::from("In-House"))
+        }
+
+        async fn search_symbol(&self, symbol: &str, _client: &Client) -> Result<Ticker> {
+            Ok(Ticker::new(
+                0,
+                0,
+                symbol.to_string(),
+                String::from("In-House Asset"),
+                String::from("EUR"),
+                None,
+                None,
+                None,
+                self.api(),
+            ))
+        }
+
+        async fn get_latest_quote(&self, symbol: &str, _client: &Client) -> Result<Quote> {
+            Ok(Quote::new(
+                symbol.to_string(),
+                Local::now(),
+                dec!(42),
+                dec!(42),
+            ))
+        }
+
+        async fn get_quote_history(
+            &self,
+            _symbol: &str,
+            _start_date: &DateTime<Local>,
+            _end_date: &DateTime<Local>,
+            _client: &Client,
+        ) -> Result<Vec<Quote>> {
+            Ok(Vec::new())
+        }
+
+        async fn get_metadata(&self, _symbol: &str, _client: &Client) -> Result<Asset> {
+            Ok(Asset::new(
+                0,
+                String::from("In-House Asset"),
+                AssetType::Other,
+                None,
+                None,
+                None,
+            ))
+        }
+    }
+
+    #[test]
+    fn registry_contains_builtin_providers() {
+        let registry = ProviderRegistry::with_defaults(None, None, None, None);
+
+        assert_eq!(
+            registry.apis(),
+            vec![
+                ApiProvider::AlphaVantage,
+                ApiProvider::Fmp,
+                ApiProvider::Marketstack,
+                ApiProvider::Local
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn registry_resolves_custom_provider() {
+        let mut registry = ProviderRegistry::with_defaults(None, None, None, None);
+        registry.register(Arc::new(InHouseProvider));
+        registry.register(Arc::new(InHouseProvider));
+
+        assert_eq!(registry.len(), 5);
+
+        assert!(ApiProvider::parse_str("In-House").is_err());
+        assert!(registry.parse_api("Marketstak").is_err());
+        let api = registry.parse_api("In-House").unwrap();
+        let provider = registry.get(&api).unwrap();
+        let quote = provider
+            .get_latest_quote("XYZ", &Client::new())
+            .await
+            .unwrap();
+
+        assert_eq!(*quote.close(), dec!(42));
+    }
+
+    #[tokio::test]
+    async fn default_api_may_name_a_custom_provider() {
+        let config = |default_api: &str| {
+            Config::parse_str(&format!("[providers]\ndefault_api = \"{}\"", default_api)).unwrap()
+        };
+        let connection = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+
+        let portfolio = Portfolio::from_config_with_providers(
+            &config("In-House"),
+            connection.clone(),
+            vec![Arc::new(InHouseProvider)],
+        )
+        .unwrap();
+        assert_eq!(
+            *portfolio.default_api(),
+            ApiProvider::Custom(String::from("In-House"))
+        );
+
+        let error = Portfolio::from_config(&config("Marketstak"), connection).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("Unknown default_api 'Marketstak'"),
+            "{:#}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn builtin_provider_requires_api_key() {
+        let registry = ProviderRegistry::with_defaults(None, None, None, None);
+        let provider = registry.get(&ApiProvider::Fmp).unwrap();
+
+        assert!(
+            provider
+                .get_latest_quote("AAPL", &Client::new())
+                .await
+                .is_err()
+        );
+    }
+}
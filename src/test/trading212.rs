@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use rust_decimal_macros::dec;
+
+    use crate::{
+        app::importers::trading212::{is_history, parse_history},
+        models::TransactionType,
+    };
+
+    const HISTORY: &str = "\
+Action,Time,ISIN,Ticker,Name,No. of shares,Price / share,Currency (Price / share),Exchange rate,Result,Currency (Result),Total,Currency (Total),Withholding tax,Currency (Withholding tax),Currency conversion fee,Currency (Currency conversion fee),ID
+Deposit,2025-01-02 09:00:00,,,,,,,,,,1000.00,EUR,,,,,d-1
+Currency conversion,2025-01-14 10:00:00,,,,,,,,,,500.00,EUR,,,,,c-1
+Market buy,2025-01-15 15:31:02.123,US0378331005,AAPL,Apple,2.5,230.00,USD,1.0250,,,561.71,EUR,,,0.86,EUR,EOF101
+Limit sell,2025-03-03 15:31:02,US0378331005,AAPL,Apple,1,240.00,USD,Not available,1.20,EUR,230.00,EUR,,,,,EOF102
+Dividend (Dividend),2025-05-15 07:30:00,US0378331005,AAPL,Apple,1.5,0.25,USD,1.1200,,,0.28,EUR,0.06,USD,,,
+Interest on cash,2025-05-31 23:00:00,,,,,,,,,,0.42,EUR,,,,,
+Card debit,2025-06-01 12:00:00,,,,,,,,,,-12.00,EUR,,,,,
+";
+
+    #[test]
+    fn history_maps_trades_with_their_fees_and_rates() {
+        assert!(is_history(HISTORY));
+        assert!(!is_history("Action,Time\n"));
+
+        let results = parse_history(HISTORY, "EUR").unwrap();
+        assert_eq!(results.len(), 6);
+        let error = format!("{:#}", results[5].as_ref().unwrap_err());
+        assert!(error.contains("Card debit"), "{}", error);
+        let records: Vec<_> = results.into_iter().flatten().collect();
+
+        let deposit = &records[0];
+        assert_eq!(*deposit.transaction_type(), TransactionType::Deposit);
+        assert_eq!(*deposit.price(), dec!(1000));
+        assert_eq!(deposit.currency(), "EUR");
+
+        let buy = &records[1];
+        assert_eq!(*buy.transaction_type(), TransactionType::Buy);
+        assert_eq!(*buy.date(), NaiveDate::from_ymd_opt(2025, 1, 15).unwrap());
+        assert_eq!(buy.symbol(), "AAPL");
+        assert_eq!(buy.alternative_symbol(), "US0378331005");
+        assert_eq!(*buy.quantity(), dec!(2.5));
+        assert_eq!(*buy.fees(), dec!(0.86));
+        assert_eq!(*buy.exchange_rate(), Some(dec!(1.025)));
+
+        let sell = &records[2];
+        assert_eq!(*sell.transaction_type(), TransactionType::Sell);
+        assert_eq!(*sell.exchange_rate(), None);
+
+        let dividend = &records[3];
+        assert_eq!(*dividend.transaction_type(), TransactionType::Div);
+        assert_eq!(*dividend.quantity() * *dividend.price(), dec!(0.375));
+        assert_eq!(*dividend.withholding_tax(), dec!(0.06));
+        assert_ne!(dividend.external_id(), records[4].external_id());
+
+        assert_eq!(*records[4].transaction_type(), TransactionType::Interest);
+    }
+}